@@ -78,12 +78,12 @@ fn main() -> Result<(), heed::BoxedError> {
     let mut split_nodes_sum = 0;
     let mut descendants_sum = 0;
 
-    let Stats { tree_stats, leaf } = reader.stats(&wtxn)?;
+    let Stats { tree_stats, leaf, .. } = reader.stats(&wtxn)?;
     let nb_roots = tree_stats.len();
     println!("There are {nb_roots} trees in this arroy index for a total of {leaf} leaf.");
 
     #[allow(clippy::unused_enumerate_index)]
-    for (_i, TreeStats { depth, dummy_normals, split_nodes, descendants }) in
+    for (_i, TreeStats { depth, dummy_normals, split_nodes, descendants, .. }) in
         tree_stats.into_iter().enumerate()
     {
         depth_sum += depth;
@@ -68,7 +68,7 @@ fn main() -> Result<()> {
             instant = Instant::now();
             smol_iterations = 0;
 
-            if timer.map_or(false, |duration| duration < total_duration.elapsed()) {
+            if timer.is_some_and(|duration| duration < total_duration.elapsed()) {
                 return Ok(());
             }
         }
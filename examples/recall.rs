@@ -0,0 +1,90 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::time::Instant;
+
+use arroy::distances::Euclidean;
+use arroy::{read_ivecs, Benchmark, Database, GroundTruth, Reader, VectorReader, Writer};
+use clap::Parser;
+use heed::EnvOpenOptions;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+const TWENTY_HUNDRED_MIB: usize = 2 * 1024 * 1024 * 1024;
+
+/// Measures the recall of arroy on a dataset like the ones of <http://corpus-texmex.irisa.fr>.
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+struct Cli {
+    /// The vectors to index, in the `.fvecs`, `.bvecs` or `.npy` format.
+    base: PathBuf,
+    /// The vectors to search for, the first items of the base are used by default.
+    #[arg(long)]
+    queries: Option<PathBuf>,
+    /// The exact neighbors of the queries in the `.ivecs` format, computed by default.
+    #[arg(long, requires = "queries")]
+    ground_truth: Option<PathBuf>,
+    /// The number of queries to run.
+    #[arg(long, default_value_t = 100)]
+    n_queries: usize,
+    /// The number of neighbors to search for.
+    #[arg(long, default_value_t = 10)]
+    count: usize,
+    #[arg(long, value_delimiter = ',', default_value = "1,10,50,100")]
+    n_trees: Vec<usize>,
+    #[arg(long, value_delimiter = ',', default_value = "100,1000,10000")]
+    search_k: Vec<NonZeroUsize>,
+    #[arg(long, value_delimiter = ',', default_value = "1")]
+    oversampling: Vec<NonZeroUsize>,
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    let dir = tempfile::tempdir()?;
+    let env = unsafe { EnvOpenOptions::new().map_size(TWENTY_HUNDRED_MIB).open(dir.path()) }?;
+    let mut wtxn = env.write_txn()?;
+    let database: Database<Euclidean> = env.create_database(&mut wtxn, None)?;
+
+    let vectors = VectorReader::open(&cli.base)?;
+    let dimensions = vectors.dimensions().unwrap_or_default();
+    let writer = Writer::new(database, 0, dimensions);
+    let before = Instant::now();
+    let n_items = writer.import_vectors(&mut wtxn, vectors, 0)?;
+    writer.builder(&mut StdRng::seed_from_u64(42)).n_trees(1).build(&mut wtxn)?;
+    eprintln!("took {:.02?} to import {n_items} vectors", before.elapsed());
+
+    let before = Instant::now();
+    let reader = Reader::open(&wtxn, 0, database)?;
+    let ground_truth = match (&cli.queries, &cli.ground_truth) {
+        (Some(queries), Some(neighbors)) => {
+            let queries =
+                VectorReader::open(queries)?.take(cli.n_queries).collect::<Result<_, _>>()?;
+            let mut neighbors = read_ivecs(BufReader::new(File::open(neighbors)?))?;
+            neighbors.truncate(cli.n_queries);
+            GroundTruth::new(queries, neighbors, cli.count)?
+        }
+        (Some(queries), None) => {
+            let queries =
+                VectorReader::open(queries)?.take(cli.n_queries).collect::<Result<_, _>>()?;
+            GroundTruth::compute(&wtxn, &reader, queries, cli.count, None)?
+        }
+        (None, _) => {
+            GroundTruth::from_items(&wtxn, &reader, 0..cli.n_queries as u32, cli.count, None)?
+        }
+    };
+    drop(reader);
+    eprintln!("took {:.02?} to get the ground truth", before.elapsed());
+
+    let mut benchmark = Benchmark::new(&ground_truth);
+    benchmark
+        .n_trees(cli.n_trees)
+        .search_k(cli.search_k.into_iter().map(Some))
+        .oversampling(cli.oversampling.into_iter().map(Some));
+    for result in benchmark.run_with_builds(&mut wtxn, &writer, &mut StdRng::seed_from_u64(42))? {
+        println!("{result}");
+    }
+
+    Ok(())
+}
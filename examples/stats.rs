@@ -2,7 +2,7 @@ use std::fs;
 use std::path::PathBuf;
 
 use arroy::distances::DotProduct;
-use arroy::{Database, Reader, Stats, TreeStats};
+use arroy::{Database, Reader, SplitLevelStats, Stats, TreeStats};
 use clap::Parser;
 use heed::EnvOpenOptions;
 
@@ -28,19 +28,34 @@ fn main() -> anyhow::Result<()> {
     let mut depth_sum = 0;
     let mut split_nodes_sum = 0;
     let mut descendants_sum = 0;
+    let mut descendants_bytes_sum = 0;
+    let mut descendants_roaring_bytes_sum = 0;
 
-    let Stats { tree_stats, leaf } = reader.stats(&rtxn)?;
+    let Stats { tree_stats, leaf, bytes, descendants_sizes, leaf_depths, split_levels } =
+        reader.stats(&rtxn)?;
     let nb_roots = tree_stats.len();
     println!("There are {nb_roots} trees in this arroy index for a total of {leaf} leaf.");
 
     #[allow(clippy::unused_enumerate_index)]
-    for (_i, TreeStats { depth, dummy_normals, split_nodes, descendants }) in
-        tree_stats.into_iter().enumerate()
+    for (
+        _i,
+        TreeStats {
+            depth,
+            dummy_normals,
+            split_nodes,
+            descendants,
+            descendants_bytes,
+            descendants_roaring_bytes,
+            items: _,
+        },
+    ) in tree_stats.into_iter().enumerate()
     {
         depth_sum += depth;
         dummy_sum += dummy_normals;
         split_nodes_sum += split_nodes;
         descendants_sum += descendants;
+        descendants_bytes_sum += descendants_bytes;
+        descendants_roaring_bytes_sum += descendants_roaring_bytes;
 
         // println!("Tree {_i} as a depth of {depth}, {split_nodes} split nodes, {dummy_normals} dummy normals ({}%), and {descendants} descendants.", dummy_normals as f64 / split_nodes as f64 * 100.);
     }
@@ -55,6 +70,11 @@ fn main() -> anyhow::Result<()> {
         dummy_sum as f64 / split_nodes_sum as f64 * 100.
     );
     println!("\tdescendants:\t\t{:.2}", descendants_sum as f64 / nb_roots as f64);
+    println!(
+        "\tdescendants bytes:\t{:.2} ({:.2}% smaller than roaring bitmaps)",
+        descendants_bytes_sum as f64 / nb_roots as f64,
+        (1. - descendants_bytes_sum as f64 / descendants_roaring_bytes_sum as f64) * 100.
+    );
     println!();
     println!(
         "That makes a total of: {} tree nodes. {:.2}% of all the nodes",
@@ -64,5 +84,30 @@ fn main() -> anyhow::Result<()> {
             * 100.,
     );
 
+    println!();
+    println!("The index takes {} bytes:", bytes.total());
+    println!("\tleafs:\t\t\t{}", bytes.leafs);
+    println!("\tsplit normals:\t\t{}", bytes.split_normals);
+    println!("\tdescendants:\t\t{}", bytes.descendants);
+    println!("\tmetadata:\t\t{}", bytes.metadata);
+
+    println!();
+    println!("Number of descendants nodes by number of items:");
+    for (size, count) in descendants_sizes {
+        println!("\t{size}:\t{count}");
+    }
+    println!("Number of items by depth:");
+    for (depth, count) in leaf_depths {
+        println!("\t{depth}:\t{count}");
+    }
+    println!("Imbalance of the split nodes by level:");
+    for (level, SplitLevelStats { split_nodes, mean_imbalance, max_imbalance }) in
+        split_levels.iter().enumerate()
+    {
+        println!(
+            "\t{level}:\t{split_nodes} nodes, mean {mean_imbalance:.2}, max {max_imbalance:.2}"
+        );
+    }
+
     Ok(())
 }
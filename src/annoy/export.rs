@@ -0,0 +1,218 @@
+use std::io::{self, Write};
+
+use heed::RoTxn;
+use roaring::RoaringBitmap;
+
+use super::{AnnoyDistance, NodeLayout};
+use crate::error::KeyContext;
+use crate::node::{Descendants, SplitPlaneNormal};
+use crate::{Database, Error, ItemId, Key, Node, NodeId, NodeMode, Result};
+
+/// Writes the items and the trees of an index in the Annoy file format.
+///
+/// The file is an array of nodes of the same size, in the native little-endian
+/// order of Annoy. The item `i` is the node `i`, the IDs missing from the index are
+/// zeroed nodes. The tree nodes follow, every tree written from its leafs to its
+/// root, then a copy of every root that Annoy looks for at the end of the file.
+pub(crate) fn export<D: AnnoyDistance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+    dimensions: usize,
+    roots: &[ItemId],
+    items: &RoaringBitmap,
+    writer: impl Write,
+) -> Result<()> {
+    // Annoy stores the IDs and the number of descendants as signed 32 bits integers
+    let n_items = match items.max() {
+        Some(max) if max >= i32::MAX as u32 => {
+            return Err(Error::AnnoyExport {
+                index,
+                reason: "the item IDs must be lower than 2^31 - 1",
+            })
+        }
+        Some(max) => max + 1,
+        None => 0,
+    };
+
+    let mut exporter = Exporter {
+        rtxn,
+        database,
+        index,
+        dimensions,
+        layout: D::METRIC.layout(dimensions),
+        n_items,
+        n_nodes: 0,
+        node: Vec::new(),
+        writer: io::BufWriter::new(writer),
+    };
+
+    for item in 0..n_items {
+        if items.contains(item) {
+            let key = Key::item(index, item);
+            let leaf = match database.get(rtxn, &key).with_key(key)? {
+                Some(Node::Leaf(leaf)) => leaf,
+                Some(_) => return Err(Error::corrupted_database(key, "expected an item")),
+                None => return Err(Error::missing_key(key)),
+            };
+            exporter.write_node(1, &[], D::annoy_extra(&leaf.header), &leaf.vector.to_vec())?;
+        } else {
+            exporter.write_node(0, &[], 0.0, &[])?;
+        }
+    }
+
+    let mut root_nodes = Vec::with_capacity(roots.len());
+    for &root in roots {
+        exporter.export_tree(root, items)?;
+        root_nodes.push(exporter.node.clone());
+    }
+
+    for root_node in root_nodes {
+        exporter.writer.write_all(&root_node)?;
+    }
+    exporter.writer.flush()?;
+
+    Ok(())
+}
+
+struct Exporter<'t, 'e, D: AnnoyDistance, W: Write> {
+    rtxn: &'t RoTxn<'e>,
+    database: Database<D>,
+    index: u16,
+    dimensions: usize,
+    layout: NodeLayout,
+    n_items: u32,
+    /// The number of nodes written so far, the index of the next one.
+    n_nodes: u32,
+    /// The last node written.
+    node: Vec<u8>,
+    writer: io::BufWriter<W>,
+}
+
+impl<D: AnnoyDistance, W: Write> Exporter<'_, '_, D, W> {
+    /// Writes a tree and returns the index of its root. Annoy finds the roots
+    /// by looking for the nodes with `n_items` descendants.
+    fn export_tree(&mut self, root: ItemId, items: &RoaringBitmap) -> Result<u32> {
+        if self.n_items as usize <= self.max_descendants() {
+            // Such a root would be read as a list of IDs, we store all the
+            // items in it and repeat some of them, Annoy deduplicates the results.
+            let ids: Vec<_> = items.iter().cycle().take(self.n_items as usize).collect();
+            return self.write_node(self.n_items, &ids, 0.0, &[]);
+        }
+
+        let (node, _) = self.export_node(NodeId::tree(root), Some(self.n_items))?;
+        Ok(node)
+    }
+
+    /// Writes the node and its children, returns its index and its number of descendants.
+    /// The number of descendants of the split plane nodes is forced to `n_descendants`.
+    fn export_node(&mut self, node_id: NodeId, n_descendants: Option<u32>) -> Result<(u32, u32)> {
+        match node_id.mode {
+            NodeMode::Item if n_descendants.is_none() => return Ok((node_id.item, 1)),
+            NodeMode::Item => return self.export_descendants(&[node_id.item], n_descendants),
+            NodeMode::Tree => (),
+            NodeMode::Metadata | NodeMode::Updated => {
+                return Err(Error::corrupted_database(
+                    Key::new(self.index, node_id),
+                    "expected a tree node",
+                ))
+            }
+        }
+
+        let key = Key::new(self.index, node_id);
+        match self.database.get(self.rtxn, &key).with_key(key)?.ok_or(Error::missing_key(key))? {
+            Node::Leaf(_) => Err(Error::corrupted_database(key, "expected a tree node")),
+            Node::Descendants(Descendants { descendants }) => {
+                let ids: Vec<_> = descendants.iter().collect();
+                self.export_descendants(&ids, n_descendants)
+            }
+            Node::SplitPlaneNormal(SplitPlaneNormal { left, right, normal, .. }) => {
+                // The split plane may have been computed on the first dimensions only
+                let normal = normal.to_vec();
+                let (left, n_left) = self.export_node(left, None)?;
+                let (right, n_right) = self.export_node(right, None)?;
+                self.write_split(n_descendants.unwrap_or(n_left + n_right), left, right, &normal)
+            }
+        }
+    }
+
+    /// Writes the IDs in a node, or in a tree of nodes with empty split
+    /// planes if they don't fit in one, and returns its index.
+    fn export_descendants(
+        &mut self,
+        ids: &[ItemId],
+        n_descendants: Option<u32>,
+    ) -> Result<(u32, u32)> {
+        let len = ids.len() as u32;
+        if ids.len() > self.max_descendants() {
+            let (left, right) = ids.split_at(ids.len() / 2);
+            let (left, _) = self.export_descendants(left, None)?;
+            let (right, _) = self.export_descendants(right, None)?;
+            self.write_split(n_descendants.unwrap_or(len), left, right, &[])
+        } else if let Some(n_descendants) = n_descendants {
+            // Both sides of an empty split plane are explored
+            let (node, _) = self.export_descendants(ids, None)?;
+            self.write_split(n_descendants, node, node, &[])
+        } else {
+            Ok((self.write_node(len, ids, 0.0, &[])?, len))
+        }
+    }
+
+    fn write_split(
+        &mut self,
+        n_descendants: u32,
+        left: u32,
+        right: u32,
+        normal: &[f32],
+    ) -> Result<(u32, u32)> {
+        // Annoy reads the nodes with few descendants as a list of IDs
+        let n_descendants = n_descendants.max(self.max_descendants() as u32 + 1);
+        Ok((self.write_node(n_descendants, &[left, right], 0.0, normal)?, n_descendants))
+    }
+
+    fn write_node(
+        &mut self,
+        n_descendants: u32,
+        children: &[u32],
+        extra: f32,
+        vector: &[f32],
+    ) -> Result<u32> {
+        if self.n_nodes >= i32::MAX as u32 {
+            return Err(Error::AnnoyExport {
+                index: self.index,
+                reason: "Annoy files cannot contain more than 2^31 - 1 nodes",
+            });
+        }
+
+        let NodeLayout {
+            size,
+            children: children_offset,
+            extra: extra_offset,
+            vector: vector_offset,
+        } = self.layout;
+        self.node.clear();
+        self.node.resize(size, 0);
+        self.node[..4].copy_from_slice(&n_descendants.to_le_bytes());
+        let vector = vector.iter().take(self.dimensions);
+        for (bytes, value) in self.node[vector_offset..].chunks_exact_mut(4).zip(vector) {
+            bytes.copy_from_slice(&value.to_le_bytes());
+        }
+        if children.is_empty() {
+            // The extra field of the angular metric shares its bytes with the children
+            self.node[extra_offset..extra_offset + 4].copy_from_slice(&extra.to_le_bytes());
+        } else {
+            // The IDs of a list of descendants overflow on the following fields
+            for (bytes, id) in self.node[children_offset..].chunks_exact_mut(4).zip(children) {
+                bytes.copy_from_slice(&id.to_le_bytes());
+            }
+        }
+
+        self.writer.write_all(&self.node)?;
+        self.n_nodes += 1;
+        Ok(self.n_nodes - 1)
+    }
+
+    fn max_descendants(&self) -> usize {
+        self.layout.max_descendants()
+    }
+}
@@ -0,0 +1,117 @@
+use std::borrow::Cow;
+
+use heed::RwTxn;
+use roaring::RoaringBitmap;
+
+use super::{AnnoyDistance, AnnoyFile, AnnoyMetric};
+use crate::node::{Descendants, Leaf, SplitPlaneNormal};
+use crate::unaligned_vector::UnalignedVector;
+use crate::{Database, Error, ItemId, Key, Node, NodeId, NormalPrecision, Result};
+
+/// Writes the items and the trees of an Annoy file in an empty index,
+/// returns the items and the roots of the trees to write in the metadata.
+///
+/// The item `i` of Annoy is the item `i` of arroy. The split nodes and the descendants
+/// nodes of Annoy are converted one by one, keeping the shape of the trees.
+pub(crate) fn import<D: AnnoyDistance>(
+    wtxn: &mut RwTxn,
+    database: Database<D>,
+    index: u16,
+    dimensions: usize,
+    bytes: &[u8],
+) -> Result<(RoaringBitmap, Vec<ItemId>)> {
+    let invalid = |reason| Error::AnnoyImport { index, reason };
+    let file = AnnoyFile::new(D::METRIC, dimensions, bytes).ok_or(invalid(
+        "the size of the file is not a multiple of the size of the nodes of these dimensions",
+    ))?;
+    if file.n_items > file.n_nodes() {
+        return Err(invalid("the roots have more descendants than the number of nodes"));
+    }
+
+    log::debug!("importing the items of the Annoy file...");
+    let mut items = RoaringBitmap::new();
+    for i in (0..file.n_items).filter(|&i| file.is_item(i)) {
+        let vector = UnalignedVector::from_vec(file.vector(i));
+        let header = D::annoy_header(&vector, file.extra(i));
+        database.put(wtxn, &Key::item(index, i as ItemId), &Node::Leaf(Leaf { header, vector }))?;
+        items.push(i as ItemId);
+    }
+
+    log::debug!("importing the {} trees of the Annoy file...", file.roots.len());
+    let mut roots = Vec::with_capacity(file.roots.len());
+    let mut visited = RoaringBitmap::new();
+    let mut n_tree_nodes = 0;
+    let mut plane_offsets = 0;
+    for &root in &file.roots {
+        let mut explore = vec![(root, n_tree_nodes)];
+        roots.push(n_tree_nodes);
+        n_tree_nodes += 1;
+
+        while let Some((i, tree_node)) = explore.pop() {
+            if !visited.insert(i as u32) {
+                return Err(invalid("a node is linked more than once"));
+            }
+
+            let node = if file.is_item(i) {
+                // Only a root can be a single item, arroy needs a tree node
+                Node::Descendants(Descendants { descendants: Cow::Owned(items_of(&[i])) })
+            } else if file.is_descendants(i) {
+                let ids: Vec<_> = file.children(i).collect();
+                let descendants = items_of(&ids);
+                if !descendants.is_subset(&items) {
+                    return Err(invalid("a node contains IDs that are not items"));
+                }
+                Node::Descendants(Descendants { descendants: Cow::Owned(descendants) })
+            } else {
+                let [left, right] = [0, 1].map(|n| file.children(i).nth(n).unwrap());
+                if left >= file.n_nodes() || right >= file.n_nodes() {
+                    return Err(invalid("a split node has children out of the file"));
+                }
+                if left == right {
+                    // Both sides lead to the same node, we can skip this split node
+                    visited.remove(i as u32);
+                    explore.push((left, tree_node));
+                    continue;
+                }
+
+                let is_minkowski =
+                    matches!(D::METRIC, AnnoyMetric::Euclidean | AnnoyMetric::Manhattan);
+                if is_minkowski && file.extra(i) != 0.0 {
+                    plane_offsets += 1;
+                }
+
+                let mut children = [left, right].map(|child| NodeId::item(child as ItemId));
+                for (node_id, child) in children.iter_mut().zip([left, right]) {
+                    if !file.is_item(child) {
+                        explore.push((child, n_tree_nodes));
+                        *node_id = NodeId::tree(n_tree_nodes);
+                        n_tree_nodes += 1;
+                    }
+                }
+                let [left, right] = children;
+                Node::SplitPlaneNormal(SplitPlaneNormal {
+                    left,
+                    right,
+                    normal: UnalignedVector::from_vec(file.vector(i)),
+                    precision: NormalPrecision::Full,
+                })
+            };
+
+            database.put(wtxn, &Key::tree(index, tree_node), &node)?;
+        }
+    }
+
+    if plane_offsets > 0 {
+        log::warn!(
+            "{plane_offsets} split planes of the Annoy file have an offset that arroy ignores, \
+             the trees must be built again to search as precisely as Annoy"
+        );
+    }
+
+    Ok((items, roots))
+}
+
+/// Returns the bitmap of the IDs, the duplicates are ignored.
+fn items_of(ids: &[usize]) -> RoaringBitmap {
+    ids.iter().map(|&id| id as ItemId).collect()
+}
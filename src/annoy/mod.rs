@@ -0,0 +1,233 @@
+use std::mem::size_of;
+
+use crate::distance::{
+    Cosine, DotProduct, Euclidean, Manhattan, NodeHeaderCosine, NodeHeaderDotProduct,
+    NodeHeaderEuclidean, NodeHeaderManhattan,
+};
+use crate::spaces::simple::dot_product;
+use crate::unaligned_vector::UnalignedVector;
+use crate::Distance;
+
+mod export;
+mod import;
+
+pub(crate) use export::export;
+pub(crate) use import::import;
+
+/// The metrics of [Annoy](https://github.com/spotify/annoy), the ones
+/// given to the `AnnoyIndex` constructor of the Python library.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnnoyMetric {
+    /// The `angular` metric, the equivalent of [`Cosine`].
+    Angular,
+    /// The `euclidean` metric, the equivalent of [`Euclidean`].
+    Euclidean,
+    /// The `manhattan` metric, the equivalent of [`Manhattan`].
+    Manhattan,
+    /// The `dot` metric, the equivalent of [`DotProduct`].
+    Dot,
+}
+
+impl AnnoyMetric {
+    /// Returns the name of the metric in Annoy.
+    pub fn name(&self) -> &'static str {
+        match self {
+            AnnoyMetric::Angular => "angular",
+            AnnoyMetric::Euclidean => "euclidean",
+            AnnoyMetric::Manhattan => "manhattan",
+            AnnoyMetric::Dot => "dot",
+        }
+    }
+
+    /// Returns the layout of the nodes of this metric for vectors of `dimensions`.
+    pub(crate) fn layout(&self, dimensions: usize) -> NodeLayout {
+        // Every field of an Annoy node is a 32 bits integer or float
+        const FIELD: usize = size_of::<u32>();
+
+        let (children, extra, vector) = match self {
+            // n_descendants, union { children[2], norm }, v[dimensions]
+            AnnoyMetric::Angular => (FIELD, FIELD, 3 * FIELD),
+            // n_descendants, a, children[2], v[dimensions]
+            AnnoyMetric::Euclidean | AnnoyMetric::Manhattan => (2 * FIELD, FIELD, 4 * FIELD),
+            // n_descendants, children[2], dot_factor, v[dimensions]
+            AnnoyMetric::Dot => (FIELD, 3 * FIELD, 4 * FIELD),
+        };
+
+        NodeLayout { size: vector + dimensions * FIELD, children, extra, vector }
+    }
+}
+
+/// The offsets of the fields of an Annoy node, every node of a file has the same size.
+///
+/// The `n_descendants` field is always first. The nodes with `n_descendants == 1` are the items,
+/// the ones with at most [`Self::max_descendants`] store the IDs of their items in the children
+/// field, overflowing on the following fields, and the other ones are the split planes.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct NodeLayout {
+    pub size: usize,
+    pub children: usize,
+    /// The field derived from the header of the items: the squared norm for
+    /// the angular metric, the plane offset of the euclidean and manhattan ones
+    /// and the extra dimension of the dot one.
+    pub extra: usize,
+    pub vector: usize,
+}
+
+impl NodeLayout {
+    /// The maximum number of item IDs a node can store instead of a split plane.
+    /// It's the `K` of Annoy.
+    pub fn max_descendants(&self) -> usize {
+        (self.size - self.children) / size_of::<u32>()
+    }
+}
+
+/// The nodes of an Annoy file, read the way `AnnoyIndex.load` does.
+pub(crate) struct AnnoyFile<'a> {
+    pub layout: NodeLayout,
+    bytes: &'a [u8],
+    /// The number of items, the items are the first nodes.
+    pub n_items: usize,
+    pub roots: Vec<usize>,
+}
+
+impl<'a> AnnoyFile<'a> {
+    /// Reads the nodes of the `metric` for vectors of `dimensions`,
+    /// returns `None` if the size of the file doesn't match them.
+    pub fn new(metric: AnnoyMetric, dimensions: usize, bytes: &'a [u8]) -> Option<AnnoyFile<'a>> {
+        let layout = metric.layout(dimensions);
+        if !bytes.len().is_multiple_of(layout.size) {
+            return None;
+        }
+
+        let mut file = AnnoyFile { layout, bytes, n_items: 0, roots: Vec::new() };
+        // The roots are the last nodes with the same number of descendants
+        let mut m = None;
+        for i in (0..file.n_nodes()).rev() {
+            let k = file.n_descendants(i);
+            if m.is_none() || m == Some(k) {
+                file.roots.push(i);
+                m = Some(k);
+            } else {
+                break;
+            }
+        }
+        // The last root precedes the copy of all the roots
+        if let [first, .., last] = file.roots[..] {
+            if file.field(first, layout.children) == file.field(last, layout.children) {
+                file.roots.pop();
+            }
+        }
+        file.roots.reverse();
+        file.n_items = m.unwrap_or_default();
+
+        Some(file)
+    }
+
+    pub fn n_nodes(&self) -> usize {
+        self.bytes.len() / self.layout.size
+    }
+
+    fn field(&self, i: usize, offset: usize) -> [u8; 4] {
+        let offset = i * self.layout.size + offset;
+        self.bytes[offset..offset + 4].try_into().unwrap()
+    }
+
+    /// The number of descendants of the node, the corrupted negative numbers are read as zeros.
+    pub fn n_descendants(&self, i: usize) -> usize {
+        i32::from_le_bytes(self.field(i, 0)).max(0) as usize
+    }
+
+    /// Returns `true` if the node is an item.
+    pub fn is_item(&self, i: usize) -> bool {
+        i < self.n_items && self.n_descendants(i) == 1
+    }
+
+    /// Returns `true` if the node stores the IDs of its items instead of a split plane.
+    pub fn is_descendants(&self, i: usize) -> bool {
+        !self.is_item(i) && self.n_descendants(i) <= self.layout.max_descendants()
+    }
+
+    /// Returns the IDs of the items of a descendants node or the two children of a split node.
+    pub fn children(&self, i: usize) -> impl Iterator<Item = usize> + '_ {
+        let len = if self.is_descendants(i) { self.n_descendants(i) } else { 2 };
+        (0..len).map(move |n| {
+            u32::from_le_bytes(self.field(i, self.layout.children + n * size_of::<u32>())) as usize
+        })
+    }
+
+    pub fn extra(&self, i: usize) -> f32 {
+        f32::from_le_bytes(self.field(i, self.layout.extra))
+    }
+
+    pub fn vector(&self, i: usize) -> Vec<f32> {
+        let node = &self.bytes[i * self.layout.size..(i + 1) * self.layout.size];
+        node[self.layout.vector..]
+            .chunks_exact(size_of::<f32>())
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect()
+    }
+}
+
+/// A [`Distance`] that can be exported to and imported from Annoy.
+pub trait AnnoyDistance: Distance<VectorCodec = f32> {
+    /// The Annoy metric computing the same distance.
+    const METRIC: AnnoyMetric;
+
+    /// Returns the extra field of the Annoy node of an item, see [`AnnoyMetric`].
+    fn annoy_extra(header: &Self::Header) -> f32;
+
+    /// Returns the header of an item read from an Annoy node with the given extra field.
+    fn annoy_header(vector: &UnalignedVector<f32>, extra: f32) -> Self::Header;
+}
+
+impl AnnoyDistance for Cosine {
+    const METRIC: AnnoyMetric = AnnoyMetric::Angular;
+
+    fn annoy_extra(header: &NodeHeaderCosine) -> f32 {
+        // Annoy keeps the squared norm, a zero makes it compute it again
+        header.norm * header.norm
+    }
+
+    fn annoy_header(vector: &UnalignedVector<f32>, _extra: f32) -> NodeHeaderCosine {
+        // The norm is missing from the files of the old versions of Annoy
+        Self::new_header(vector)
+    }
+}
+
+impl AnnoyDistance for Euclidean {
+    const METRIC: AnnoyMetric = AnnoyMetric::Euclidean;
+
+    fn annoy_extra(header: &NodeHeaderEuclidean) -> f32 {
+        header.bias
+    }
+
+    fn annoy_header(_vector: &UnalignedVector<f32>, extra: f32) -> NodeHeaderEuclidean {
+        NodeHeaderEuclidean { bias: extra }
+    }
+}
+
+impl AnnoyDistance for Manhattan {
+    const METRIC: AnnoyMetric = AnnoyMetric::Manhattan;
+
+    fn annoy_extra(header: &NodeHeaderManhattan) -> f32 {
+        header.bias
+    }
+
+    fn annoy_header(_vector: &UnalignedVector<f32>, extra: f32) -> NodeHeaderManhattan {
+        NodeHeaderManhattan { bias: extra }
+    }
+}
+
+impl AnnoyDistance for DotProduct {
+    const METRIC: AnnoyMetric = AnnoyMetric::Dot;
+
+    fn annoy_extra(header: &NodeHeaderDotProduct) -> f32 {
+        header.extra_dim
+    }
+
+    fn annoy_header(vector: &UnalignedVector<f32>, extra: f32) -> NodeHeaderDotProduct {
+        // Like the preprocessing of the build, the norm is the one of the largest item
+        let norm = dot_product(vector, vector) + extra * extra;
+        NodeHeaderDotProduct { extra_dim: extra, norm }
+    }
+}
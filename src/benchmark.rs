@@ -0,0 +1,430 @@
+use std::collections::BinaryHeap;
+use std::fmt;
+use std::num::NonZeroUsize;
+use std::time::{Duration, Instant};
+
+use heed::{RoTxn, RwTxn};
+use ordered_float::OrderedFloat;
+use rand::seq::IteratorRandom;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use rayon::ThreadPool;
+
+use crate::internals::UnalignedVector;
+use crate::node::Leaf;
+use crate::{Distance, Error, ItemId, Reader, Result, SearchTuning, Writer};
+
+/// The number of items compared to all the queries at once when computing the ground truth.
+const GROUND_TRUTH_BATCH_SIZE: usize = 1024;
+
+/// The number of items used as queries to tune the `search_k`.
+const TUNING_QUERIES: usize = 100;
+
+/// The exact nearest neighbors of a set of queries, used to measure the recall of arroy.
+#[derive(Debug, Clone)]
+pub struct GroundTruth {
+    count: usize,
+    queries: Vec<Vec<f32>>,
+    neighbors: Vec<Vec<ItemId>>,
+}
+
+impl GroundTruth {
+    /// Creates a ground truth from known neighbors, like the `.ivecs` files of the
+    /// ANN benchmark datasets read with [`read_ivecs`](crate::read_ivecs).
+    ///
+    /// Only the first `count` neighbors of every query are kept.
+    pub fn new(
+        queries: Vec<Vec<f32>>,
+        neighbors: Vec<Vec<ItemId>>,
+        count: usize,
+    ) -> Result<GroundTruth> {
+        if queries.len() != neighbors.len() {
+            return Err(Error::InvalidGroundTruth {
+                reason: format!(
+                    "{} queries were given but the neighbors of {} queries",
+                    queries.len(),
+                    neighbors.len()
+                ),
+            });
+        }
+        let neighbors = neighbors.into_iter().map(|mut n| {
+            n.truncate(count);
+            n
+        });
+        Ok(GroundTruth { count, queries, neighbors: neighbors.collect() })
+    }
+
+    /// Computes the exact `count` nearest neighbors of the `queries` by comparing them
+    /// to every item of the index.
+    ///
+    /// The queries are compared in parallel in the given thread pool,
+    /// or in the current one if none is given.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use arroy::{distances::Euclidean, GroundTruth, Reader};
+    /// # let (reader, rtxn): (Reader<Euclidean>, heed::RoTxn) = todo!();
+    /// let queries = vec![vec![0.25, -0.5, 1.0], vec![1.5, 0.0, -0.75]];
+    /// let thread_pool = rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+    /// let ground_truth = GroundTruth::compute(&rtxn, &reader, queries, 10, Some(&thread_pool))?;
+    /// # Ok::<(), arroy::Error>(())
+    /// ```
+    pub fn compute<D: Distance>(
+        rtxn: &RoTxn,
+        reader: &Reader<D>,
+        queries: Vec<Vec<f32>>,
+        count: usize,
+        thread_pool: Option<&ThreadPool>,
+    ) -> Result<GroundTruth>
+    where
+        D::Header: Sync,
+    {
+        if let Some(query) = queries.iter().find(|q| q.len() != reader.dimensions()) {
+            return Err(Error::InvalidVecDimension {
+                expected: reader.dimensions(),
+                received: query.len(),
+            });
+        }
+
+        let query_leafs: Vec<_> = queries.iter().map(|q| leaf::<D>(q.clone())).collect();
+        let mut heaps: Vec<_> = queries.iter().map(|_| BinaryHeap::new()).collect();
+        let mut batch = Vec::with_capacity(GROUND_TRUTH_BATCH_SIZE);
+        let mut items = reader.iter(rtxn)?;
+        loop {
+            batch.clear();
+            for result in items.by_ref().take(GROUND_TRUTH_BATCH_SIZE) {
+                let (item, vector) = result?;
+                batch.push((item, leaf::<D>(vector)));
+            }
+            if batch.is_empty() {
+                break;
+            }
+
+            // The heaps keep the `count` closest items found so far, the farthest on top
+            let mut compare = || {
+                heaps.par_iter_mut().zip(&query_leafs).for_each(|(heap, query)| {
+                    for (item, leaf) in &batch {
+                        let distance = OrderedFloat(D::built_distance(query, leaf));
+                        if heap.len() < count {
+                            heap.push((distance, *item));
+                        } else if heap.peek().is_some_and(|&(farthest, _)| distance < farthest) {
+                            heap.pop();
+                            heap.push((distance, *item));
+                        }
+                    }
+                })
+            };
+            match thread_pool {
+                Some(thread_pool) => thread_pool.install(compare),
+                None => compare(),
+            }
+        }
+
+        let neighbors = heaps
+            .into_iter()
+            .map(|heap| heap.into_sorted_vec().into_iter().map(|(_, item)| item).collect())
+            .collect();
+        Ok(GroundTruth { count, queries, neighbors })
+    }
+
+    /// Computes the exact `count` nearest neighbors of the vectors of the `items`,
+    /// the items are expected to find themselves. See [`Self::compute`].
+    pub fn from_items<D: Distance>(
+        rtxn: &RoTxn,
+        reader: &Reader<D>,
+        items: impl IntoIterator<Item = ItemId>,
+        count: usize,
+        thread_pool: Option<&ThreadPool>,
+    ) -> Result<GroundTruth>
+    where
+        D::Header: Sync,
+    {
+        let mut queries = Vec::new();
+        for item in items {
+            match reader.item_vector(rtxn, item)? {
+                Some(vector) => queries.push(vector),
+                None => {
+                    return Err(Error::InvalidGroundTruth {
+                        reason: format!("the item {item} doesn't exist"),
+                    })
+                }
+            }
+        }
+        GroundTruth::compute(rtxn, reader, queries, count, thread_pool)
+    }
+
+    /// The number of neighbors searched for every query.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// The vectors of the queries.
+    pub fn queries(&self) -> &[Vec<f32>] {
+        &self.queries
+    }
+
+    /// The exact nearest neighbors of every query, from the closest to the farthest.
+    pub fn neighbors(&self) -> &[Vec<ItemId>] {
+        &self.neighbors
+    }
+
+    /// Returns the proportion of the exact neighbors of the `nth` query that were `found`.
+    pub fn recall(&self, nth: usize, found: &[ItemId]) -> f32 {
+        let neighbors = &self.neighbors[nth];
+        if neighbors.is_empty() {
+            return 1.0;
+        }
+        let hits = found.iter().filter(|item| neighbors.contains(item)).count();
+        hits as f32 / neighbors.len() as f32
+    }
+}
+
+fn leaf<D: Distance>(vector: Vec<f32>) -> Leaf<'static, D> {
+    let vector = UnalignedVector::from_vec(vector);
+    Leaf { header: D::new_header(&vector), vector }
+}
+
+/// Measures the recall, the throughput and the latency of the queries of a [`GroundTruth`]
+/// for every combination of the `n_trees`, `search_k` and `oversampling` values.
+///
+/// Created by [`Benchmark::new`].
+pub struct Benchmark<'a> {
+    ground_truth: &'a GroundTruth,
+    n_trees: Vec<usize>,
+    search_k: Vec<Option<NonZeroUsize>>,
+    oversampling: Vec<Option<NonZeroUsize>>,
+}
+
+impl<'a> Benchmark<'a> {
+    /// Creates a benchmark of the queries of the ground truth with the default
+    /// `search_k` and `oversampling` only.
+    pub fn new(ground_truth: &'a GroundTruth) -> Benchmark<'a> {
+        Benchmark {
+            ground_truth,
+            n_trees: Vec::new(),
+            search_k: vec![None],
+            oversampling: vec![None],
+        }
+    }
+
+    /// The numbers of trees to measure, see [`ArroyBuilder::n_trees`](crate::ArroyBuilder::n_trees).
+    /// Only used by [`Self::run_with_builds`].
+    pub fn n_trees(&mut self, n_trees: impl IntoIterator<Item = usize>) -> &mut Self {
+        self.n_trees = n_trees.into_iter().collect();
+        self
+    }
+
+    /// The `search_k` values to measure, see [`QueryBuilder::search_k`](crate::QueryBuilder::search_k).
+    /// `None` measures the default `search_k`.
+    pub fn search_k(
+        &mut self,
+        search_k: impl IntoIterator<Item = Option<NonZeroUsize>>,
+    ) -> &mut Self {
+        self.search_k = search_k.into_iter().collect();
+        self
+    }
+
+    /// The oversampling values to measure, see [`QueryBuilder::oversampling`](crate::QueryBuilder::oversampling).
+    /// `None` measures the default oversampling of the distance.
+    pub fn oversampling(
+        &mut self,
+        oversampling: impl IntoIterator<Item = Option<NonZeroUsize>>,
+    ) -> &mut Self {
+        self.oversampling = oversampling.into_iter().collect();
+        self
+    }
+
+    /// Measures every combination of `search_k` and `oversampling` on the trees of the index.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use arroy::{distances::Euclidean, Benchmark, GroundTruth, Reader};
+    /// # let (reader, rtxn): (Reader<Euclidean>, heed::RoTxn) = todo!();
+    /// use std::num::NonZeroUsize;
+    ///
+    /// let ground_truth = GroundTruth::from_items(&rtxn, &reader, 0..100, 10, None)?;
+    /// let search_k = [100, 1000, 10000].map(NonZeroUsize::new);
+    /// for result in Benchmark::new(&ground_truth).search_k(search_k).run(&rtxn, &reader)? {
+    ///     println!("{result}");
+    /// }
+    /// # Ok::<(), arroy::Error>(())
+    /// ```
+    pub fn run<D: Distance>(
+        &self,
+        rtxn: &RoTxn,
+        reader: &Reader<D>,
+    ) -> Result<Vec<BenchmarkResult>> {
+        let mut results = Vec::new();
+        for &search_k in &self.search_k {
+            for &oversampling in &self.oversampling {
+                results.push(self.measure(rtxn, reader, search_k, oversampling)?);
+            }
+        }
+        Ok(results)
+    }
+
+    /// Builds the index with every number of trees, in order, and measures every
+    /// combination of `search_k` and `oversampling` on them.
+    ///
+    /// The trees are built in the write transaction, abort it to get the index back as it was.
+    pub fn run_with_builds<D: Distance, R: Rng + SeedableRng>(
+        &self,
+        wtxn: &mut RwTxn,
+        writer: &Writer<D>,
+        rng: &mut R,
+    ) -> Result<Vec<BenchmarkResult>> {
+        let mut results = Vec::new();
+        for &n_trees in &self.n_trees {
+            writer.builder(rng).n_trees(n_trees).build(wtxn)?;
+            let reader = Reader::open(wtxn, writer.index, writer.database)?;
+            results.extend(self.run(wtxn, &reader)?);
+        }
+        Ok(results)
+    }
+
+    fn measure<D: Distance>(
+        &self,
+        rtxn: &RoTxn,
+        reader: &Reader<D>,
+        search_k: Option<NonZeroUsize>,
+        oversampling: Option<NonZeroUsize>,
+    ) -> Result<BenchmarkResult> {
+        let mut query = reader.nns(self.ground_truth.count);
+        if let Some(search_k) = search_k {
+            query.search_k(search_k);
+        }
+        if let Some(oversampling) = oversampling {
+            query.oversampling(oversampling);
+        }
+
+        let mut recall = 0.0;
+        let mut latencies = Vec::with_capacity(self.ground_truth.queries.len());
+        for (nth, vector) in self.ground_truth.queries.iter().enumerate() {
+            let now = Instant::now();
+            let found = query.by_vector(rtxn, vector)?;
+            latencies.push(now.elapsed());
+            let found: Vec<_> = found.into_iter().map(|(item, _)| item).collect();
+            recall += self.ground_truth.recall(nth, &found);
+        }
+
+        let total: Duration = latencies.iter().sum();
+        latencies.sort_unstable();
+        let percentile = |p: usize| match latencies.len() {
+            0 => Duration::ZERO,
+            len => latencies[(len * p / 100).min(len - 1)],
+        };
+        let n_queries = latencies.len().max(1);
+
+        Ok(BenchmarkResult {
+            n_trees: reader.n_trees(),
+            search_k,
+            oversampling,
+            count: self.ground_truth.count,
+            recall: recall / n_queries as f32,
+            qps: latencies.len() as f64 / total.as_secs_f64().max(f64::EPSILON),
+            p50: percentile(50),
+            p99: percentile(99),
+        })
+    }
+}
+
+/// The measures of a combination of parameters of a [`Benchmark`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BenchmarkResult {
+    /// The number of trees of the index.
+    pub n_trees: usize,
+    /// The `search_k` of the queries, `None` for the default one.
+    pub search_k: Option<NonZeroUsize>,
+    /// The oversampling of the queries, `None` for the default one.
+    pub oversampling: Option<NonZeroUsize>,
+    /// The number of neighbors searched for every query.
+    pub count: usize,
+    /// The mean proportion of the exact nearest neighbors found by the queries.
+    pub recall: f32,
+    /// The number of queries run per second, on a single thread.
+    pub qps: f64,
+    /// The median latency of the queries.
+    pub p50: Duration,
+    /// The latency under which 99% of the queries ran.
+    pub p99: Duration,
+}
+
+impl fmt::Display for BenchmarkResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let or_default = |value: Option<NonZeroUsize>| {
+            value.map_or_else(|| "default".to_string(), |v| v.to_string())
+        };
+        write!(
+            f,
+            "n_trees: {}, search_k: {}, oversampling: {}, recall@{}: {:.4}, qps: {:.0}, p50: {:.2?}, p99: {:.2?}",
+            self.n_trees,
+            or_default(self.search_k),
+            or_default(self.oversampling),
+            self.count,
+            self.recall,
+            self.qps,
+            self.p50,
+            self.p99,
+        )
+    }
+}
+
+/// See [`Reader::tune_search_k`].
+pub(crate) fn tune_search_k<D: Distance, R: Rng>(
+    rtxn: &RoTxn,
+    reader: &Reader<D>,
+    count: usize,
+    target_recall: f32,
+    rng: &mut R,
+) -> Result<SearchTuning>
+where
+    D::Header: Sync,
+{
+    let n_trees = reader.n_trees();
+    let items = reader.item_ids().iter().choose_multiple(rng, TUNING_QUERIES);
+    let ground_truth = GroundTruth::from_items(rtxn, reader, items, count, None)?;
+    let benchmark = Benchmark::new(&ground_truth);
+    let recall = |search_k: usize| -> Result<f32> {
+        if ground_truth.queries.is_empty() {
+            return Ok(1.0);
+        }
+        let search_k = NonZeroUsize::new(search_k);
+        let result = benchmark.measure(rtxn, reader, search_k, Some(NonZeroUsize::MIN))?;
+        Ok(result.recall)
+    };
+
+    // Exploring all the nodes of all the trees finds the exact neighbors
+    let max = (reader.n_items() as usize).saturating_mul(n_trees).max(1);
+    let mut low = 0;
+    let mut high = count.saturating_mul(n_trees).clamp(1, max);
+    let mut reached = recall(high)?;
+    while reached < target_recall && high < max {
+        low = high;
+        high = high.saturating_mul(2).min(max);
+        reached = recall(high)?;
+    }
+
+    // We stop once the search_k is known at ~3% so that we don't measure too many times
+    if reached >= target_recall {
+        while high - low > (high / 32).max(1) {
+            let middle = low + (high - low) / 2;
+            let middle_recall = recall(middle)?;
+            if middle_recall >= target_recall {
+                high = middle;
+                reached = middle_recall;
+            } else {
+                low = middle;
+            }
+        }
+    }
+
+    Ok(SearchTuning {
+        count,
+        n_trees,
+        search_k: NonZeroUsize::new(high).unwrap(),
+        target_recall,
+        recall: reached,
+    })
+}
@@ -0,0 +1,596 @@
+//! A command-line tool to inspect and operate the arroy indexes of an LMDB environment.
+//!
+//! ```text
+//! arroy --env data.ary list
+//! arroy --env data.ary stats --index 0
+//! arroy --env data.ary query --index 0 --item 42 --count 10
+//! arroy --env data.ary import --index 1 --distance euclidean sift_base.fvecs
+//! arroy --env data.ary build --index 1 --n-trees 50
+//! arroy --env data.ary dot --index 1 --tree 0 > tree.dot
+//! ```
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use anyhow::{bail, Context};
+use arroy::distances::{
+    BinaryQuantizedCosine, BinaryQuantizedEuclidean, BinaryQuantizedManhattan, Cosine, DotProduct,
+    Euclidean, Manhattan,
+};
+use arroy::internals::{KeyCodec, NodeCodec};
+use arroy::{
+    Database, Distance, NormalPrecision, Reader, SplitLevelStats, Stats, TreeStats, VectorReader,
+    Writer,
+};
+use clap::{Parser, Subcommand, ValueEnum};
+use heed::types::Bytes;
+use heed::{Env, EnvFlags, EnvOpenOptions, RoTxn};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+/// 200 GiB
+const DEFAULT_MAP_SIZE: usize = 1024 * 1024 * 1024 * 200;
+
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+struct Cli {
+    /// The path of the LMDB environment.
+    #[arg(long, default_value = "import.ary")]
+    env: PathBuf,
+
+    /// The name of the LMDB database of the environment, the unnamed one by default.
+    #[arg(long)]
+    database: Option<String>,
+
+    /// The maximum size of the environment.
+    #[arg(long, default_value_t = DEFAULT_MAP_SIZE)]
+    map_size: usize,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Lists the indexes of the database with their metadata.
+    List,
+    /// Prints the stats of the trees of an index.
+    Stats {
+        #[arg(long, default_value_t = 0)]
+        index: u16,
+    },
+    /// Verifies the trees of an index and prints the problems found.
+    Verify {
+        #[arg(long, default_value_t = 0)]
+        index: u16,
+    },
+    /// Searches for the nearest neighbors of an item or of a vector.
+    Query {
+        #[arg(long, default_value_t = 0)]
+        index: u16,
+        /// The item to search the neighbors of.
+        #[arg(long, conflicts_with = "vector", required_unless_present = "vector")]
+        item: Option<u32>,
+        /// The vector to search the neighbors of, as comma separated values.
+        #[arg(long, value_delimiter = ',', allow_hyphen_values = true)]
+        vector: Option<Vec<f32>>,
+        /// The number of neighbors to return.
+        #[arg(long, default_value_t = 10)]
+        count: usize,
+        /// The number of nodes to explore.
+        #[arg(long)]
+        search_k: Option<NonZeroUsize>,
+    },
+    /// Adds the vectors of a `.fvecs`, `.bvecs` or `.npy` file to an index,
+    /// the item IDs are the positions of the vectors in the file.
+    Import {
+        #[arg(long, default_value_t = 0)]
+        index: u16,
+        /// The distance of the index, the one of the index is used if it already exists.
+        #[arg(long)]
+        distance: Option<DistanceArg>,
+        /// The item ID of the first vector.
+        #[arg(long, default_value_t = 0)]
+        first_id: u32,
+        /// The file of vectors.
+        path: PathBuf,
+    },
+    /// Writes the vectors of an index in a `.fvecs` file, ordered by item ID.
+    Export {
+        #[arg(long, default_value_t = 0)]
+        index: u16,
+        /// Also writes the item IDs in this file, one per line.
+        #[arg(long)]
+        ids: Option<PathBuf>,
+        /// The `.fvecs` file to write.
+        path: PathBuf,
+    },
+    /// Builds the trees of an index, the options of the last build are used by default.
+    Build {
+        #[arg(long, default_value_t = 0)]
+        index: u16,
+        /// The distance of the index, required if it was never built.
+        #[arg(long)]
+        distance: Option<DistanceArg>,
+        /// The number of dimensions of the vectors, required if the index was never built.
+        #[arg(long)]
+        dimensions: Option<usize>,
+        #[arg(long)]
+        n_trees: Option<usize>,
+        #[arg(long)]
+        split_after: Option<usize>,
+        #[arg(long)]
+        split_dimensions: Option<usize>,
+        #[arg(long)]
+        reduce_dimensions: Option<usize>,
+        #[arg(long)]
+        normal_precision: Option<PrecisionArg>,
+        #[arg(long, default_value_t = 42)]
+        seed: u64,
+    },
+    /// Writes a tree of an index in the dot format of graphviz.
+    Dot {
+        #[arg(long, default_value_t = 0)]
+        index: u16,
+        /// The position of the tree in the index.
+        #[arg(long, default_value_t = 0)]
+        tree: usize,
+        /// The file to write, the standard output by default.
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum DistanceArg {
+    Cosine,
+    Euclidean,
+    Manhattan,
+    DotProduct,
+    BinaryQuantizedCosine,
+    BinaryQuantizedEuclidean,
+    BinaryQuantizedManhattan,
+}
+
+impl DistanceArg {
+    fn from_name(name: &str) -> anyhow::Result<DistanceArg> {
+        let distances = [
+            (Cosine::name(), DistanceArg::Cosine),
+            (Euclidean::name(), DistanceArg::Euclidean),
+            (Manhattan::name(), DistanceArg::Manhattan),
+            (DotProduct::name(), DistanceArg::DotProduct),
+            (BinaryQuantizedCosine::name(), DistanceArg::BinaryQuantizedCosine),
+            (BinaryQuantizedEuclidean::name(), DistanceArg::BinaryQuantizedEuclidean),
+            (BinaryQuantizedManhattan::name(), DistanceArg::BinaryQuantizedManhattan),
+        ];
+        match distances.into_iter().find(|(n, _)| *n == name) {
+            Some((_, distance)) => Ok(distance),
+            None => bail!("unknown distance {name:?}"),
+        }
+    }
+}
+
+/// Calls the generic function with the distance type.
+macro_rules! with_distance {
+    ($distance:expr, $function:ident($($arg:expr),* $(,)?)) => {
+        match $distance {
+            DistanceArg::Cosine => $function::<Cosine>($($arg),*),
+            DistanceArg::Euclidean => $function::<Euclidean>($($arg),*),
+            DistanceArg::Manhattan => $function::<Manhattan>($($arg),*),
+            DistanceArg::DotProduct => $function::<DotProduct>($($arg),*),
+            DistanceArg::BinaryQuantizedCosine => $function::<BinaryQuantizedCosine>($($arg),*),
+            DistanceArg::BinaryQuantizedEuclidean => {
+                $function::<BinaryQuantizedEuclidean>($($arg),*)
+            }
+            DistanceArg::BinaryQuantizedManhattan => {
+                $function::<BinaryQuantizedManhattan>($($arg),*)
+            }
+        }
+    };
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum PrecisionArg {
+    Full,
+    F16,
+    Int8,
+}
+
+fn main() -> anyhow::Result<()> {
+    env_logger::init();
+    let Cli { env: path, database: name, map_size, command } = Cli::parse();
+
+    let read_only = !matches!(command, Command::Import { .. } | Command::Build { .. });
+    let env = open_env(&path, map_size, name.is_some(), read_only)?;
+    let name = name.as_deref();
+
+    match command {
+        Command::List => list(&env, name),
+        Command::Stats { index } => {
+            let rtxn = env.read_txn()?;
+            let (distance, database) = open_database(&env, &rtxn, name, index)?;
+            with_distance!(distance, stats(&rtxn, database.remap_data_type(), index))
+        }
+        Command::Verify { index } => {
+            let rtxn = env.read_txn()?;
+            let (distance, database) = open_database(&env, &rtxn, name, index)?;
+            with_distance!(distance, verify(&rtxn, database.remap_data_type(), index))
+        }
+        Command::Query { index, item, vector, count, search_k } => {
+            let rtxn = env.read_txn()?;
+            let (distance, database) = open_database(&env, &rtxn, name, index)?;
+            let query = Query { item, vector, count, search_k };
+            with_distance!(distance, search(&rtxn, database.remap_data_type(), index, query))
+        }
+        Command::Import { index, distance, first_id, path } => {
+            let mut wtxn = env.write_txn()?;
+            let database: heed::Database<KeyCodec, Bytes> = env.create_database(&mut wtxn, name)?;
+            let distance = match index_distance(&wtxn, database, index)? {
+                Some(existing) => existing,
+                None => distance.context("the distance of a new index must be given")?,
+            };
+            let dimensions =
+                arroy::list_indexes(&wtxn, database.remap_data_type::<NodeCodec<Cosine>>())?
+                    .into_iter()
+                    .find(|info| info.index == index)
+                    .and_then(|info| info.dimensions);
+            with_distance!(
+                distance,
+                import(&mut wtxn, database.remap_data_type(), index, dimensions, first_id, &path)
+            )?;
+            wtxn.commit()?;
+            Ok(())
+        }
+        Command::Export { index, ids, path } => {
+            let rtxn = env.read_txn()?;
+            let (distance, database) = open_database(&env, &rtxn, name, index)?;
+            with_distance!(
+                distance,
+                export(&rtxn, database.remap_data_type(), index, &path, ids.as_deref())
+            )
+        }
+        Command::Build {
+            index,
+            distance,
+            dimensions,
+            n_trees,
+            split_after,
+            split_dimensions,
+            reduce_dimensions,
+            normal_precision,
+            seed,
+        } => {
+            let mut wtxn = env.write_txn()?;
+            let database: heed::Database<KeyCodec, Bytes> = env.create_database(&mut wtxn, name)?;
+            let info = arroy::list_indexes(&wtxn, database.remap_data_type::<NodeCodec<Cosine>>())?
+                .into_iter()
+                .find(|info| info.index == index)
+                .with_context(|| format!("there is no index {index}"))?;
+            let distance = match info.distance {
+                Some(name) => DistanceArg::from_name(&name)?,
+                None => distance.context("the distance of an index never built must be given")?,
+            };
+            let dimensions = match info.dimensions.or(dimensions) {
+                Some(dimensions) => dimensions,
+                None => bail!("the dimensions of an index never built must be given"),
+            };
+            let options = BuildOptions {
+                n_trees,
+                split_after,
+                split_dimensions,
+                reduce_dimensions,
+                normal_precision,
+                seed,
+            };
+            with_distance!(
+                distance,
+                build(&mut wtxn, database.remap_data_type(), index, dimensions, &options)
+            )?;
+            wtxn.commit()?;
+            Ok(())
+        }
+        Command::Dot { index, tree, output } => {
+            let rtxn = env.read_txn()?;
+            let (distance, database) = open_database(&env, &rtxn, name, index)?;
+            let writer: Box<dyn Write> = match output {
+                Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+                None => Box::new(BufWriter::new(io::stdout().lock())),
+            };
+            with_distance!(distance, dot(&rtxn, database.remap_data_type(), index, tree, writer))
+        }
+    }
+}
+
+fn open_env(path: &Path, map_size: usize, named: bool, read_only: bool) -> anyhow::Result<Env> {
+    let mut options = EnvOpenOptions::new();
+    options.map_size(map_size);
+    if named {
+        options.max_dbs(128);
+    }
+    if read_only {
+        // safety: the environment is only read, the other processes can keep writing in it
+        unsafe { options.flags(EnvFlags::READ_ONLY) };
+    } else {
+        std::fs::create_dir_all(path)?;
+    }
+    // safety: the environment must not be opened twice by this process
+    let env = unsafe { options.open(path) }
+        .with_context(|| format!("cannot open the environment {}", path.display()))?;
+    Ok(env)
+}
+
+/// Opens the database and returns the distance of the index.
+fn open_database(
+    env: &Env,
+    rtxn: &RoTxn,
+    name: Option<&str>,
+    index: u16,
+) -> anyhow::Result<(DistanceArg, heed::Database<KeyCodec, Bytes>)> {
+    let database: heed::Database<KeyCodec, Bytes> =
+        env.open_database(rtxn, name)?.context("the database doesn't exist")?;
+    match index_distance(rtxn, database, index)? {
+        Some(distance) => Ok((distance, database)),
+        None => bail!("the index {index} doesn't exist or was never built"),
+    }
+}
+
+/// Returns the distance of the index, `None` if it doesn't exist or was never built.
+fn index_distance(
+    rtxn: &RoTxn,
+    database: heed::Database<KeyCodec, Bytes>,
+    index: u16,
+) -> anyhow::Result<Option<DistanceArg>> {
+    // The distance of the database doesn't matter to list the indexes
+    let indexes = arroy::list_indexes(rtxn, database.remap_data_type::<NodeCodec<Cosine>>())?;
+    match indexes.into_iter().find(|info| info.index == index).and_then(|info| info.distance) {
+        Some(name) => DistanceArg::from_name(&name).map(Some),
+        None => Ok(None),
+    }
+}
+
+fn list(env: &Env, name: Option<&str>) -> anyhow::Result<()> {
+    let rtxn = env.read_txn()?;
+    let Some(database) = env.open_database::<KeyCodec, NodeCodec<Cosine>>(&rtxn, name)? else {
+        bail!("the database doesn't exist");
+    };
+
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "index\tversion\tdistance\tdimensions\titems\ttrees\tneeds build")?;
+    for info in arroy::list_indexes(&rtxn, database)? {
+        let or_dash = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+        writeln!(
+            stdout,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            info.index,
+            info.format_version,
+            or_dash(info.distance),
+            or_dash(info.dimensions.map(|d| d.to_string())),
+            or_dash(info.n_items.map(|n| n.to_string())),
+            or_dash(info.n_trees.map(|n| n.to_string())),
+            info.needs_build,
+        )?;
+    }
+    Ok(())
+}
+
+fn stats<D: Distance>(rtxn: &RoTxn, database: Database<D>, index: u16) -> anyhow::Result<()> {
+    let reader = Reader::<D>::open(rtxn, index, database)?;
+    let Stats { leaf, tree_stats, bytes, descendants_sizes, leaf_depths, split_levels } =
+        reader.stats(rtxn)?;
+    println!("index {index}: {leaf} items of {} dimensions", reader.dimensions());
+    println!("build config: {:?}", reader.build_config());
+    println!(
+        "bytes: {} total, {} leafs, {} split normals, {} descendants, {} metadata",
+        bytes.total(),
+        bytes.leafs,
+        bytes.split_normals,
+        bytes.descendants,
+        bytes.metadata,
+    );
+    println!();
+    println!("tree\tdepth\tsplit nodes\tdummy normals\tdescendants\tdescendants bytes\titems");
+    for (i, tree) in tree_stats.iter().enumerate() {
+        let TreeStats {
+            depth,
+            dummy_normals,
+            split_nodes,
+            descendants,
+            descendants_bytes,
+            items,
+            ..
+        } = tree;
+        println!(
+            "{i}\t{depth}\t{split_nodes}\t\t{dummy_normals}\t\t{descendants}\t\t{descendants_bytes}\t\t\t{items}"
+        );
+    }
+    println!();
+    println!("descendants size\tnodes");
+    for (size, nodes) in descendants_sizes {
+        println!("{size}\t\t\t{nodes}");
+    }
+    println!();
+    println!("depth\titems");
+    for (depth, items) in leaf_depths {
+        println!("{depth}\t{items}");
+    }
+    println!();
+    println!("level\tsplit nodes\tmean imbalance\tmax imbalance");
+    for (level, SplitLevelStats { split_nodes, mean_imbalance, max_imbalance }) in
+        split_levels.iter().enumerate()
+    {
+        println!("{level}\t{split_nodes}\t\t{mean_imbalance:.3}\t\t{max_imbalance:.3}");
+    }
+    Ok(())
+}
+
+fn verify<D: Distance>(rtxn: &RoTxn, database: Database<D>, index: u16) -> anyhow::Result<()> {
+    let reader = Reader::<D>::open(rtxn, index, database)?;
+    let report = reader.verify(rtxn)?;
+    if report.is_valid() {
+        println!("the index {index} is valid");
+        Ok(())
+    } else {
+        println!("{report:#?}");
+        bail!("the index {index} is not valid, it can be repaired with `Writer::repair`")
+    }
+}
+
+struct Query {
+    item: Option<u32>,
+    vector: Option<Vec<f32>>,
+    count: usize,
+    search_k: Option<NonZeroUsize>,
+}
+
+fn search<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+    query: Query,
+) -> anyhow::Result<()> {
+    let reader = Reader::<D>::open(rtxn, index, database)?;
+    let mut builder = reader.nns(query.count);
+    if let Some(search_k) = query.search_k {
+        builder.search_k(search_k);
+    }
+
+    let now = Instant::now();
+    let results = match (query.item, &query.vector) {
+        (Some(item), _) => {
+            builder.by_item(rtxn, item)?.with_context(|| format!("there is no item {item}"))?
+        }
+        (None, Some(vector)) => builder.by_vector(rtxn, vector)?,
+        (None, None) => bail!("an item or a vector must be given"),
+    };
+    let elapsed = now.elapsed();
+
+    for (item, distance) in results {
+        println!("{item}\t{distance}");
+    }
+    eprintln!("found in {elapsed:.2?}");
+    Ok(())
+}
+
+fn import<D: Distance>(
+    wtxn: &mut heed::RwTxn,
+    database: Database<D>,
+    index: u16,
+    index_dimensions: Option<usize>,
+    first_id: u32,
+    path: &Path,
+) -> anyhow::Result<()> {
+    let vectors = VectorReader::open(path)?;
+    let Some(dimensions) = vectors.dimensions() else {
+        bail!("the file {} doesn't contain any vector", path.display());
+    };
+    if let Some(expected) = index_dimensions.filter(|&expected| expected != dimensions) {
+        bail!(
+            "the index {index} has {expected} dimensions but the vectors of {} have {dimensions}",
+            path.display()
+        );
+    }
+
+    let now = Instant::now();
+    let writer = Writer::<D>::new(database, index, dimensions);
+    let count = writer.import_vectors(wtxn, vectors, first_id)?;
+    eprintln!("imported {count} vectors in {:.2?}, the index must be built", now.elapsed());
+    Ok(())
+}
+
+fn export<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+    path: &Path,
+    ids: Option<&Path>,
+) -> anyhow::Result<()> {
+    let reader = Reader::<D>::open(rtxn, index, database)?;
+    let mut vectors = BufWriter::new(File::create(path)?);
+    let mut ids = ids.map(File::create).transpose()?.map(BufWriter::new);
+
+    for result in reader.iter(rtxn)? {
+        let (item, vector) = result?;
+        vectors.write_all(&(vector.len() as i32).to_le_bytes())?;
+        for value in vector {
+            vectors.write_all(&value.to_le_bytes())?;
+        }
+        if let Some(ids) = &mut ids {
+            writeln!(ids, "{item}")?;
+        }
+    }
+    vectors.flush()?;
+    if let Some(ids) = &mut ids {
+        ids.flush()?;
+    }
+
+    let items = reader.item_ids();
+    if ids.is_none() && items.max().is_some_and(|max| max as u64 + 1 != items.len()) {
+        eprintln!("the item IDs are not the positions of the vectors, use --ids to write them");
+    }
+    Ok(())
+}
+
+struct BuildOptions {
+    n_trees: Option<usize>,
+    split_after: Option<usize>,
+    split_dimensions: Option<usize>,
+    reduce_dimensions: Option<usize>,
+    normal_precision: Option<PrecisionArg>,
+    seed: u64,
+}
+
+fn build<D: Distance>(
+    wtxn: &mut heed::RwTxn,
+    database: Database<D>,
+    index: u16,
+    dimensions: usize,
+    options: &BuildOptions,
+) -> anyhow::Result<()> {
+    let writer = Writer::<D>::new(database, index, dimensions);
+    let mut rng = StdRng::seed_from_u64(options.seed);
+    let mut builder = writer.builder(&mut rng);
+    if let Some(n_trees) = options.n_trees {
+        builder.n_trees(n_trees);
+    }
+    if let Some(split_after) = options.split_after {
+        builder.split_after(split_after);
+    }
+    if let Some(split_dimensions) = options.split_dimensions {
+        builder.split_dimensions(split_dimensions);
+    }
+    if let Some(reduce_dimensions) = options.reduce_dimensions {
+        builder.reduce_dimensions(reduce_dimensions);
+    }
+    if let Some(precision) = options.normal_precision {
+        builder.normal_precision(match precision {
+            PrecisionArg::Full => NormalPrecision::Full,
+            PrecisionArg::F16 => NormalPrecision::F16,
+            PrecisionArg::Int8 => NormalPrecision::Int8,
+        });
+    }
+
+    let now = Instant::now();
+    builder.build(wtxn)?;
+    eprintln!("built the index {index} in {:.2?}", now.elapsed());
+    Ok(())
+}
+
+fn dot<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+    tree: usize,
+    mut writer: impl Write,
+) -> anyhow::Result<()> {
+    let reader = Reader::<D>::open(rtxn, index, database)?;
+    if tree >= reader.n_trees() {
+        bail!("the index {index} only has {} trees", reader.n_trees());
+    }
+    reader.plot_tree(rtxn, tree, &mut writer)?;
+    writer.flush()?;
+    eprintln!("convert the graph to a png with `dot -T png > graph.png`, it comes with graphviz");
+    Ok(())
+}
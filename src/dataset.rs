@@ -0,0 +1,476 @@
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::mem::size_of;
+use std::path::Path;
+
+use half::f16;
+
+use crate::{Error, ItemId, Result};
+
+/// The file formats of the vector datasets used by the ANN benchmarks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VectorFormat {
+    /// Every vector is its number of dimensions as a little-endian
+    /// `i32` followed by its little-endian `f32` values.
+    Fvecs,
+    /// Like [`Self::Fvecs`] but the values are `u8`.
+    Bvecs,
+    /// A two dimensional NumPy array of little-endian `float32` or `float16`,
+    /// one vector per row, as saved by `numpy.save`.
+    Npy,
+}
+
+impl VectorFormat {
+    /// Returns the format of a file from its extension.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<VectorFormat> {
+        match path.as_ref().extension()?.to_str()? {
+            "fvecs" => Some(VectorFormat::Fvecs),
+            "bvecs" => Some(VectorFormat::Bvecs),
+            "npy" => Some(VectorFormat::Npy),
+            _ => None,
+        }
+    }
+}
+
+/// The type of the values of the vectors in the file.
+#[derive(Debug, Clone, Copy)]
+enum Element {
+    F32,
+    F16,
+    U8,
+}
+
+impl Element {
+    fn size(&self) -> usize {
+        match self {
+            Element::F32 => size_of::<f32>(),
+            Element::F16 => size_of::<f16>(),
+            Element::U8 => size_of::<u8>(),
+        }
+    }
+}
+
+/// Reads the vectors of a dataset one at a time, the file is never entirely loaded in memory.
+///
+/// ```no_run
+/// use arroy::VectorReader;
+///
+/// # fn main() -> arroy::Result<()> {
+/// let mut vectors = VectorReader::open("sift_base.fvecs")?;
+/// let mut vector = Vec::new();
+/// while vectors.read_vector(&mut vector)? {
+///     println!("{vector:?}");
+/// }
+/// # Ok(()) }
+/// ```
+pub struct VectorReader<R> {
+    reader: BufReader<R>,
+    format: VectorFormat,
+    element: Element,
+    dimensions: Option<usize>,
+    /// The number of rows left to read in a NumPy array.
+    remaining: Option<usize>,
+    /// The dimensions of the next vector of a `.fvecs` or `.bvecs` file,
+    /// read in advance to know the dimensions of the dataset.
+    next_dimensions: Option<usize>,
+    buffer: Vec<u8>,
+}
+
+impl VectorReader<File> {
+    /// Opens a dataset, its format is guessed from the extension of the file.
+    pub fn open(path: impl AsRef<Path>) -> Result<VectorReader<File>> {
+        let path = path.as_ref();
+        let format = VectorFormat::from_path(path).ok_or_else(|| Error::InvalidVectorFile {
+            reason: format!("unknown extension of {}", path.display()),
+        })?;
+        VectorReader::new(File::open(path)?, format)
+    }
+}
+
+impl<R: Read> VectorReader<R> {
+    /// Reads the vectors of the given format, the header of the file is read immediately.
+    pub fn new(reader: R, format: VectorFormat) -> Result<VectorReader<R>> {
+        let mut reader = VectorReader {
+            reader: BufReader::new(reader),
+            format,
+            element: Element::F32,
+            dimensions: None,
+            remaining: None,
+            next_dimensions: None,
+            buffer: Vec::new(),
+        };
+
+        match format {
+            VectorFormat::Fvecs | VectorFormat::Bvecs => {
+                if format == VectorFormat::Bvecs {
+                    reader.element = Element::U8;
+                }
+                reader.next_dimensions = read_dimensions(&mut reader.reader)?;
+                reader.dimensions = reader.next_dimensions;
+            }
+            VectorFormat::Npy => {
+                let NpyHeader { element, rows, dimensions } = read_npy_header(&mut reader.reader)?;
+                reader.element = element;
+                reader.dimensions = Some(dimensions);
+                reader.remaining = Some(rows);
+            }
+        }
+
+        Ok(reader)
+    }
+
+    /// The format of the file.
+    pub fn format(&self) -> VectorFormat {
+        self.format
+    }
+
+    /// The number of dimensions of the vectors, `None` if the file is empty.
+    pub fn dimensions(&self) -> Option<usize> {
+        self.dimensions
+    }
+
+    /// Reads the next vector in `vector` and returns `false` once all the vectors were read.
+    /// Reusing the same `Vec` for all the vectors avoids allocating a new one every time.
+    pub fn read_vector(&mut self, vector: &mut Vec<f32>) -> Result<bool> {
+        let dimensions = match self.format {
+            VectorFormat::Fvecs | VectorFormat::Bvecs => {
+                let dimensions = match self.next_dimensions.take() {
+                    Some(dimensions) => dimensions,
+                    None => match read_dimensions(&mut self.reader)? {
+                        Some(dimensions) => dimensions,
+                        None => return Ok(false),
+                    },
+                };
+                if Some(dimensions) != self.dimensions {
+                    return Err(Error::InvalidVectorFile {
+                        reason: format!(
+                            "a vector has {dimensions} dimensions instead of {}",
+                            self.dimensions.unwrap_or_default()
+                        ),
+                    });
+                }
+                dimensions
+            }
+            VectorFormat::Npy => match &mut self.remaining {
+                Some(0) | None => return Ok(false),
+                Some(remaining) => {
+                    *remaining -= 1;
+                    self.dimensions.unwrap_or_default()
+                }
+            },
+        };
+
+        let len = dimensions.checked_mul(self.element.size()).ok_or_else(|| {
+            Error::InvalidVectorFile {
+                reason: format!("a vector has too many dimensions: {dimensions}"),
+            }
+        })?;
+        read_chunked(&mut self.reader, len, &mut self.buffer)?;
+
+        vector.clear();
+        match self.element {
+            Element::F32 => vector.extend(
+                self.buffer
+                    .chunks_exact(size_of::<f32>())
+                    .map(|b| f32::from_le_bytes(b.try_into().unwrap())),
+            ),
+            Element::F16 => vector.extend(
+                self.buffer
+                    .chunks_exact(size_of::<f16>())
+                    .map(|b| f16::from_le_bytes([b[0], b[1]]).to_f32()),
+            ),
+            Element::U8 => vector.extend(self.buffer.iter().map(|&b| b as f32)),
+        }
+
+        Ok(true)
+    }
+}
+
+impl<R: Read> Iterator for VectorReader<R> {
+    type Item = Result<Vec<f32>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut vector = Vec::new();
+        match self.read_vector(&mut vector) {
+            Ok(true) => Some(Ok(vector)),
+            Ok(false) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+/// Reads the ground truth of a benchmark from an `.ivecs` file: for every query,
+/// the IDs of its nearest neighbors, the position of the vectors in the dataset.
+pub fn read_ivecs(reader: impl Read) -> Result<Vec<Vec<ItemId>>> {
+    let mut reader = BufReader::new(reader);
+    let mut neighbors = Vec::new();
+    let mut buffer = Vec::new();
+    while let Some(len) = read_dimensions(&mut reader)? {
+        let len = len.checked_mul(size_of::<u32>()).ok_or_else(|| Error::InvalidVectorFile {
+            reason: format!("a query has too many neighbors: {len}"),
+        })?;
+        read_chunked(&mut reader, len, &mut buffer)?;
+        neighbors.push(
+            buffer
+                .chunks_exact(size_of::<u32>())
+                .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+                .collect(),
+        );
+    }
+    Ok(neighbors)
+}
+
+/// Reads exactly `len` bytes in the buffer. The bytes are read by chunks
+/// to not allocate a length read from a corrupted file before reaching its end.
+fn read_chunked(reader: &mut impl Read, len: usize, buffer: &mut Vec<u8>) -> Result<()> {
+    buffer.clear();
+    reader.by_ref().take(len as u64).read_to_end(buffer)?;
+    if buffer.len() != len {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+    Ok(())
+}
+
+/// Reads the number of dimensions preceding a vector, returns `None` at the end of the file.
+fn read_dimensions(reader: &mut impl Read) -> Result<Option<usize>> {
+    let mut bytes = [0; size_of::<i32>()];
+    let mut read = 0;
+    while read < bytes.len() {
+        match reader.read(&mut bytes[read..]) {
+            Ok(0) if read == 0 => return Ok(None),
+            Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+            Ok(n) => read += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    match usize::try_from(i32::from_le_bytes(bytes)) {
+        Ok(dimensions) => Ok(Some(dimensions)),
+        Err(_) => {
+            Err(Error::InvalidVectorFile { reason: "a vector has negative dimensions".into() })
+        }
+    }
+}
+
+struct NpyHeader {
+    element: Element,
+    rows: usize,
+    dimensions: usize,
+}
+
+/// Reads the header of a `.npy` file, a Python dictionary describing the array:
+/// `{'descr': '<f4', 'fortran_order': False, 'shape': (1000, 128), }`.
+fn read_npy_header(reader: &mut impl Read) -> Result<NpyHeader> {
+    let invalid = |reason: &str| Error::InvalidVectorFile { reason: reason.to_string() };
+
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic)?;
+    let len = match magic {
+        [0x93, b'N', b'U', b'M', b'P', b'Y', 1, _] => {
+            let mut len = [0; size_of::<u16>()];
+            reader.read_exact(&mut len)?;
+            u16::from_le_bytes(len) as usize
+        }
+        [0x93, b'N', b'U', b'M', b'P', b'Y', 2 | 3, _] => {
+            let mut len = [0; size_of::<u32>()];
+            reader.read_exact(&mut len)?;
+            u32::from_le_bytes(len) as usize
+        }
+        [0x93, b'N', b'U', b'M', b'P', b'Y', ..] => {
+            return Err(invalid("unsupported NumPy version"))
+        }
+        _ => return Err(invalid("not a NumPy file")),
+    };
+
+    let mut header = Vec::new();
+    read_chunked(reader, len, &mut header)?;
+    let header = String::from_utf8(header).map_err(|_| invalid("the NumPy header is not UTF-8"))?;
+
+    let element = match dict_value(&header, "descr").map(|d| d.trim_matches(['\'', '"'])) {
+        Some("<f4") => Element::F32,
+        Some("<f2") => Element::F16,
+        Some(descr) => {
+            return Err(Error::InvalidVectorFile {
+                reason: format!("unsupported NumPy type {descr}, expected <f4 or <f2"),
+            })
+        }
+        None => return Err(invalid("the NumPy header has no descr")),
+    };
+
+    if dict_value(&header, "fortran_order") != Some("False") {
+        return Err(invalid("the NumPy arrays must be in C order"));
+    }
+
+    let shape =
+        dict_value(&header, "shape").ok_or_else(|| invalid("the NumPy header has no shape"))?;
+    let shape = shape
+        .trim_start_matches('(')
+        .split(')')
+        .next()
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse::<usize>().map_err(|_| invalid("the NumPy shape is invalid")))
+        .collect::<Result<Vec<_>>>()?;
+    match shape[..] {
+        [rows, dimensions] => Ok(NpyHeader { element, rows, dimensions }),
+        _ => Err(invalid("the NumPy array must have two dimensions")),
+    }
+}
+
+/// Returns the text following the key in a Python dictionary, up to the next comma
+/// for the scalars and up to the closing parenthesis for the tuples.
+fn dict_value<'a>(header: &'a str, key: &str) -> Option<&'a str> {
+    let start = header.find(&format!("'{key}'"))? + key.len() + 2;
+    let value = header[start..].trim_start().strip_prefix(':')?.trim_start();
+    let end = if value.starts_with('(') {
+        value.find(')')? + 1
+    } else {
+        value.find([',', '}']).unwrap_or(value.len())
+    };
+    Some(value[..end].trim())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn vecs(vectors: &[&[f32]], bytes: bool) -> Vec<u8> {
+        let mut file = Vec::new();
+        for vector in vectors {
+            file.extend((vector.len() as i32).to_le_bytes());
+            for x in vector.iter() {
+                if bytes {
+                    file.push(*x as u8);
+                } else {
+                    file.extend(x.to_le_bytes());
+                }
+            }
+        }
+        file
+    }
+
+    fn npy(header: &str, values: &[u8]) -> Vec<u8> {
+        let mut file = b"\x93NUMPY\x01\x00".to_vec();
+        file.extend((header.len() as u16).to_le_bytes());
+        file.extend(header.as_bytes());
+        file.extend(values);
+        file
+    }
+
+    fn read_all<R: Read>(reader: VectorReader<R>) -> Result<Vec<Vec<f32>>> {
+        reader.collect()
+    }
+
+    #[test]
+    fn fvecs() {
+        let file = vecs(&[&[1.0, 2.5], &[-3.0, 4.0], &[0.0, 0.5]], false);
+        let reader = VectorReader::new(&file[..], VectorFormat::Fvecs).unwrap();
+        assert_eq!(reader.dimensions(), Some(2));
+        insta::assert_debug_snapshot!(read_all(reader).unwrap(), @r###"
+        [
+            [
+                1.0,
+                2.5,
+            ],
+            [
+                -3.0,
+                4.0,
+            ],
+            [
+                0.0,
+                0.5,
+            ],
+        ]
+        "###);
+
+        let reader = VectorReader::new(&[][..], VectorFormat::Fvecs).unwrap();
+        assert_eq!(reader.dimensions(), None);
+        assert!(read_all(reader).unwrap().is_empty());
+
+        let file = vecs(&[&[1.0, 2.5], &[-3.0]], false);
+        let reader = VectorReader::new(&file[..], VectorFormat::Fvecs).unwrap();
+        insta::assert_snapshot!(read_all(reader).unwrap_err(), @"Invalid vector file: a vector has 1 dimensions instead of 2");
+
+        let reader = VectorReader::new(&file[..11], VectorFormat::Fvecs).unwrap();
+        insta::assert_snapshot!(read_all(reader).unwrap_err(), @"unexpected end of file");
+
+        // A corrupted header must not allocate the length it announces
+        let mut file = i32::MAX.to_le_bytes().to_vec();
+        file.extend(1.0f32.to_le_bytes());
+        let reader = VectorReader::new(&file[..], VectorFormat::Fvecs).unwrap();
+        insta::assert_snapshot!(read_all(reader).unwrap_err(), @"unexpected end of file");
+    }
+
+    #[test]
+    fn bvecs() {
+        let file = vecs(&[&[1.0, 2.0, 255.0], &[0.0, 10.0, 3.0]], true);
+        let reader = VectorReader::new(&file[..], VectorFormat::Bvecs).unwrap();
+        assert_eq!(reader.dimensions(), Some(3));
+        assert_eq!(read_all(reader).unwrap(), vec![vec![1.0, 2.0, 255.0], vec![0.0, 10.0, 3.0]]);
+    }
+
+    #[test]
+    fn npy_arrays() {
+        let values: Vec<u8> =
+            [1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0].iter().flat_map(|x| x.to_le_bytes()).collect();
+        let file = npy("{'descr': '<f4', 'fortran_order': False, 'shape': (3, 2), }\n", &values);
+        let reader = VectorReader::new(&file[..], VectorFormat::Npy).unwrap();
+        assert_eq!(reader.dimensions(), Some(2));
+        assert_eq!(read_all(reader).unwrap(), vec![vec![1.0, 2.0], vec![3.0, 4.0], vec![5.0, 6.0]]);
+
+        let values: Vec<u8> =
+            [0.5f32, -2.0, 3.0].iter().flat_map(|&x| f16::from_f32(x).to_le_bytes()).collect();
+        let file = npy("{'descr': '<f2', 'fortran_order': False, 'shape': (1, 3), }\n", &values);
+        let reader = VectorReader::new(&file[..], VectorFormat::Npy).unwrap();
+        assert_eq!(read_all(reader).unwrap(), vec![vec![0.5, -2.0, 3.0]]);
+
+        let file = npy("{'descr': '<f8', 'fortran_order': False, 'shape': (1, 3), }\n", &[]);
+        let error = VectorReader::new(&file[..], VectorFormat::Npy).err().unwrap();
+        insta::assert_snapshot!(error, @"Invalid vector file: unsupported NumPy type <f8, expected <f4 or <f2");
+        let file = npy("{'descr': '<f4', 'fortran_order': True, 'shape': (1, 3), }\n", &[]);
+        let error = VectorReader::new(&file[..], VectorFormat::Npy).err().unwrap();
+        insta::assert_snapshot!(error, @"Invalid vector file: the NumPy arrays must be in C order");
+        let file = npy("{'descr': '<f4', 'fortran_order': False, 'shape': (3,), }\n", &[]);
+        let error = VectorReader::new(&file[..], VectorFormat::Npy).err().unwrap();
+        insta::assert_snapshot!(error, @"Invalid vector file: the NumPy array must have two dimensions");
+        let file = npy(
+            "{'descr': '<f4', 'fortran_order': False, 'shape': (2, 1000000000000), }\n",
+            &values,
+        );
+        let reader = VectorReader::new(&file[..], VectorFormat::Npy).unwrap();
+        insta::assert_snapshot!(read_all(reader).unwrap_err(), @"unexpected end of file");
+        let file = npy(
+            "{'descr': '<f4', 'fortran_order': False, 'shape': (1, 18446744073709551615), }\n",
+            &[],
+        );
+        let reader = VectorReader::new(&file[..], VectorFormat::Npy).unwrap();
+        insta::assert_snapshot!(read_all(reader).unwrap_err(), @"Invalid vector file: a vector has too many dimensions: 18446744073709551615");
+        let error = VectorReader::new(&b"not a numpy file"[..], VectorFormat::Npy).err().unwrap();
+        insta::assert_snapshot!(error, @"Invalid vector file: not a NumPy file");
+    }
+
+    #[test]
+    fn ivecs() {
+        let mut file = Vec::new();
+        for ids in [&[3, 1, 4][..], &[1, 5, 9]] {
+            file.extend(3i32.to_le_bytes());
+            file.extend(ids.iter().flat_map(|id: &u32| id.to_le_bytes()));
+        }
+        assert_eq!(read_ivecs(&file[..]).unwrap(), vec![vec![3, 1, 4], vec![1, 5, 9]]);
+
+        file.extend(i32::MAX.to_le_bytes());
+        insta::assert_snapshot!(read_ivecs(&file[..]).unwrap_err(), @"unexpected end of file");
+    }
+
+    #[test]
+    fn formats() {
+        assert_eq!(VectorFormat::from_path("sift/sift_base.fvecs"), Some(VectorFormat::Fvecs));
+        assert_eq!(VectorFormat::from_path("bigann_base.bvecs"), Some(VectorFormat::Bvecs));
+        assert_eq!(VectorFormat::from_path("embeddings.npy"), Some(VectorFormat::Npy));
+        assert_eq!(VectorFormat::from_path("vectors.txt"), None);
+        assert_eq!(VectorFormat::from_path("vectors"), None);
+    }
+}
@@ -20,7 +20,7 @@ pub enum Cosine {}
 #[repr(C)]
 #[derive(Pod, Zeroable, Debug, Clone, Copy)]
 pub struct NodeHeaderCosine {
-    norm: f32,
+    pub(crate) norm: f32,
 }
 
 impl Distance for Cosine {
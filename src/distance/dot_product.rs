@@ -1,7 +1,7 @@
 use std::borrow::Cow;
 
 use bytemuck::{Pod, Zeroable};
-use heed::{RwPrefix, RwTxn};
+use heed::{RoPrefix, RoTxn, RwPrefix, RwTxn};
 use rand::Rng;
 
 use super::two_means;
@@ -23,9 +23,9 @@ pub enum DotProduct {}
 #[repr(C)]
 #[derive(Pod, Zeroable, Debug, Clone, Copy)]
 pub struct NodeHeaderDotProduct {
-    extra_dim: f32,
+    pub(crate) extra_dim: f32,
     /// An extra constant term to determine the offset of the plane
-    norm: f32,
+    pub(crate) norm: f32,
 }
 
 impl Distance for DotProduct {
@@ -87,6 +87,14 @@ impl Distance for DotProduct {
         node.header.norm = dot_product(&node.vector, &node.vector);
     }
 
+    fn reduced_leaf<'a>(
+        leaf: &Leaf<Self>,
+        vector: Cow<'a, UnalignedVector<Self::VectorCodec>>,
+    ) -> Leaf<'a, Self> {
+        // The extra dimension computed when preprocessing the vector must be kept
+        Leaf { header: leaf.header, vector }
+    }
+
     fn create_split<'a, R: Rng>(
         children: &'a ImmutableSubsetLeafs<Self>,
         rng: &mut R,
@@ -149,12 +157,8 @@ impl Distance for DotProduct {
                 None => break,
             };
 
-            let node_norm = Self::norm_no_header(&leaf.vector);
-            let squared_norm_diff = (max_norm * max_norm) - (node_norm * node_norm);
-
             let mut leaf = leaf.into_owned();
-            leaf.header.norm = max_norm * max_norm;
-            leaf.header.extra_dim = squared_norm_diff.sqrt();
+            leaf.header = preprocessed_header(max_norm, &leaf.vector);
 
             // safety: We do not keep a reference to the current value, we own it.
             unsafe { cursor.put_current(&item_id, &Node::Leaf(leaf))? };
@@ -162,4 +166,34 @@ impl Distance for DotProduct {
 
         Ok(())
     }
+
+    fn is_preprocessed(
+        rtxn: &RoTxn,
+        new_iter: impl for<'a> Fn(&'a RoTxn) -> heed::Result<RoPrefix<'a, KeyCodec, NodeCodec<Self>>>,
+    ) -> heed::Result<bool> {
+        let mut max_norm = 0.0;
+        for result in new_iter(rtxn)? {
+            let (_item_id, node) = result?;
+            let Some(leaf) = node.leaf() else { break };
+            max_norm = f32::max(max_norm, Self::norm_no_header(&leaf.vector));
+        }
+
+        for result in new_iter(rtxn)? {
+            let (_item_id, node) = result?;
+            let Some(leaf) = node.leaf() else { break };
+            let expected = preprocessed_header(max_norm, &leaf.vector);
+            if leaf.header.norm != expected.norm || leaf.header.extra_dim != expected.extra_dim {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+/// Computes the header of a leaf once the maximum norm of all the items is known.
+fn preprocessed_header(max_norm: f32, vector: &UnalignedVector<f32>) -> NodeHeaderDotProduct {
+    let node_norm = DotProduct::norm_no_header(vector);
+    let squared_norm_diff = (max_norm * max_norm) - (node_norm * node_norm);
+    NodeHeaderDotProduct { extra_dim: squared_norm_diff.sqrt(), norm: max_norm * max_norm }
 }
@@ -22,7 +22,7 @@ pub enum Euclidean {}
 #[derive(Pod, Zeroable, Debug, Clone, Copy)]
 pub struct NodeHeaderEuclidean {
     /// An extra constant term to determine the offset of the plane
-    bias: f32,
+    pub(crate) bias: f32,
 }
 
 impl Distance for Euclidean {
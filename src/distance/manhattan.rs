@@ -21,7 +21,7 @@ pub enum Manhattan {}
 #[derive(Pod, Zeroable, Debug, Clone, Copy)]
 pub struct NodeHeaderManhattan {
     /// An extra constant term to determine the offset of the plane
-    bias: f32,
+    pub(crate) bias: f32,
 }
 
 impl Distance for Manhattan {
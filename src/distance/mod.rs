@@ -12,7 +12,7 @@ use bytemuck::{Pod, Zeroable};
 pub use cosine::{Cosine, NodeHeaderCosine};
 pub use dot_product::{DotProduct, NodeHeaderDotProduct};
 pub use euclidean::{Euclidean, NodeHeaderEuclidean};
-use heed::{RwPrefix, RwTxn};
+use heed::{RoPrefix, RoTxn, RwPrefix, RwTxn};
 pub use manhattan::{Manhattan, NodeHeaderManhattan};
 use rand::Rng;
 
@@ -94,6 +94,23 @@ pub trait Distance: Send + Sync + Sized + Clone + fmt::Debug + 'static {
         mean.vector = UnalignedVector::from_vec(vec);
     }
 
+    /// Restricts the leaf to its first dimensions to compute the split planes on them.
+    fn prefix_leaf(leaf: Leaf<'_, Self>, dimensions: usize) -> Leaf<'_, Self> {
+        let vector = match leaf.vector {
+            Cow::Borrowed(vector) => Cow::Borrowed(vector.prefix(dimensions)),
+            Cow::Owned(_) => Cow::Owned(leaf.vector.prefix(dimensions).to_owned()),
+        };
+        Self::reduced_leaf(&leaf, vector)
+    }
+
+    /// Creates the leaf of a vector derived from the one of `leaf` with fewer dimensions.
+    fn reduced_leaf<'a>(
+        _leaf: &Leaf<Self>,
+        vector: Cow<'a, UnalignedVector<Self::VectorCodec>>,
+    ) -> Leaf<'a, Self> {
+        Leaf { header: Self::new_header(&vector), vector }
+    }
+
     fn create_split<'a, R: Rng>(
         children: &'a ImmutableSubsetLeafs<Self>,
         rng: &mut R,
@@ -131,6 +148,14 @@ pub trait Distance: Send + Sync + Sized + Clone + fmt::Debug + 'static {
     ) -> heed::Result<()> {
         Ok(())
     }
+
+    /// Returns `false` if [`Self::preprocess`] would modify any of the items.
+    fn is_preprocessed(
+        _rtxn: &RoTxn,
+        _new_iter: impl for<'a> Fn(&'a RoTxn) -> heed::Result<RoPrefix<'a, KeyCodec, NodeCodec<Self>>>,
+    ) -> heed::Result<bool> {
+        Ok(true)
+    }
 }
 
 fn two_means<D: Distance, R: Rng>(
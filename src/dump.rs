@@ -0,0 +1,430 @@
+use std::io::{self, Read, Write};
+use std::mem::size_of;
+
+use half::f16;
+use heed::types::Bytes;
+use heed::{BoxedError, BytesDecode, RoTxn, RwTxn};
+
+use crate::error::DecodeError;
+use crate::internals::KeyCodec;
+use crate::node::{
+    LEAF_TAG, SPLIT_PLANE_NORMAL_F16_TAG, SPLIT_PLANE_NORMAL_INT8_TAG, SPLIT_PLANE_NORMAL_TAG,
+};
+use crate::roaring::RoaringBitmapCodec;
+use crate::unaligned_vector::UnalignedVectorCodec;
+use crate::{
+    BuildConfigCodec, Database, Distance, Error, Key, MetadataCodec, NodeCodec, NodeId, NodeMode,
+    PendingBuildCodec, Prefix, PrefixCodec, ProjectionCodec, Result, SearchTuningCodec,
+    VersionCodec,
+};
+
+/// The bytes every dump starts with.
+const MAGIC: &[u8; 8] = b"ARROYDMP";
+
+/// The version of the layout of the dumps, the values are in the format of the index.
+const DUMP_VERSION: u32 = 1;
+
+/// The byte replacing the mode of a node ID after the last value.
+const END: u8 = u8::MAX;
+
+/// Writes all the values of an index in a dump.
+///
+/// A dump is a header with the distance and the dimensions of the index, followed by the node IDs
+/// and the values of the index in the order of the database, then the number of values and the
+/// CRC-32 of all the preceding bytes. The integers are in little-endian, and the words the values
+/// store in the native byte order are converted to little-endian.
+pub(crate) fn dump<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+    dimensions: usize,
+    writer: impl Write,
+) -> Result<()> {
+    let mut writer = ChecksumWriter::new(io::BufWriter::new(writer));
+    writer.write_all(MAGIC)?;
+    writer.write_all(&DUMP_VERSION.to_le_bytes())?;
+    writer.write_all(&dimensions_to_u32(dimensions)?.to_le_bytes())?;
+    writer.write_all(&[D::name().len() as u8])?;
+    writer.write_all(D::name().as_bytes())?;
+
+    let mut count = 0u64;
+    let mut value = Vec::new();
+    let iter = database
+        .remap_types::<PrefixCodec, Bytes>()
+        .prefix_iter(rtxn, &Prefix::all(index))?
+        .remap_key_type::<KeyCodec>();
+    for result in iter {
+        let (key, bytes) = result?;
+        value.clear();
+        value.extend_from_slice(bytes);
+        convert_native_words::<D>(key.node, &mut value)
+            .map_err(|e| Error::corrupted_database(key, e))?;
+
+        writer.write_all(&key.node.to_bytes())?;
+        writer.write_all(&(value.len() as u32).to_le_bytes())?;
+        writer.write_all(&value)?;
+        count += 1;
+    }
+
+    writer.write_all(&[END])?;
+    writer.write_all(&count.to_le_bytes())?;
+    let checksum = writer.crc.finish();
+    writer.inner.write_all(&checksum.to_le_bytes())?;
+    writer.inner.flush()?;
+
+    log::debug!("dumped {count} values of the index {index}");
+    Ok(())
+}
+
+/// Writes the values of a dump in an empty index, they are checked with their codecs
+/// before being written. The checksum is only known once the whole dump is read.
+pub(crate) fn restore<D: Distance>(
+    wtxn: &mut RwTxn,
+    database: Database<D>,
+    index: u16,
+    dimensions: usize,
+    reader: impl Read,
+) -> Result<()> {
+    let invalid = |reason: String| Error::InvalidDump { reason };
+    let mut reader = ChecksumReader::new(io::BufReader::new(reader));
+
+    let mut magic = [0; MAGIC.len()];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid("not an arroy dump".to_string()));
+    }
+    let version = read_u32(&mut reader)?;
+    if version > DUMP_VERSION {
+        return Err(invalid(format!(
+            "the dump is in the version {version} but only the versions up to {DUMP_VERSION} are supported"
+        )));
+    }
+    let dump_dimensions = read_u32(&mut reader)? as usize;
+    let mut distance = vec![0; read_bytes::<1>(&mut reader)?[0] as usize];
+    reader.read_exact(&mut distance)?;
+    let distance = String::from_utf8_lossy(&distance);
+    if distance != D::name() {
+        return Err(Error::UnmatchingDistance {
+            expected: distance.into_owned(),
+            received: D::name(),
+        });
+    }
+    if dump_dimensions != dimensions {
+        return Err(Error::InvalidVecDimension { expected: dimensions, received: dump_dimensions });
+    }
+
+    let mut count = 0u64;
+    let mut value = Vec::new();
+    loop {
+        let [mode] = read_bytes(&mut reader)?;
+        if mode == END {
+            break;
+        }
+        let [a, b, c, d] = read_bytes(&mut reader)?;
+        let (node, _) =
+            NodeId::from_bytes(&[mode, a, b, c, d]).map_err(|e| invalid(e.to_string()))?;
+        let key = Key::new(index, node);
+
+        let len = read_u32(&mut reader)? as u64;
+        value.clear();
+        // The value is read by chunks to not allocate the length of a corrupted dump
+        reader.by_ref().take(len).read_to_end(&mut value)?;
+        if value.len() as u64 != len {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        convert_native_words::<D>(node, &mut value)
+            .map_err(BoxedError::from)
+            .and_then(|()| check_value::<D>(node, &value))
+            .map_err(|e| invalid(format!("the value of {node:?} cannot be decoded: {e}")))?;
+
+        database.remap_data_type::<Bytes>().put(wtxn, &key, &value)?;
+        count += 1;
+    }
+
+    let dump_count = u64::from_le_bytes(read_bytes(&mut reader)?);
+    let checksum = reader.crc.finish();
+    let dump_checksum = u32::from_le_bytes(read_bytes(&mut reader.inner)?);
+    if dump_checksum != checksum {
+        return Err(invalid("the checksum doesn't match, the dump is corrupted".to_string()));
+    }
+    if dump_count != count {
+        return Err(invalid(format!("expected {dump_count} values but found {count}")));
+    }
+
+    log::debug!("restored {count} values in the index {index}");
+    Ok(())
+}
+
+fn dimensions_to_u32(dimensions: usize) -> Result<u32> {
+    u32::try_from(dimensions).map_err(|_| Error::InvalidDump {
+        reason: "the number of dimensions must fit in 32 bits".to_string(),
+    })
+}
+
+fn read_bytes<const N: usize>(reader: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    read_bytes(reader).map(u32::from_le_bytes)
+}
+
+/// Decodes the value with the codec of its key.
+fn check_value<D: Distance>(node: NodeId, value: &[u8]) -> Result<(), BoxedError> {
+    match node.mode {
+        NodeMode::Item | NodeMode::Tree => NodeCodec::<D>::bytes_decode(value).map(drop),
+        NodeMode::Updated if value.is_empty() => Ok(()),
+        NodeMode::Updated => Err(DecodeError::Invalid("an updated item has a value").into()),
+        NodeMode::Metadata if node == NodeId::metadata() => {
+            MetadataCodec::bytes_decode(value).map(drop)
+        }
+        NodeMode::Metadata if node == NodeId::legacy_updated_items() => {
+            RoaringBitmapCodec::bytes_decode(value).map(drop)
+        }
+        NodeMode::Metadata if node == NodeId::pending_build() => {
+            PendingBuildCodec::bytes_decode(value).map(drop)
+        }
+        NodeMode::Metadata if node == NodeId::build_config() => {
+            BuildConfigCodec::bytes_decode(value).map(drop)
+        }
+        NodeMode::Metadata if node == NodeId::projection() => {
+            ProjectionCodec::bytes_decode(value).map(drop)
+        }
+        NodeMode::Metadata if node == NodeId::version() => {
+            VersionCodec::bytes_decode(value).map(drop)
+        }
+        NodeMode::Metadata if node == NodeId::search_tuning() => {
+            SearchTuningCodec::bytes_decode(value).map(drop)
+        }
+        NodeMode::Metadata => Err(DecodeError::Invalid("unknown metadata").into()),
+    }
+}
+
+/// Converts the words of the value that are stored in the native byte order from or to
+/// little-endian, the byte order of the dumps. There is nothing to do on little-endian targets.
+fn convert_native_words<D: Distance>(node: NodeId, value: &mut [u8]) -> Result<(), DecodeError> {
+    if cfg!(target_endian = "little") {
+        Ok(())
+    } else {
+        swap_native_words::<D>(node, value)
+    }
+}
+
+/// Reverses the bytes of every word of the value that is stored in the native byte order.
+///
+/// The headers of the leafs are made of `f32`s, the vectors and the normals of the split planes
+/// of the words of the codec of the distance, and the roots and the projection of `u32`s and `f32`s.
+/// The other values are either big-endian, bytes, or roaring bitmaps that are always little-endian.
+fn swap_native_words<D: Distance>(node: NodeId, value: &mut [u8]) -> Result<(), DecodeError> {
+    // The left and right node IDs of the split planes
+    const CHILDREN: usize = 2 * (size_of::<NodeMode>() + size_of::<u32>());
+    let vector_word = <D::VectorCodec as UnalignedVectorCodec>::WORD_SIZE;
+
+    match node.mode {
+        NodeMode::Item | NodeMode::Tree => match value {
+            [LEAF_TAG, bytes @ ..] => {
+                let (header, vector) = split_at(bytes, size_of::<D::Header>(), "leaf header")?;
+                swap_words(header, size_of::<f32>(), "leaf header")?;
+                swap_words(vector, vector_word, "leaf vector")
+            }
+            [SPLIT_PLANE_NORMAL_TAG, bytes @ ..] => {
+                let (_, normal) = split_at(bytes, CHILDREN, "node id")?;
+                swap_words(normal, vector_word, "normal")
+            }
+            [SPLIT_PLANE_NORMAL_F16_TAG, bytes @ ..] => {
+                let (_, normal) = split_at(bytes, CHILDREN, "node id")?;
+                swap_words(normal, size_of::<f16>(), "f16 normal")
+            }
+            [SPLIT_PLANE_NORMAL_INT8_TAG, bytes @ ..] => {
+                let (_, bytes) = split_at(bytes, CHILDREN, "node id")?;
+                let (scale, _) = split_at(bytes, size_of::<f32>(), "int8 normal scale")?;
+                swap_words(scale, size_of::<f32>(), "int8 normal scale")
+            }
+            // The descendants are stored as varints or roaring bitmaps
+            _ => Ok(()),
+        },
+        NodeMode::Metadata if node == NodeId::metadata() => {
+            let distance = value
+                .iter()
+                .position(|&b| b == 0)
+                .ok_or(DecodeError::Invalid("the distance name is not terminated"))?;
+            let (_, bytes) = split_at(value, distance + 1 + size_of::<u32>(), "dimensions")?;
+            let (items_size, bytes) = split_at(bytes, size_of::<u32>(), "items size")?;
+            let items_size = u32::from_be_bytes(items_size.try_into().unwrap()) as usize;
+            let (_, roots) = split_at(bytes, items_size, "items")?;
+            swap_words(roots, size_of::<u32>(), "roots")
+        }
+        NodeMode::Metadata if node == NodeId::pending_build() => {
+            let (_, roots) = split_at(value, size_of::<u64>(), "fingerprint")?;
+            swap_words(roots, size_of::<u32>(), "roots")
+        }
+        NodeMode::Metadata if node == NodeId::projection() => {
+            let (_, matrix) = split_at(value, size_of::<u32>(), "dimensions")?;
+            swap_words(matrix, size_of::<f32>(), "projection matrix")
+        }
+        NodeMode::Metadata | NodeMode::Updated => Ok(()),
+    }
+}
+
+fn split_at<'a>(
+    bytes: &'a mut [u8],
+    len: usize,
+    what: &'static str,
+) -> Result<(&'a mut [u8], &'a mut [u8]), DecodeError> {
+    if bytes.len() < len {
+        return Err(DecodeError::Truncated { what, expected: len, remaining: bytes.len() });
+    }
+    Ok(bytes.split_at_mut(len))
+}
+
+fn swap_words(bytes: &mut [u8], size: usize, what: &'static str) -> Result<(), DecodeError> {
+    DecodeError::check_aligned(bytes, size, what)?;
+    bytes.chunks_exact_mut(size).for_each(<[u8]>::reverse);
+    Ok(())
+}
+
+/// The CRC-32 used by zlib and PNG.
+struct Crc32 {
+    state: u32,
+}
+
+impl Crc32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut crc = i as u32;
+            let mut bit = 0;
+            while bit < 8 {
+                crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+                bit += 1;
+            }
+            table[i] = crc;
+            i += 1;
+        }
+        table
+    };
+
+    fn new() -> Crc32 {
+        Crc32 { state: u32::MAX }
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.state =
+                Self::TABLE[((self.state ^ byte as u32) & 0xFF) as usize] ^ (self.state >> 8);
+        }
+    }
+
+    fn finish(&self) -> u32 {
+        !self.state
+    }
+}
+
+struct ChecksumWriter<W> {
+    inner: W,
+    crc: Crc32,
+}
+
+impl<W> ChecksumWriter<W> {
+    fn new(inner: W) -> Self {
+        ChecksumWriter { inner, crc: Crc32::new() }
+    }
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.crc.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+struct ChecksumReader<R> {
+    inner: R,
+    crc: Crc32,
+}
+
+impl<R> ChecksumReader<R> {
+    fn new(inner: R) -> Self {
+        ChecksumReader { inner, crc: Crc32::new() }
+    }
+}
+
+impl<R: Read> Read for ChecksumReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.crc.update(&buf[..read]);
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use heed::BytesEncode;
+
+    use super::*;
+    use crate::distance::{BinaryQuantizedEuclidean, Euclidean};
+    use crate::node::{Leaf, Node, SplitPlaneNormal};
+    use crate::unaligned_vector::UnalignedVector;
+    use crate::NormalPrecision;
+
+    #[test]
+    fn crc32() {
+        let mut crc = Crc32::new();
+        crc.update(b"123456789");
+        assert_eq!(crc.finish(), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn swap_leaf() {
+        let vector = UnalignedVector::from_vec(vec![1.0, -2.0]);
+        let leaf = Node::Leaf(Leaf::<Euclidean> { header: Euclidean::new_header(&vector), vector });
+        let bytes = NodeCodec::bytes_encode(&leaf).unwrap().into_owned();
+
+        let mut swapped = bytes.clone();
+        swap_native_words::<Euclidean>(NodeId::item(0), &mut swapped).unwrap();
+        let mut expected = vec![LEAF_TAG];
+        for x in [0.0f32, 1.0, -2.0] {
+            expected.extend(if cfg!(target_endian = "little") {
+                x.to_be_bytes()
+            } else {
+                x.to_le_bytes()
+            });
+        }
+        assert_eq!(swapped, expected);
+
+        swap_native_words::<Euclidean>(NodeId::item(0), &mut swapped).unwrap();
+        assert_eq!(swapped, bytes);
+    }
+
+    #[test]
+    fn swap_split_planes() {
+        for precision in [NormalPrecision::Full, NormalPrecision::F16, NormalPrecision::Int8] {
+            let node = Node::SplitPlaneNormal(SplitPlaneNormal::<BinaryQuantizedEuclidean> {
+                left: NodeId::tree(1),
+                right: NodeId::item(2),
+                normal: UnalignedVector::from_vec(vec![1.0; 100]),
+                precision,
+            });
+            let bytes = NodeCodec::bytes_encode(&node).unwrap().into_owned();
+
+            let mut swapped = bytes.clone();
+            swap_native_words::<BinaryQuantizedEuclidean>(NodeId::tree(0), &mut swapped).unwrap();
+            // The node IDs are in big-endian and never swapped
+            assert_eq!(swapped[..11], bytes[..11]);
+            swap_native_words::<BinaryQuantizedEuclidean>(NodeId::tree(0), &mut swapped).unwrap();
+            assert_eq!(swapped, bytes);
+        }
+
+        let error =
+            swap_native_words::<Euclidean>(NodeId::tree(0), &mut [LEAF_TAG, 0, 0]).unwrap_err();
+        insta::assert_snapshot!(error, @"leaf header: expected 4 bytes but only 2 remain");
+    }
+}
@@ -40,6 +40,17 @@ pub enum Error {
         received: &'static str,
     },
 
+    /// The vectors of an index cannot be converted to the requested number of dimensions.
+    #[error("Cannot change the dimensions of the index from {from} to {to}: {reason}")]
+    InvalidDimensionChange {
+        /// The current number of dimensions of the index.
+        from: usize,
+        /// The requested number of dimensions.
+        to: usize,
+        /// Why the conversion is not possible.
+        reason: &'static str,
+    },
+
     /// Arroy is not able to find the metadata for a given index.
     /// It is probably because the user forget to build the database.
     #[error(
@@ -47,6 +58,22 @@ pub enum Error {
     )]
     MissingMetadata(u16),
 
+    /// The index was written by a newer version of arroy with a format this one cannot read.
+    #[error("The index {index} is written in the format version {version} but only the versions up to {supported} are supported")]
+    UnsupportedVersion {
+        /// The index that was opened.
+        index: u16,
+        /// The version of the format the index is written in.
+        version: u32,
+        /// The latest version of the format this version of arroy supports.
+        supported: u32,
+    },
+
+    /// The index was written by an older version of arroy and must be
+    /// migrated with [`Writer::upgrade`](crate::Writer::upgrade) before being built.
+    #[error("The index {0} is written in an older format and must be upgraded")]
+    NeedUpgrade(u16),
+
     /// The last time items in the database were updated, the [`Writer::build`] method wasn't called.
     #[error("The trees have not been built after an update on index {0}")]
     NeedBuild(u16),
@@ -55,6 +82,76 @@ pub enum Error {
     #[error("The corresponding build process has been cancelled")]
     BuildCancelled,
 
+    /// The items must be preprocessed with [`Writer::preprocess`](crate::Writer::preprocess)
+    /// before the build is prepared.
+    #[error("The items of the index {0} must be preprocessed before preparing the build")]
+    NeedPreprocess(u16),
+
+    /// The items or the trees of the index were modified after the build was prepared.
+    #[error("The index {0} was modified since the build was prepared, it must be prepared again")]
+    OutdatedBuild(u16),
+
+    /// A value of the database cannot be decoded, it was either corrupted
+    /// or written by a version of arroy that this one doesn't understand.
+    #[error("Corrupted database: {mode}({item}) of index `{index}` cannot be decoded: {reason}")]
+    CorruptedDatabase {
+        /// The index that contains the value.
+        index: u16,
+        /// The kind of item that was being decoded.
+        mode: &'static str,
+        /// The item ID of the value.
+        item: ItemId,
+        /// Why the value cannot be decoded.
+        reason: String,
+    },
+
+    /// The index cannot be written in the Annoy file format.
+    #[error("The index {index} cannot be exported to Annoy: {reason}")]
+    AnnoyExport {
+        /// The index that was exported.
+        index: u16,
+        /// Why the index cannot be exported.
+        reason: &'static str,
+    },
+
+    /// The Annoy file cannot be imported, it doesn't match the metric
+    /// and the dimensions of the index or it is corrupted.
+    #[error("Cannot import the Annoy file in the index {index}: {reason}")]
+    AnnoyImport {
+        /// The index the file was imported in.
+        index: u16,
+        /// Why the file cannot be imported.
+        reason: &'static str,
+    },
+
+    /// A file of vectors is not in the expected format or is corrupted.
+    #[error("Invalid vector file: {reason}")]
+    InvalidVectorFile {
+        /// Why the file cannot be read.
+        reason: String,
+    },
+
+    /// The dump cannot be restored, it is not a dump of arroy or it is corrupted.
+    #[error("Invalid dump: {reason}")]
+    InvalidDump {
+        /// Why the dump cannot be restored.
+        reason: String,
+    },
+
+    /// An option of the [`ArroyBuilder`](crate::ArroyBuilder) doesn't fit the index.
+    #[error("Invalid build option: {reason}")]
+    InvalidBuildOption {
+        /// Why the option cannot be used.
+        reason: String,
+    },
+
+    /// The queries and the neighbors of a ground truth don't match.
+    #[error("Invalid ground truth: {reason}")]
+    InvalidGroundTruth {
+        /// Why the ground truth cannot be used.
+        reason: String,
+    },
+
     /// Internal error
     #[error("Internal error: {mode}({item}) is missing in index `{index}`")]
     MissingKey {
@@ -69,15 +166,83 @@ pub enum Error {
 
 impl Error {
     pub(crate) fn missing_key(key: Key) -> Self {
-        Self::MissingKey {
+        Self::MissingKey { index: key.index, mode: mode_name(key.node.mode), item: key.node.item }
+    }
+
+    pub(crate) fn corrupted_database(key: Key, reason: impl ToString) -> Self {
+        Self::CorruptedDatabase {
             index: key.index,
-            mode: match key.node.mode {
-                NodeMode::Item => "Item",
-                NodeMode::Tree => "Tree",
-                NodeMode::Metadata => "Metadata",
-                NodeMode::Updated => "Updated",
-            },
+            mode: mode_name(key.node.mode),
             item: key.node.item,
+            reason: reason.to_string(),
+        }
+    }
+}
+
+fn mode_name(mode: NodeMode) -> &'static str {
+    match mode {
+        NodeMode::Item => "Item",
+        NodeMode::Tree => "Tree",
+        NodeMode::Metadata => "Metadata",
+        NodeMode::Updated => "Updated",
+    }
+}
+
+/// Attaches the key that was read to the errors of the codecs.
+pub(crate) trait KeyContext<T> {
+    fn with_key(self, key: Key) -> Result<T, Error>;
+}
+
+impl<T> KeyContext<T> for heed::Result<T> {
+    fn with_key(self, key: Key) -> Result<T, Error> {
+        self.map_err(|error| match error {
+            heed::Error::Decoding(reason) => Error::corrupted_database(key, reason),
+            error => Error::Heed(error),
+        })
+    }
+}
+
+/// The reasons the codecs can fail to decode a value of the database.
+#[derive(Debug, thiserror::Error)]
+pub enum DecodeError {
+    #[error("{what}: expected {expected} bytes but only {remaining} remain")]
+    Truncated { what: &'static str, expected: usize, remaining: usize },
+    #[error("{what}: expected {expected} bytes but got {len}")]
+    WrongLength { what: &'static str, expected: usize, len: usize },
+    #[error("{what}: {len} bytes cannot be split in values of {size} bytes")]
+    Unaligned { what: &'static str, len: usize, size: usize },
+    #[error("unknown node tag {0}")]
+    UnknownTag(u8),
+    #[error("unknown node mode {0}")]
+    UnknownMode(u8),
+    #[error("{0}")]
+    Invalid(&'static str),
+}
+
+impl DecodeError {
+    /// Splits the first `len` bytes of `bytes` off, or returns an error if there are not enough.
+    pub(crate) fn take<'a>(
+        bytes: &mut &'a [u8],
+        len: usize,
+        what: &'static str,
+    ) -> Result<&'a [u8], DecodeError> {
+        if bytes.len() < len {
+            return Err(DecodeError::Truncated { what, expected: len, remaining: bytes.len() });
+        }
+        let (head, tail) = bytes.split_at(len);
+        *bytes = tail;
+        Ok(head)
+    }
+
+    /// Returns an error if `bytes` cannot be split in values of `size` bytes.
+    pub(crate) fn check_aligned(
+        bytes: &[u8],
+        size: usize,
+        what: &'static str,
+    ) -> Result<(), DecodeError> {
+        match bytes.len() % size {
+            0 => Ok(()),
+            _ => Err(DecodeError::Unaligned { what, len: bytes.len(), size }),
         }
     }
 }
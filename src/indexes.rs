@@ -0,0 +1,78 @@
+use std::ops::Bound;
+
+use heed::types::DecodeIgnore;
+use heed::RoTxn;
+
+use crate::error::KeyContext;
+use crate::{Database, Distance, Key, MetadataCodec, Prefix, PrefixCodec, Result, VersionCodec};
+
+/// A summary of an index of the database, returned by [`list_indexes`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexInfo {
+    /// The number of the index.
+    pub index: u16,
+    /// The version of the format the index is written in, see [`FORMAT_VERSION`](crate::FORMAT_VERSION).
+    pub format_version: u32,
+    /// The name of the distance of the index, `None` if it was never built.
+    pub distance: Option<String>,
+    /// The number of dimensions of the vectors, `None` if the index was never built.
+    pub dimensions: Option<usize>,
+    /// The number of items of the last build, `None` if the index was never built.
+    pub n_items: Option<u64>,
+    /// The number of trees of the last build, `None` if the index was never built.
+    pub n_trees: Option<usize>,
+    /// Whether items were updated since the last build.
+    pub needs_build: bool,
+}
+
+/// Returns the indexes stored in the database, in order, with their metadata.
+///
+/// The distance of the database doesn't matter, the indexes of all the distances are listed.
+///
+/// # Example
+///
+/// ```no_run
+/// # use arroy::{distances::Euclidean, Database};
+/// # let (database, rtxn): (Database<Euclidean>, heed::RoTxn) = todo!();
+/// for info in arroy::list_indexes(&rtxn, database)? {
+///     println!("{}: {:?} with {:?} items", info.index, info.distance, info.n_items);
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn list_indexes<D: Distance>(rtxn: &RoTxn, database: Database<D>) -> Result<Vec<IndexInfo>> {
+    let mut indexes = Vec::new();
+    let mut next = Some(0);
+    // We jump from the first key of an index to the first key of the next one
+    while let Some(index) = next {
+        let range = (Bound::Included(Key::metadata(index)), Bound::Unbounded);
+        let mut iter = database.remap_data_type::<DecodeIgnore>().range(rtxn, &range)?;
+        let Some((key, ())) = iter.next().transpose()? else { break };
+        let index = key.index;
+        next = index.checked_add(1);
+
+        let key = Key::metadata(index);
+        let metadata = database.remap_data_type::<MetadataCodec>().get(rtxn, &key).with_key(key)?;
+        let key = Key::version(index);
+        let format_version =
+            database.remap_data_type::<VersionCodec>().get(rtxn, &key).with_key(key)?;
+        let needs_build = database
+            .remap_types::<PrefixCodec, DecodeIgnore>()
+            .prefix_iter(rtxn, &Prefix::updated(index))?
+            .remap_key_type::<DecodeIgnore>()
+            .next()
+            .is_some();
+
+        indexes.push(IndexInfo {
+            index,
+            // The indexes written before the format was versioned don't store it
+            format_version: format_version.unwrap_or_default(),
+            distance: metadata.as_ref().map(|m| m.distance.to_string()),
+            dimensions: metadata.as_ref().map(|m| m.dimensions as usize),
+            n_items: metadata.as_ref().map(|m| m.items.len()),
+            n_trees: metadata.as_ref().map(|m| m.roots.len()),
+            needs_build,
+        });
+    }
+
+    Ok(indexes)
+}
@@ -4,6 +4,7 @@ use std::mem::size_of;
 use byteorder::{BigEndian, ByteOrder};
 use heed::BoxedError;
 
+use crate::error::DecodeError;
 use crate::{NodeId, NodeMode};
 
 /// This whole structure must fit in an u64 so we can tell LMDB to optimize its storage.
@@ -14,7 +15,13 @@ use crate::{NodeId, NodeMode};
 ///  - `Item`: we're looking at a `Leaf` node.
 ///  - `Tree`: we're looking at one of the internal generated node from arroy. Could be a descendants or a split plane.
 ///  - `Updated`: The list of items that has been updated since the last build of the database.
-///  - `Metadata`: There is only one item at `0` that contains the header required to read the index.
+///  - `Metadata`: The item at `0` contains the header required to read the index.
+///    The item at `1` contains the updated items of the indexes written by older versions of arroy.
+///    The item at `2` contains the trees of a build that is not finished yet.
+///    The item at `3` contains the options used to build the trees.
+///    The item at `4` contains the projection applied to the items to compute the split planes.
+///    The item at `5` contains the version of the format the index is written in.
+///    The item at `6` contains the `search_k` tuned for a target recall.
 #[derive(Debug, Copy, Clone)]
 pub struct Key {
     /// The prefix specified by the user.
@@ -33,6 +40,30 @@ impl Key {
         Self::new(index, NodeId::metadata())
     }
 
+    pub const fn pending_build(index: u16) -> Self {
+        Self::new(index, NodeId::pending_build())
+    }
+
+    pub const fn build_config(index: u16) -> Self {
+        Self::new(index, NodeId::build_config())
+    }
+
+    pub const fn projection(index: u16) -> Self {
+        Self::new(index, NodeId::projection())
+    }
+
+    pub const fn version(index: u16) -> Self {
+        Self::new(index, NodeId::version())
+    }
+
+    pub const fn search_tuning(index: u16) -> Self {
+        Self::new(index, NodeId::search_tuning())
+    }
+
+    pub const fn legacy_updated_items(index: u16) -> Self {
+        Self::new(index, NodeId::legacy_updated_items())
+    }
+
     pub const fn updated(index: u16, item: u32) -> Self {
         Self::new(index, NodeId::updated(item))
     }
@@ -67,14 +98,20 @@ impl heed::BytesDecode<'_> for KeyCodec {
     type DItem = Key;
 
     fn bytes_decode(bytes: &[u8]) -> Result<Self::DItem, BoxedError> {
+        if bytes.len() != size_of::<u64>() {
+            return Err(DecodeError::WrongLength {
+                what: "key",
+                expected: size_of::<u64>(),
+                len: bytes.len(),
+            }
+            .into());
+        }
         let prefix = BigEndian::read_u16(bytes);
         let bytes = &bytes[size_of::<u16>()..];
-        let mode = bytes[0].try_into()?;
-        let bytes = &bytes[size_of::<u8>()..];
-        let item = BigEndian::read_u32(bytes);
+        let (node, _padding) = NodeId::from_bytes(bytes)?;
         // We don't need to deserialize the unused space
 
-        Ok(Key { index: prefix, node: NodeId { mode, item }, _padding: 0 })
+        Ok(Key { index: prefix, node, _padding: 0 })
     }
 }
 
@@ -125,7 +162,7 @@ impl<'a> heed::BytesEncode<'a> for PrefixCodec {
 
 #[cfg(test)]
 mod test {
-    use heed::BytesEncode;
+    use heed::{BytesDecode, BytesEncode};
 
     use super::*;
 
@@ -135,4 +172,12 @@ mod test {
         let encoded = KeyCodec::bytes_encode(&key).unwrap();
         assert_eq!(encoded.len(), size_of::<u64>());
     }
+
+    #[test]
+    fn invalid_keys() {
+        let error = KeyCodec::bytes_decode(&[0, 1, 2]).unwrap_err();
+        insta::assert_snapshot!(error, @"key: expected 8 bytes but got 3");
+        let error = KeyCodec::bytes_decode(&[0, 1, 8, 0, 0, 0, 0, 0]).unwrap_err();
+        insta::assert_snapshot!(error, @"unknown node mode 8");
+    }
 }
@@ -72,14 +72,22 @@
     html_logo_url = "https://raw.githubusercontent.com/meilisearch/arroy/main/assets/arroy-electric-clusters-logo.png?raw=true"
 )]
 
+mod annoy;
+mod benchmark;
+mod dataset;
 mod distance;
+mod dump;
 mod error;
+mod indexes;
 mod item_iter;
 mod key;
 mod metadata;
 mod node;
 mod node_id;
 mod parallel;
+#[cfg(feature = "plot")]
+mod plot;
+mod projection;
 mod reader;
 mod roaring;
 mod spaces;
@@ -89,16 +97,30 @@ mod writer;
 #[cfg(test)]
 mod tests;
 mod unaligned_vector;
+mod verify;
 
+pub use annoy::{AnnoyDistance, AnnoyMetric};
+pub use benchmark::{Benchmark, BenchmarkResult, GroundTruth};
+pub use dataset::{read_ivecs, VectorFormat, VectorReader};
 pub use distance::Distance;
 pub use error::Error;
+pub use indexes::{list_indexes, IndexInfo};
 use key::{Key, Prefix, PrefixCodec};
-use metadata::{Metadata, MetadataCodec};
+pub use metadata::{BuildConfig, SearchTuning, FORMAT_VERSION};
+use metadata::{
+    BuildConfigCodec, Metadata, MetadataCodec, PendingBuild, PendingBuildCodec, SearchTuningCodec,
+    VersionCodec,
+};
+pub use node::NormalPrecision;
 use node::{Node, NodeCodec};
 use node_id::{NodeId, NodeMode};
+#[cfg(feature = "plot")]
+pub use plot::TreePlotOptions;
+use projection::{Projection, ProjectionCodec};
 pub use reader::{QueryBuilder, Reader};
-pub use stats::{Stats, TreeStats};
-pub use writer::{ArroyBuilder, Writer};
+pub use stats::{IndexBytes, SplitLevelStats, Stats, TreeStats};
+pub use verify::{GarbageReport, VerifyReport};
+pub use writer::{ArroyBuilder, DimensionChange, PreparedBuild, Writer};
 
 /// The set of types used by the [`Distance`] trait.
 pub mod internals {
@@ -1,12 +1,16 @@
 use std::borrow::Cow;
 use std::ffi::CStr;
 use std::mem::size_of;
+use std::num::NonZeroUsize;
 
 use ::roaring::RoaringBitmap;
 use byteorder::{BigEndian, ByteOrder};
 use heed::BoxedError;
 
-use crate::node::ItemIds;
+use crate::error::DecodeError;
+use crate::node::{ItemIds, NormalPrecision};
+use crate::projection::{Projection, ProjectionCodec};
+use crate::ItemId;
 
 #[derive(Debug)]
 pub struct Metadata<'a> {
@@ -23,7 +27,7 @@ impl<'a> heed::BytesEncode<'a> for MetadataCodec {
 
     fn bytes_encode(item: &'a Self::EItem) -> Result<Cow<'a, [u8]>, BoxedError> {
         let Metadata { dimensions, items, roots, distance } = item;
-        debug_assert!(!distance.as_bytes().iter().any(|&b| b == 0));
+        debug_assert!(!distance.as_bytes().contains(&0));
 
         let mut output = Vec::with_capacity(
             size_of::<u32>()
@@ -48,21 +52,330 @@ impl<'a> heed::BytesDecode<'a> for MetadataCodec {
 
     fn bytes_decode(bytes: &'a [u8]) -> Result<Self::DItem, BoxedError> {
         let distance = CStr::from_bytes_until_nul(bytes)?.to_str()?;
-        let bytes = &bytes[distance.len() + 1..];
-        let dimensions = BigEndian::read_u32(bytes);
-        let bytes = &bytes[size_of::<u32>()..];
-        let items_size = BigEndian::read_u32(bytes) as usize;
-        let bytes = &bytes[size_of::<u32>()..];
-        let items = RoaringBitmap::deserialize_from(&bytes[..items_size])?;
-        let bytes = &bytes[items_size..];
+        let mut bytes = &bytes[distance.len() + 1..];
+        let dimensions = DecodeError::take(&mut bytes, size_of::<u32>(), "dimensions")?;
+        let dimensions = BigEndian::read_u32(dimensions);
+        let items_size = DecodeError::take(&mut bytes, size_of::<u32>(), "items size")?;
+        let items_size = BigEndian::read_u32(items_size) as usize;
+        let items = DecodeError::take(&mut bytes, items_size, "items")?;
+        let items = RoaringBitmap::deserialize_from(items)?;
+        DecodeError::check_aligned(bytes, size_of::<ItemId>(), "roots")?;
 
         Ok(Metadata { dimensions, items, roots: ItemIds::from_bytes(bytes), distance })
     }
 }
 
+/// The version of the format arroy writes its indexes in.
+///
+/// It is increased every time the layout of the database changes in a way the previous
+/// versions of arroy cannot read. The indexes written before the format was versioned
+/// are in the version `0` and can be migrated with [`Writer::upgrade`](crate::Writer::upgrade).
+pub const FORMAT_VERSION: u32 = 1;
+
+pub enum VersionCodec {}
+
+impl<'a> heed::BytesEncode<'a> for VersionCodec {
+    type EItem = u32;
+
+    fn bytes_encode(item: &'a Self::EItem) -> Result<Cow<'a, [u8]>, BoxedError> {
+        Ok(Cow::Owned(item.to_be_bytes().to_vec()))
+    }
+}
+
+impl heed::BytesDecode<'_> for VersionCodec {
+    type DItem = u32;
+
+    fn bytes_decode(bytes: &[u8]) -> Result<Self::DItem, BoxedError> {
+        if bytes.len() != size_of::<u32>() {
+            let (expected, len) = (size_of::<u32>(), bytes.len());
+            return Err(DecodeError::WrongLength { what: "version", expected, len }.into());
+        }
+        Ok(BigEndian::read_u32(bytes))
+    }
+}
+
+/// The trees that were generated by a build that is not finished yet.
+#[derive(Debug)]
+pub struct PendingBuild<'a> {
+    /// A hash of the state of the index when the trees were generated.
+    pub fingerprint: u64,
+    /// The projection the trees are built on when it isn't the one of the index,
+    /// `Some(None)` removes it. These trees then replace all the existing ones.
+    pub new_projection: Option<Option<Projection<'a>>>,
+    pub roots: ItemIds<'a>,
+}
+
+pub enum PendingBuildCodec {}
+
+impl PendingBuildCodec {
+    const SAME_PROJECTION: u8 = 0;
+    const NO_PROJECTION: u8 = 1;
+    const NEW_PROJECTION: u8 = 2;
+}
+
+impl<'a> heed::BytesEncode<'a> for PendingBuildCodec {
+    type EItem = PendingBuild<'a>;
+
+    fn bytes_encode(item: &'a Self::EItem) -> Result<Cow<'a, [u8]>, BoxedError> {
+        let PendingBuild { fingerprint, new_projection, roots } = item;
+
+        let mut output = Vec::with_capacity(size_of::<u64>() + 1 + roots.len() * size_of::<u32>());
+        output.extend_from_slice(&fingerprint.to_be_bytes());
+        match new_projection {
+            None => output.push(Self::SAME_PROJECTION),
+            Some(None) => output.push(Self::NO_PROJECTION),
+            Some(Some(projection)) => {
+                let projection = ProjectionCodec::bytes_encode(projection)?;
+                output.push(Self::NEW_PROJECTION);
+                output.extend_from_slice(&u32::try_from(projection.len())?.to_be_bytes());
+                output.extend_from_slice(&projection);
+            }
+        }
+        output.extend_from_slice(roots.raw_bytes());
+
+        Ok(Cow::Owned(output))
+    }
+}
+
+impl<'a> heed::BytesDecode<'a> for PendingBuildCodec {
+    type DItem = PendingBuild<'a>;
+
+    fn bytes_decode(bytes: &'a [u8]) -> Result<Self::DItem, BoxedError> {
+        let mut bytes = bytes;
+        let fingerprint = DecodeError::take(&mut bytes, size_of::<u64>(), "fingerprint")?;
+        let fingerprint = BigEndian::read_u64(fingerprint);
+        let new_projection = match DecodeError::take(&mut bytes, 1, "projection tag")?[0] {
+            Self::SAME_PROJECTION => None,
+            Self::NO_PROJECTION => Some(None),
+            Self::NEW_PROJECTION => {
+                let size = DecodeError::take(&mut bytes, size_of::<u32>(), "projection size")?;
+                let size = BigEndian::read_u32(size) as usize;
+                let projection = DecodeError::take(&mut bytes, size, "projection")?;
+                Some(Some(ProjectionCodec::bytes_decode(projection)?))
+            }
+            _ => return Err(DecodeError::Invalid("unknown projection in the pending build").into()),
+        };
+        DecodeError::check_aligned(bytes, size_of::<ItemId>(), "roots")?;
+
+        Ok(PendingBuild { fingerprint, new_projection, roots: ItemIds::from_bytes(bytes) })
+    }
+}
+
+/// The options used to build the trees of an index.
+///
+/// They are stored in the database after every build and used as the defaults of the
+/// next builds, so that updating an index doesn't silently change its shape.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BuildConfig {
+    /// The number of trees requested with [`ArroyBuilder::n_trees`](crate::ArroyBuilder::n_trees).
+    /// `None` means arroy determines the number of trees by itself.
+    pub n_trees: Option<usize>,
+    /// The maximum number of items in a descendants node set with
+    /// [`ArroyBuilder::split_after`](crate::ArroyBuilder::split_after).
+    /// `None` means it defaults to the number of dimensions.
+    pub split_after: Option<usize>,
+    /// The number of dimensions the split planes are computed on, set with
+    /// [`ArroyBuilder::split_dimensions`](crate::ArroyBuilder::split_dimensions).
+    /// `None` means all the dimensions are used.
+    pub split_dimensions: Option<usize>,
+    /// The number of dimensions the vectors are projected to before computing the split
+    /// planes, set with [`ArroyBuilder::reduce_dimensions`](crate::ArroyBuilder::reduce_dimensions).
+    /// `None` means the vectors are not projected.
+    pub reduced_dimensions: Option<usize>,
+    /// The precision of the normals of the split planes, set with
+    /// [`ArroyBuilder::normal_precision`](crate::ArroyBuilder::normal_precision).
+    pub normal_precision: NormalPrecision,
+    /// The seed set with [`ArroyBuilder::seed`](crate::ArroyBuilder::seed).
+    pub seed: Option<u64>,
+    /// Whether the trees are built with
+    /// [`ArroyBuilder::deterministic`](crate::ArroyBuilder::deterministic).
+    pub deterministic: bool,
+}
+
+impl BuildConfig {
+    /// Returns `true` if none of the options were set.
+    pub(crate) fn is_empty(&self) -> bool {
+        *self == BuildConfig::default()
+    }
+}
+
+pub enum BuildConfigCodec {}
+
+impl BuildConfigCodec {
+    const N_TREES: u8 = 0b001;
+    const SPLIT_AFTER: u8 = 0b010;
+    const SEED: u8 = 0b100;
+    const DETERMINISTIC: u8 = 0b1000;
+    const SPLIT_DIMENSIONS: u8 = 0b10000;
+    const REDUCED_DIMENSIONS: u8 = 0b100000;
+    /// The two last bits store the [`NormalPrecision`].
+    const NORMAL_PRECISION_SHIFT: u8 = 6;
+}
+
+impl<'a> heed::BytesEncode<'a> for BuildConfigCodec {
+    type EItem = BuildConfig;
+
+    fn bytes_encode(item: &'a Self::EItem) -> Result<Cow<'a, [u8]>, BoxedError> {
+        let BuildConfig {
+            n_trees,
+            split_after,
+            split_dimensions,
+            reduced_dimensions,
+            normal_precision,
+            seed,
+            deterministic,
+        } = item;
+
+        let flags = n_trees.map_or(0, |_| Self::N_TREES)
+            | split_after.map_or(0, |_| Self::SPLIT_AFTER)
+            | seed.map_or(0, |_| Self::SEED)
+            | if *deterministic { Self::DETERMINISTIC } else { 0 }
+            | split_dimensions.map_or(0, |_| Self::SPLIT_DIMENSIONS)
+            | reduced_dimensions.map_or(0, |_| Self::REDUCED_DIMENSIONS)
+            | match normal_precision {
+                NormalPrecision::Full => 0,
+                NormalPrecision::F16 => 1,
+                NormalPrecision::Int8 => 2,
+            } << Self::NORMAL_PRECISION_SHIFT;
+        let values = n_trees
+            .map(|n| n as u64)
+            .into_iter()
+            .chain(split_after.map(|n| n as u64))
+            .chain(*seed)
+            .chain(split_dimensions.map(|n| n as u64))
+            .chain(reduced_dimensions.map(|n| n as u64));
+
+        let mut output = Vec::with_capacity(1 + 5 * size_of::<u64>());
+        output.push(flags);
+        output.extend(values.flat_map(u64::to_be_bytes));
+
+        Ok(Cow::Owned(output))
+    }
+}
+
+impl heed::BytesDecode<'_> for BuildConfigCodec {
+    type DItem = BuildConfig;
+
+    fn bytes_decode(bytes: &[u8]) -> Result<Self::DItem, BoxedError> {
+        let [flags, bytes @ ..] = bytes else {
+            return Err(DecodeError::Invalid("the build config cannot be empty").into());
+        };
+        let flags = *flags;
+        DecodeError::check_aligned(bytes, size_of::<u64>(), "build config values")?;
+        let mut values = bytes.chunks_exact(size_of::<u64>()).map(BigEndian::read_u64);
+        let mut next_if = |flag: u8| match flags & flag {
+            0 => Ok(None),
+            _ => values
+                .next()
+                .map(Some)
+                .ok_or(DecodeError::Invalid("missing value in the build config")),
+        };
+
+        let n_trees = next_if(Self::N_TREES)?.map(|n| n as usize);
+        let split_after = next_if(Self::SPLIT_AFTER)?.map(|n| n as usize);
+        let seed = next_if(Self::SEED)?;
+        let split_dimensions = next_if(Self::SPLIT_DIMENSIONS)?.map(|n| n as usize);
+        let reduced_dimensions = next_if(Self::REDUCED_DIMENSIONS)?.map(|n| n as usize);
+        let deterministic = flags & Self::DETERMINISTIC != 0;
+        let normal_precision = match flags >> Self::NORMAL_PRECISION_SHIFT {
+            0 => NormalPrecision::Full,
+            1 => NormalPrecision::F16,
+            2 => NormalPrecision::Int8,
+            _ => {
+                return Err(
+                    DecodeError::Invalid("unknown normal precision in the build config").into()
+                )
+            }
+        };
+        if values.next().is_some() {
+            return Err(DecodeError::Invalid("too many values in the build config").into());
+        }
+
+        Ok(BuildConfig {
+            n_trees,
+            split_after,
+            split_dimensions,
+            reduced_dimensions,
+            normal_precision,
+            seed,
+            deterministic,
+        })
+    }
+}
+
+/// The smallest `search_k` that reaches a target recall, measured with
+/// [`Reader::tune_search_k`](crate::Reader::tune_search_k).
+///
+/// Once stored with [`Writer::set_search_tuning`](crate::Writer::set_search_tuning),
+/// it is used by the queries that don't set a `search_k`, scaled to their `count`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SearchTuning {
+    /// The number of neighbors the queries searched for.
+    pub count: usize,
+    /// The number of trees of the index when it was measured, the tuning
+    /// is ignored once the index is built with another number of trees.
+    pub n_trees: usize,
+    /// The number of nodes to explore to reach the recall, without oversampling.
+    pub search_k: NonZeroUsize,
+    /// The recall that was requested.
+    pub target_recall: f32,
+    /// The recall reached with this `search_k`, lower than the target if it can't be reached.
+    pub recall: f32,
+}
+
+impl SearchTuning {
+    /// Returns the `search_k` to use for a query of `count` neighbors.
+    pub(crate) fn search_k(&self, count: usize) -> usize {
+        (self.search_k.get().saturating_mul(count)).div_ceil(self.count.max(1)).max(1)
+    }
+}
+
+pub enum SearchTuningCodec {}
+
+impl<'a> heed::BytesEncode<'a> for SearchTuningCodec {
+    type EItem = SearchTuning;
+
+    fn bytes_encode(item: &'a Self::EItem) -> Result<Cow<'a, [u8]>, BoxedError> {
+        let SearchTuning { count, n_trees, search_k, target_recall, recall } = item;
+
+        let mut output = Vec::with_capacity(3 * size_of::<u64>() + 2 * size_of::<f32>());
+        output.extend_from_slice(&(*count as u64).to_be_bytes());
+        output.extend_from_slice(&(*n_trees as u64).to_be_bytes());
+        output.extend_from_slice(&(search_k.get() as u64).to_be_bytes());
+        output.extend_from_slice(&target_recall.to_be_bytes());
+        output.extend_from_slice(&recall.to_be_bytes());
+
+        Ok(Cow::Owned(output))
+    }
+}
+
+impl heed::BytesDecode<'_> for SearchTuningCodec {
+    type DItem = SearchTuning;
+
+    fn bytes_decode(bytes: &[u8]) -> Result<Self::DItem, BoxedError> {
+        let expected = 3 * size_of::<u64>() + 2 * size_of::<f32>();
+        if bytes.len() != expected {
+            let len = bytes.len();
+            return Err(DecodeError::WrongLength { what: "search tuning", expected, len }.into());
+        }
+        let (integers, recalls) = bytes.split_at(3 * size_of::<u64>());
+        let mut integers = integers.chunks_exact(size_of::<u64>()).map(BigEndian::read_u64);
+        let count = integers.next().unwrap() as usize;
+        let n_trees = integers.next().unwrap() as usize;
+        let search_k = NonZeroUsize::new(integers.next().unwrap() as usize)
+            .ok_or(DecodeError::Invalid("the tuned search_k cannot be zero"))?;
+        let target_recall = BigEndian::read_f32(recalls);
+        let recall = BigEndian::read_f32(&recalls[size_of::<f32>()..]);
+
+        Ok(SearchTuning { count, n_trees, search_k, target_recall, recall })
+    }
+}
+
 #[cfg(test)]
 mod test {
     use heed::{BytesDecode, BytesEncode};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
 
     use super::*;
 
@@ -83,4 +396,138 @@ mod test {
         assert_eq!(metadata.roots.raw_bytes(), decoded.roots.raw_bytes());
         assert_eq!(metadata.distance, decoded.distance);
     }
+
+    #[test]
+    fn invalid_metadata() {
+        let metadata = Metadata {
+            dimensions: 12,
+            items: RoaringBitmap::from_sorted_iter(0..100).unwrap(),
+            roots: ItemIds::from_slice(&[1, 2, 3, 4]),
+            distance: "tamo",
+        };
+        let encoded = MetadataCodec::bytes_encode(&metadata).unwrap();
+
+        let error = MetadataCodec::bytes_decode(&encoded[..7]).unwrap_err();
+        insta::assert_snapshot!(error, @"dimensions: expected 4 bytes but only 2 remain");
+        let error = MetadataCodec::bytes_decode(&encoded[..20]).unwrap_err();
+        insta::assert_snapshot!(error, @"items: expected 216 bytes but only 7 remain");
+        let error = MetadataCodec::bytes_decode(&encoded[..encoded.len() - 1]).unwrap_err();
+        insta::assert_snapshot!(error, @"roots: 15 bytes cannot be split in values of 4 bytes");
+    }
+
+    #[test]
+    fn version_codec() {
+        let encoded = VersionCodec::bytes_encode(&FORMAT_VERSION).unwrap();
+        assert_eq!(VersionCodec::bytes_decode(&encoded).unwrap(), FORMAT_VERSION);
+
+        let error = VersionCodec::bytes_decode(&[0, 1]).unwrap_err();
+        insta::assert_snapshot!(error, @"version: expected 4 bytes but got 2");
+    }
+
+    #[test]
+    fn pending_build_codec() {
+        let projection = Projection::random(&mut StdRng::seed_from_u64(42), 6, 3);
+        for new_projection in [None, Some(None), Some(Some(projection))] {
+            let pending = PendingBuild {
+                fingerprint: 42,
+                new_projection,
+                roots: ItemIds::from_slice(&[1, 2, 3, 4]),
+            };
+
+            let encoded = PendingBuildCodec::bytes_encode(&pending).unwrap();
+            let decoded = PendingBuildCodec::bytes_decode(&encoded).unwrap();
+
+            assert_eq!(pending.fingerprint, decoded.fingerprint);
+            assert_eq!(pending.new_projection.is_some(), decoded.new_projection.is_some());
+            assert_eq!(PendingBuildCodec::bytes_encode(&decoded).unwrap(), encoded);
+            assert_eq!(pending.roots.raw_bytes(), decoded.roots.raw_bytes());
+        }
+    }
+
+    #[test]
+    fn invalid_pending_build() {
+        let error = PendingBuildCodec::bytes_decode(&[0, 0, 1]).unwrap_err();
+        insta::assert_snapshot!(error, @"fingerprint: expected 8 bytes but only 3 remain");
+        let error = PendingBuildCodec::bytes_decode(&[0, 0, 0, 0, 0, 0, 0, 1]).unwrap_err();
+        insta::assert_snapshot!(error, @"projection tag: expected 1 bytes but only 0 remain");
+        let error = PendingBuildCodec::bytes_decode(&[0, 0, 0, 0, 0, 0, 0, 1, 3]).unwrap_err();
+        insta::assert_snapshot!(error, @"unknown projection in the pending build");
+        let error =
+            PendingBuildCodec::bytes_decode(&[0, 0, 0, 0, 0, 0, 0, 1, 2, 0, 0, 0, 8]).unwrap_err();
+        insta::assert_snapshot!(error, @"projection: expected 8 bytes but only 0 remain");
+        let error =
+            PendingBuildCodec::bytes_decode(&[0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0]).unwrap_err();
+        insta::assert_snapshot!(error, @"roots: 2 bytes cannot be split in values of 4 bytes");
+    }
+
+    #[test]
+    fn build_config_codec() {
+        for config in [
+            BuildConfig::default(),
+            BuildConfig {
+                n_trees: Some(10),
+                split_after: None,
+                split_dimensions: None,
+                reduced_dimensions: Some(64),
+                normal_precision: NormalPrecision::F16,
+                seed: Some(42),
+                deterministic: true,
+            },
+            BuildConfig {
+                n_trees: None,
+                split_after: Some(500),
+                split_dimensions: Some(128),
+                reduced_dimensions: None,
+                normal_precision: NormalPrecision::Int8,
+                seed: None,
+                deterministic: false,
+            },
+            BuildConfig {
+                n_trees: Some(1),
+                split_after: Some(2),
+                split_dimensions: Some(1),
+                reduced_dimensions: Some(2),
+                normal_precision: NormalPrecision::Full,
+                seed: Some(u64::MAX),
+                deterministic: false,
+            },
+        ] {
+            let encoded = BuildConfigCodec::bytes_encode(&config).unwrap();
+            let decoded = BuildConfigCodec::bytes_decode(&encoded).unwrap();
+            assert_eq!(config, decoded);
+        }
+    }
+
+    #[test]
+    fn invalid_build_config() {
+        let error = BuildConfigCodec::bytes_decode(&[]).unwrap_err();
+        insta::assert_snapshot!(error, @"the build config cannot be empty");
+        let error = BuildConfigCodec::bytes_decode(&[BuildConfigCodec::N_TREES]).unwrap_err();
+        insta::assert_snapshot!(error, @"missing value in the build config");
+        let error = BuildConfigCodec::bytes_decode(&[0, 0, 0, 0, 0, 0, 0, 0, 1]).unwrap_err();
+        insta::assert_snapshot!(error, @"too many values in the build config");
+        let error = BuildConfigCodec::bytes_decode(&[0, 0]).unwrap_err();
+        insta::assert_snapshot!(error, @"build config values: 1 bytes cannot be split in values of 8 bytes");
+    }
+
+    #[test]
+    fn search_tuning_codec() {
+        let tuning = SearchTuning {
+            count: 10,
+            n_trees: 50,
+            search_k: NonZeroUsize::new(1500).unwrap(),
+            target_recall: 0.95,
+            recall: 0.9612,
+        };
+        let encoded = SearchTuningCodec::bytes_encode(&tuning).unwrap();
+        assert_eq!(SearchTuningCodec::bytes_decode(&encoded).unwrap(), tuning);
+        assert_eq!(tuning.search_k(10), 1500);
+        assert_eq!(tuning.search_k(3), 450);
+        assert_eq!(tuning.search_k(1), 150);
+
+        let error = SearchTuningCodec::bytes_decode(&encoded[1..]).unwrap_err();
+        insta::assert_snapshot!(error, @"search tuning: expected 32 bytes but got 31");
+        let error = SearchTuningCodec::bytes_decode(&[0; 32]).unwrap_err();
+        insta::assert_snapshot!(error, @"the tuned search_k cannot be zero");
+    }
 }
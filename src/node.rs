@@ -4,10 +4,12 @@ use std::mem::size_of;
 
 use bytemuck::{bytes_of, cast_slice, pod_read_unaligned};
 use byteorder::{ByteOrder, NativeEndian};
+use half::f16;
 use heed::{BoxedError, BytesDecode, BytesEncode};
 use roaring::RoaringBitmap;
 
 use crate::distance::Distance;
+use crate::error::DecodeError;
 use crate::unaligned_vector::UnalignedVector;
 use crate::{ItemId, NodeId};
 
@@ -18,9 +20,12 @@ pub enum Node<'a, D: Distance> {
     SplitPlaneNormal(SplitPlaneNormal<'a, D>),
 }
 
-const LEAF_TAG: u8 = 0;
+pub(crate) const LEAF_TAG: u8 = 0;
 const DESCENDANTS_TAG: u8 = 1;
-const SPLIT_PLANE_NORMAL_TAG: u8 = 2;
+pub(crate) const SPLIT_PLANE_NORMAL_TAG: u8 = 2;
+pub(crate) const SPLIT_PLANE_NORMAL_F16_TAG: u8 = 3;
+pub(crate) const SPLIT_PLANE_NORMAL_INT8_TAG: u8 = 4;
+const DESCENDANTS_VARINT_TAG: u8 = 5;
 
 impl<'a, D: Distance> Node<'a, D> {
     pub fn leaf(self) -> Option<Leaf<'a, D>> {
@@ -75,6 +80,63 @@ impl fmt::Debug for Descendants<'_> {
     }
 }
 
+impl Descendants<'_> {
+    /// Returns the number of bytes the ids take once serialized as a `RoaringBitmap`.
+    pub fn roaring_len(&self) -> usize {
+        self.descendants.serialized_size()
+    }
+
+    /// Returns the number of bytes the ids take once encoded as the
+    /// varints of the differences between consecutive ids.
+    pub fn varint_len(&self) -> usize {
+        let mut previous = 0;
+        self.descendants
+            .iter()
+            .map(|id| {
+                let delta = id - previous;
+                previous = id;
+                varint_len(delta)
+            })
+            .sum()
+    }
+
+    /// Returns the number of bytes the ids take in the database,
+    /// the smallest of the two encodings is always picked.
+    pub fn encoded_len(&self) -> usize {
+        self.roaring_len().min(self.varint_len())
+    }
+}
+
+/// Returns the number of bytes of the LEB128 encoding of `n`.
+fn varint_len(n: u32) -> usize {
+    (u32::BITS - n.leading_zeros()).max(1).div_ceil(7) as usize
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut n: u32) {
+    while n >= 0x80 {
+        bytes.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    bytes.push(n as u8);
+}
+
+fn read_varint(bytes: &mut &[u8]) -> Result<u32, DecodeError> {
+    let mut n = 0u32;
+    for (i, &byte) in bytes.iter().enumerate() {
+        let shift = 7 * i as u32;
+        let value = (byte & 0x7f) as u32;
+        if shift >= u32::BITS || (value << shift) >> shift != value {
+            return Err(DecodeError::Invalid("a descendant id overflows"));
+        }
+        n |= value << shift;
+        if byte & 0x80 == 0 {
+            *bytes = &bytes[i + 1..];
+            return Ok(n);
+        }
+    }
+    Err(DecodeError::Invalid("the last descendant id is truncated"))
+}
+
 #[derive(Clone)]
 pub struct ItemIds<'a> {
     bytes: &'a [u8],
@@ -116,22 +178,57 @@ pub struct SplitPlaneNormal<'a, D: Distance> {
     pub left: NodeId,
     pub right: NodeId,
     pub normal: Cow<'a, UnalignedVector<D::VectorCodec>>,
+    /// The precision the normal is stored with.
+    pub precision: NormalPrecision,
 }
 
 impl<D: Distance> fmt::Debug for SplitPlaneNormal<'_, D> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let name = format!("SplitPlaneNormal<{}>", D::name());
-        f.debug_struct(&name)
-            .field("left", &self.left)
-            .field("right", &self.right)
-            .field("normal", &self.normal)
-            .finish()
+        let mut debug = f.debug_struct(&name);
+        debug.field("left", &self.left).field("right", &self.right).field("normal", &&*self.normal);
+        if self.precision != NormalPrecision::Full {
+            debug.field("precision", &self.precision);
+        }
+        debug.finish()
     }
 }
 
 impl<D: Distance> Clone for SplitPlaneNormal<'_, D> {
     fn clone(&self) -> Self {
-        Self { left: self.left, right: self.right, normal: self.normal.clone() }
+        Self {
+            left: self.left,
+            right: self.right,
+            normal: self.normal.clone(),
+            precision: self.precision,
+        }
+    }
+}
+
+/// The precision the normals of the split planes are stored with.
+///
+/// The normals make up most of the size of the tree nodes. Searching only needs
+/// the side of the split planes the query is on and roughly how far it is from them,
+/// so the normals can be stored with fewer bits than the items.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum NormalPrecision {
+    /// The normals are stored like the items, with the codec of the distance.
+    #[default]
+    Full,
+    /// Every value is stored as a half-precision float, on 16 bits.
+    F16,
+    /// Every value is stored on 8 bits, scaled by the largest absolute value of the normal.
+    Int8,
+}
+
+impl NormalPrecision {
+    /// Returns the number of bytes a normal of `len` values is encoded in.
+    fn encoded_len(&self, len: usize) -> usize {
+        match self {
+            NormalPrecision::Full => usize::MAX,
+            NormalPrecision::F16 => len * size_of::<f16>(),
+            NormalPrecision::Int8 => size_of::<f32>() + len,
+        }
     }
 }
 
@@ -149,15 +246,53 @@ impl<'a, D: Distance> BytesEncode<'a> for NodeCodec<D> {
                 bytes.extend_from_slice(bytes_of(header));
                 bytes.extend_from_slice(vector.as_bytes());
             }
-            Node::SplitPlaneNormal(SplitPlaneNormal { normal, left, right }) => {
-                bytes.push(SPLIT_PLANE_NORMAL_TAG);
+            Node::SplitPlaneNormal(SplitPlaneNormal { normal, left, right, precision }) => {
+                // The binary quantized normals are already smaller than the compact ones
+                let precision = if precision.encoded_len(normal.len()) < normal.as_bytes().len() {
+                    *precision
+                } else {
+                    NormalPrecision::Full
+                };
+
+                bytes.push(match precision {
+                    NormalPrecision::Full => SPLIT_PLANE_NORMAL_TAG,
+                    NormalPrecision::F16 => SPLIT_PLANE_NORMAL_F16_TAG,
+                    NormalPrecision::Int8 => SPLIT_PLANE_NORMAL_INT8_TAG,
+                });
                 bytes.extend_from_slice(&left.to_bytes());
                 bytes.extend_from_slice(&right.to_bytes());
-                bytes.extend_from_slice(normal.as_bytes());
+                match precision {
+                    NormalPrecision::Full => bytes.extend_from_slice(normal.as_bytes()),
+                    NormalPrecision::F16 => {
+                        bytes.extend(normal.iter().flat_map(|x| f16::from_f32(x).to_ne_bytes()))
+                    }
+                    NormalPrecision::Int8 => {
+                        let max = normal.iter().fold(0.0, |max: f32, x| max.max(x.abs()));
+                        let scale = max / i8::MAX as f32;
+                        bytes.extend_from_slice(&scale.to_ne_bytes());
+                        bytes.extend(normal.iter().map(|x| {
+                            if scale == 0.0 {
+                                0
+                            } else {
+                                (x / scale).round() as i8 as u8
+                            }
+                        }));
+                    }
+                }
             }
-            Node::Descendants(Descendants { descendants }) => {
-                bytes.push(DESCENDANTS_TAG);
-                descendants.serialize_into(&mut bytes)?;
+            Node::Descendants(node) => {
+                // The roaring containers take more space than the ids of the small buckets
+                if node.varint_len() < node.roaring_len() {
+                    bytes.push(DESCENDANTS_VARINT_TAG);
+                    let mut previous = 0;
+                    for id in node.descendants.iter() {
+                        write_varint(&mut bytes, id - previous);
+                        previous = id;
+                    }
+                } else {
+                    bytes.push(DESCENDANTS_TAG);
+                    node.descendants.serialize_into(&mut bytes)?;
+                }
             }
         }
         Ok(Cow::Owned(bytes))
@@ -170,25 +305,193 @@ impl<'a, D: Distance> BytesDecode<'a> for NodeCodec<D> {
     fn bytes_decode(bytes: &'a [u8]) -> Result<Self::DItem, BoxedError> {
         match bytes {
             [LEAF_TAG, bytes @ ..] => {
-                let (header_bytes, remaining) = bytes.split_at(size_of::<D::Header>());
+                let mut remaining = bytes;
+                let header_bytes =
+                    DecodeError::take(&mut remaining, size_of::<D::Header>(), "leaf header")?;
                 let header = pod_read_unaligned(header_bytes);
                 let vector = UnalignedVector::<D::VectorCodec>::from_bytes(remaining)?;
 
                 Ok(Node::Leaf(Leaf { header, vector }))
             }
             [SPLIT_PLANE_NORMAL_TAG, bytes @ ..] => {
-                let (left, bytes) = NodeId::from_bytes(bytes);
-                let (right, bytes) = NodeId::from_bytes(bytes);
+                let (left, bytes) = NodeId::from_bytes(bytes)?;
+                let (right, bytes) = NodeId::from_bytes(bytes)?;
                 Ok(Node::SplitPlaneNormal(SplitPlaneNormal {
                     normal: UnalignedVector::<D::VectorCodec>::from_bytes(bytes)?,
                     left,
                     right,
+                    precision: NormalPrecision::Full,
+                }))
+            }
+            [SPLIT_PLANE_NORMAL_F16_TAG, bytes @ ..] => {
+                let (left, bytes) = NodeId::from_bytes(bytes)?;
+                let (right, bytes) = NodeId::from_bytes(bytes)?;
+                DecodeError::check_aligned(bytes, size_of::<f16>(), "f16 normal")?;
+                let normal = bytes
+                    .chunks_exact(size_of::<f16>())
+                    .map(|bytes| f16::from_ne_bytes([bytes[0], bytes[1]]).to_f32())
+                    .collect();
+                Ok(Node::SplitPlaneNormal(SplitPlaneNormal {
+                    normal: UnalignedVector::from_vec(normal),
+                    left,
+                    right,
+                    precision: NormalPrecision::F16,
+                }))
+            }
+            [SPLIT_PLANE_NORMAL_INT8_TAG, bytes @ ..] => {
+                let (left, bytes) = NodeId::from_bytes(bytes)?;
+                let (right, bytes) = NodeId::from_bytes(bytes)?;
+                let mut bytes = bytes;
+                let scale = DecodeError::take(&mut bytes, size_of::<f32>(), "int8 normal scale")?;
+                let scale = NativeEndian::read_f32(scale);
+                let normal = bytes.iter().map(|&x| x as i8 as f32 * scale).collect();
+                Ok(Node::SplitPlaneNormal(SplitPlaneNormal {
+                    normal: UnalignedVector::from_vec(normal),
+                    left,
+                    right,
+                    precision: NormalPrecision::Int8,
                 }))
             }
             [DESCENDANTS_TAG, bytes @ ..] => Ok(Node::Descendants(Descendants {
                 descendants: Cow::Owned(RoaringBitmap::deserialize_from(bytes)?),
             })),
-            unknown => panic!("What the fuck is an {unknown:?}"),
+            [DESCENDANTS_VARINT_TAG, bytes @ ..] => {
+                let mut bytes = bytes;
+                let mut descendants = RoaringBitmap::new();
+                let mut previous = None;
+                while !bytes.is_empty() {
+                    let delta = read_varint(&mut bytes)?;
+                    let id = match previous {
+                        Some(previous) => u32::checked_add(previous, delta),
+                        None => Some(delta),
+                    };
+                    // pushing only succeeds if the id is greater than the previous one
+                    match id {
+                        Some(id) if descendants.push(id) => previous = Some(id),
+                        _ => {
+                            return Err(DecodeError::Invalid(
+                                "the descendant ids are not strictly increasing",
+                            )
+                            .into())
+                        }
+                    }
+                }
+                Ok(Node::Descendants(Descendants { descendants: Cow::Owned(descendants) }))
+            }
+            [unknown, ..] => Err(DecodeError::UnknownTag(*unknown).into()),
+            [] => Err(DecodeError::Invalid("a node cannot be empty").into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::distance::{BinaryQuantizedEuclidean, Euclidean};
+
+    fn split_plane_normal<D: Distance>(
+        normal: &[f32],
+        precision: NormalPrecision,
+    ) -> Node<'static, D> {
+        Node::SplitPlaneNormal(SplitPlaneNormal {
+            left: NodeId::tree(1),
+            right: NodeId::item(2),
+            normal: UnalignedVector::from_vec(normal.to_vec()),
+            precision,
+        })
+    }
+
+    fn round_trip<D: Distance>(node: &Node<D>) -> (usize, SplitPlaneNormal<'static, D>) {
+        let bytes = NodeCodec::bytes_encode(node).unwrap().into_owned();
+        match NodeCodec::<D>::bytes_decode(&bytes).unwrap() {
+            Node::SplitPlaneNormal(SplitPlaneNormal { left, right, normal, precision }) => {
+                let normal = Cow::Owned(normal.into_owned());
+                (bytes.len(), SplitPlaneNormal { left, right, normal, precision })
+            }
+            node => panic!("decoded {node:?} instead of a split plane normal"),
+        }
+    }
+
+    #[test]
+    fn compact_normals() {
+        let normal = [0.6, -0.8, 0.0, 0.001];
+
+        let (full_len, full) =
+            round_trip(&split_plane_normal::<Euclidean>(&normal, NormalPrecision::Full));
+        assert_eq!(full.precision, NormalPrecision::Full);
+        assert_eq!(full.normal.to_vec(), normal);
+        assert_eq!((full.left, full.right), (NodeId::tree(1), NodeId::item(2)));
+
+        let (f16_len, f16) =
+            round_trip(&split_plane_normal::<Euclidean>(&normal, NormalPrecision::F16));
+        assert_eq!(f16.precision, NormalPrecision::F16);
+        assert_eq!(full_len - f16_len, normal.len() * 2);
+        for (x, y) in normal.iter().zip(f16.normal.iter()) {
+            assert!((x - y).abs() < 0.001, "{x} and {y} are too far apart");
+        }
+
+        let (int8_len, int8) =
+            round_trip(&split_plane_normal::<Euclidean>(&normal, NormalPrecision::Int8));
+        assert_eq!(int8.precision, NormalPrecision::Int8);
+        assert_eq!(full_len - int8_len, normal.len() * 3 - 4);
+        for (x, y) in normal.iter().zip(int8.normal.iter()) {
+            assert!((x - y).abs() < 0.01, "{x} and {y} are too far apart");
+        }
+        // The largest value is kept exactly
+        assert_eq!(int8.normal.iter().nth(1), Some(-0.8));
+
+        let (_, zero) =
+            round_trip(&split_plane_normal::<Euclidean>(&[0.0; 4], NormalPrecision::Int8));
+        assert!(zero.normal.is_zero());
+    }
+
+    #[test]
+    fn compact_descendants() {
+        fn encode(ids: &RoaringBitmap) -> Vec<u8> {
+            let node =
+                Node::<Euclidean>::Descendants(Descendants { descendants: Cow::Borrowed(ids) });
+            NodeCodec::bytes_encode(&node).unwrap().into_owned()
+        }
+
+        let ids = RoaringBitmap::from_iter([0, 3, 200, 70_000, u32::MAX]);
+        let bytes = encode(&ids);
+        assert_eq!(bytes[0], DESCENDANTS_VARINT_TAG);
+        assert_eq!(bytes.len(), 1 + 1 + 1 + 2 + 3 + 5);
+        match NodeCodec::<Euclidean>::bytes_decode(&bytes).unwrap() {
+            Node::Descendants(Descendants { descendants }) => assert_eq!(*descendants, ids),
+            node => panic!("decoded {node:?} instead of descendants"),
         }
+
+        // The bitmap containers are smaller once the ids are dense enough
+        let ids = RoaringBitmap::from_iter(0..100_000);
+        assert_eq!(encode(&ids)[0], DESCENDANTS_TAG);
+
+        let truncated = [DESCENDANTS_VARINT_TAG, 3, 0x80];
+        assert!(NodeCodec::<Euclidean>::bytes_decode(&truncated).is_err());
+        let unsorted = [DESCENDANTS_VARINT_TAG, 3, 0];
+        assert!(NodeCodec::<Euclidean>::bytes_decode(&unsorted).is_err());
+        let overflow = [DESCENDANTS_VARINT_TAG, 0xff, 0xff, 0xff, 0xff, 0x1f];
+        assert!(NodeCodec::<Euclidean>::bytes_decode(&overflow).is_err());
+    }
+
+    #[test]
+    fn invalid_nodes() {
+        let decode = |bytes: &[u8]| NodeCodec::<Euclidean>::bytes_decode(bytes).unwrap_err();
+
+        insta::assert_snapshot!(decode(&[]), @"a node cannot be empty");
+        insta::assert_snapshot!(decode(&[42, 0, 0]), @"unknown node tag 42");
+        insta::assert_snapshot!(decode(&[LEAF_TAG, 0, 0]), @"leaf header: expected 4 bytes but only 2 remain");
+        insta::assert_snapshot!(decode(&[SPLIT_PLANE_NORMAL_TAG, 2, 0, 0, 0, 1, 3, 0]), @"node id: expected 5 bytes but only 2 remain");
+        insta::assert_snapshot!(decode(&[SPLIT_PLANE_NORMAL_TAG, 2, 0, 0, 0, 1, 9, 0, 0, 0, 2]), @"unknown node mode 9");
+        insta::assert_snapshot!(decode(&[SPLIT_PLANE_NORMAL_F16_TAG, 2, 0, 0, 0, 1, 3, 0, 0, 0, 2, 0]), @"f16 normal: 1 bytes cannot be split in values of 2 bytes");
+        insta::assert_snapshot!(decode(&[SPLIT_PLANE_NORMAL_INT8_TAG, 2, 0, 0, 0, 1, 3, 0, 0, 0, 2, 0]), @"int8 normal scale: expected 4 bytes but only 1 remain");
+    }
+
+    #[test]
+    fn compact_normals_are_not_used_when_bigger() {
+        let normal = [0.6, -0.8, 0.0, 0.001];
+        let node = split_plane_normal::<BinaryQuantizedEuclidean>(&normal, NormalPrecision::F16);
+        let (_, decoded) = round_trip(&node);
+        assert_eq!(decoded.precision, NormalPrecision::Full);
     }
 }
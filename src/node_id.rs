@@ -3,6 +3,7 @@ use std::mem::size_of;
 
 use byteorder::{BigEndian, ByteOrder};
 
+use crate::error::DecodeError;
 use crate::ItemId;
 
 /// /!\ Changing the value of the enum can be DB-breaking /!\
@@ -54,6 +55,37 @@ impl NodeId {
         Self { mode: NodeMode::Metadata, item: 0 }
     }
 
+    /// The versions of arroy preceding the format version stored the
+    /// updated items in a bitmap under the `ItemId` 1.
+    pub const fn legacy_updated_items() -> Self {
+        Self { mode: NodeMode::Metadata, item: 1 }
+    }
+
+    /// The trees of a build that is not finished yet are stored under the `ItemId` 2.
+    pub const fn pending_build() -> Self {
+        Self { mode: NodeMode::Metadata, item: 2 }
+    }
+
+    /// The options used to build the trees are stored under the `ItemId` 3.
+    pub const fn build_config() -> Self {
+        Self { mode: NodeMode::Metadata, item: 3 }
+    }
+
+    /// The projection of the vectors the split planes are computed on is stored under the `ItemId` 4.
+    pub const fn projection() -> Self {
+        Self { mode: NodeMode::Metadata, item: 4 }
+    }
+
+    /// The version of the format the index is written in is stored under the `ItemId` 5.
+    pub const fn version() -> Self {
+        Self { mode: NodeMode::Metadata, item: 5 }
+    }
+
+    /// The `search_k` tuned for a target recall is stored under the `ItemId` 6.
+    pub const fn search_tuning() -> Self {
+        Self { mode: NodeMode::Metadata, item: 6 }
+    }
+
     pub const fn updated(item: u32) -> Self {
         Self { mode: NodeMode::Updated, item }
     }
@@ -92,11 +124,14 @@ impl NodeId {
         output
     }
 
-    pub fn from_bytes(bytes: &[u8]) -> (Self, &[u8]) {
-        let mode = NodeMode::try_from(bytes[0]).expect("Could not parse the node mode");
-        let item = BigEndian::read_u32(&bytes[1..]);
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<(Self, &[u8]), DecodeError> {
+        let len = size_of::<NodeMode>() + size_of::<ItemId>();
+        let node_id = DecodeError::take(&mut bytes, len, "node id")?;
+        let mode =
+            NodeMode::try_from(node_id[0]).map_err(|_| DecodeError::UnknownMode(node_id[0]))?;
+        let item = BigEndian::read_u32(&node_id[1..]);
 
-        (Self { mode, item }, &bytes[size_of::<NodeMode>() + size_of::<ItemId>()..])
+        Ok((Self { mode, item }, bytes))
     }
 }
 
@@ -133,6 +133,8 @@ pub struct ConcurrentNodeIds {
     select_in_bitmap: AtomicU32,
     /// Tells if you should look in the roaring bitmap or if all the IDs are already exhausted.
     look_into_bitmap: AtomicBool,
+    /// The first ID after all the IDs that were used when creating the generator.
+    first_unused: u32,
 }
 
 impl ConcurrentNodeIds {
@@ -148,9 +150,38 @@ impl ConcurrentNodeIds {
             select_in_bitmap: AtomicU32::new(0),
             look_into_bitmap: AtomicBool::new(!available.is_empty()),
             available,
+            first_unused: last_id,
         }
     }
 
+    /// Creates an ID generator avoiding the specified used IDs, the `replaced` ones
+    /// are not counted as used as their tree nodes are about to be deleted.
+    pub fn replacing(used: RoaringBitmap, replaced: &RoaringBitmap) -> ConcurrentNodeIds {
+        let n_replaced = used.intersection_len(replaced);
+        let ids = ConcurrentNodeIds::new(used);
+        ids.used.fetch_sub(n_replaced, Ordering::Relaxed);
+        ids
+    }
+
+    /// Creates an ID generator for a tree generated on its own. Its IDs start after
+    /// the ones that were used when creating this generator and never conflict with
+    /// the existing tree nodes, but they must be remapped to the IDs returned by `self`.
+    pub fn local(&self) -> ConcurrentNodeIds {
+        ConcurrentNodeIds {
+            current: AtomicU32::new(self.first_unused),
+            used: AtomicU64::new(0),
+            select_in_bitmap: AtomicU32::new(0),
+            look_into_bitmap: AtomicBool::new(false),
+            available: RoaringBitmap::new(),
+            first_unused: self.first_unused,
+        }
+    }
+
+    /// Returns the first ID returned by the generators created with [`Self::local`].
+    pub fn first_local(&self) -> u32 {
+        self.first_unused
+    }
+
     /// Returns a new unique ID and increase the count of IDs used.
     pub fn next(&self) -> Result<u32> {
         if self.used.fetch_add(1, Ordering::Relaxed) > u32::MAX as u64 {
@@ -183,6 +214,8 @@ impl ConcurrentNodeIds {
 pub struct ImmutableLeafs<'t, D> {
     leafs: IntMap<ItemId, *const u8>,
     constant_length: Option<usize>,
+    /// The file the leafs are pointing in when they don't come from LMDB.
+    _file: Option<TmpNodesReader>,
     _marker: marker::PhantomData<(&'t (), D)>,
 }
 
@@ -211,11 +244,32 @@ impl<'t, D: Distance> ImmutableLeafs<'t, D> {
             leafs.insert(item_id, bytes.as_ptr());
         }
 
-        Ok(ImmutableLeafs { leafs, constant_length, _marker: marker::PhantomData })
+        Ok(ImmutableLeafs { leafs, constant_length, _file: None, _marker: marker::PhantomData })
+    }
+
+    /// Creates the structure from leafs written in a temporary file,
+    /// like the projected items the trees are built on.
+    pub fn from_tmp_nodes(file: TmpNodesReader) -> heed::Result<Self> {
+        #[cfg(unix)]
+        file.mmap.advise(memmap2::Advice::Random)?;
+
+        let mut leafs = IntMap::with_capacity_and_hasher(file.len(), BuildNoHashHasher::default());
+        let mut constant_length = None;
+        for (item_id, bytes) in file.to_insert() {
+            assert_eq!(*constant_length.get_or_insert(bytes.len()), bytes.len());
+            leafs.insert(item_id, bytes.as_ptr());
+        }
+
+        Ok(ImmutableLeafs {
+            leafs,
+            constant_length,
+            _file: Some(file),
+            _marker: marker::PhantomData,
+        })
     }
 
     /// Returns the leafs identified by the given ID.
-    pub fn get(&self, item_id: ItemId) -> heed::Result<Option<Leaf<'t, D>>> {
+    pub fn get(&self, item_id: ItemId) -> heed::Result<Option<Leaf<'_, D>>> {
         let len = match self.constant_length {
             Some(len) => len,
             None => return Ok(None),
@@ -227,6 +281,7 @@ impl<'t, D: Distance> ImmutableLeafs<'t, D> {
 
         // safety:
         // - ptr: The pointer comes from LMDB. Since the database cannot be written to, it is still valid.
+        //        Or it comes from the file that lives as long as this structure.
         // - len: All the items share the same dimensions and are the same size
         let bytes = unsafe { slice::from_raw_parts(ptr, len) };
         NodeCodec::bytes_decode(bytes).map_err(heed::Error::Decoding).map(|node| node.leaf())
@@ -239,23 +294,38 @@ unsafe impl<D> Sync for ImmutableLeafs<'_, D> {}
 pub struct ImmutableSubsetLeafs<'t, D> {
     subset: &'t RoaringBitmap,
     leafs: &'t ImmutableLeafs<'t, D>,
+    /// The number of dimensions the leafs are restricted to.
+    dimensions: Option<usize>,
 }
 
 impl<'t, D: Distance> ImmutableSubsetLeafs<'t, D> {
     /// Creates a subset view of the available leafs.
     pub fn from_item_ids(leafs: &'t ImmutableLeafs<'t, D>, subset: &'t RoaringBitmap) -> Self {
-        ImmutableSubsetLeafs { subset, leafs }
+        ImmutableSubsetLeafs { subset, leafs, dimensions: None }
+    }
+
+    /// Restricts the leafs returned to their first dimensions.
+    pub fn with_prefix(self, dimensions: Option<usize>) -> Self {
+        ImmutableSubsetLeafs { dimensions, ..self }
     }
 
     /// Returns the leafs identified by the given ID in the subset.
     pub fn get(&self, item_id: ItemId) -> heed::Result<Option<Leaf<'t, D>>> {
         if self.subset.contains(item_id) {
-            self.leafs.get(item_id)
+            self.leaf(item_id)
         } else {
             Ok(None)
         }
     }
 
+    fn leaf(&self, item_id: ItemId) -> heed::Result<Option<Leaf<'t, D>>> {
+        let leaf = self.leafs.get(item_id)?;
+        Ok(match self.dimensions {
+            Some(dimensions) => leaf.map(|leaf| D::prefix_leaf(leaf, dimensions)),
+            None => leaf,
+        })
+    }
+
     pub fn len(&self) -> u64 {
         self.subset.len()
     }
@@ -264,11 +334,11 @@ impl<'t, D: Distance> ImmutableSubsetLeafs<'t, D> {
     pub fn choose_two<R: Rng>(&self, rng: &mut R) -> heed::Result<Option<[Leaf<'t, D>; 2]>> {
         let indexes = index::sample(rng, self.subset.len() as usize, 2);
         let first = match self.subset.select(indexes.index(0) as u32) {
-            Some(item_id) => self.leafs.get(item_id)?,
+            Some(item_id) => self.leaf(item_id)?,
             None => None,
         };
         let second = match self.subset.select(indexes.index(1) as u32) {
-            Some(item_id) => self.leafs.get(item_id)?,
+            Some(item_id) => self.leaf(item_id)?,
             None => None,
         };
         Ok(first.zip(second).map(|(a, b)| [a, b]))
@@ -282,7 +352,7 @@ impl<'t, D: Distance> ImmutableSubsetLeafs<'t, D> {
             let ubound = (self.subset.len() - 1) as u32;
             let index = rng.gen_range(0..=ubound);
             match self.subset.select(index) {
-                Some(item_id) => self.leafs.get(item_id),
+                Some(item_id) => self.leaf(item_id),
                 None => Ok(None),
             }
         }
@@ -0,0 +1,179 @@
+use std::io::{self, Write};
+
+use heed::RoTxn;
+
+use crate::error::KeyContext;
+use crate::node::{Descendants, SplitPlaneNormal};
+use crate::reader::item_leaf;
+use crate::{Database, Distance, Error, ItemId, Key, Node, NodeId, NodeMode, Projection, Result};
+
+/// The options of [`Reader::plot_tree_json`](crate::Reader::plot_tree_json).
+#[derive(Debug, Clone, Default)]
+pub struct TreePlotOptions {
+    /// The depth after which the nodes are not detailed anymore, only the number of
+    /// items under them is written. `None` writes the whole tree.
+    pub max_depth: Option<usize>,
+    /// Whether the ids of the items of the descendants nodes are written.
+    pub items: bool,
+    /// The two axes of the 2D view, vectors of the dimensions of the index. When they
+    /// are set, the normals of the split nodes are written as 2D vectors and, if
+    /// `items` is set, the items as 2D points.
+    pub axes: Option<[Vec<f32>; 2]>,
+}
+
+/// Writes the nodes of a tree as nested JSON objects.
+pub(crate) struct JsonTree<'a, 't, D: Distance> {
+    rtxn: &'t RoTxn<'t>,
+    database: Database<D>,
+    index: u16,
+    options: &'a TreePlotOptions,
+    /// The axes projected like the items were before computing the split planes.
+    projected_axes: Option<[Vec<f32>; 2]>,
+}
+
+impl<'a, 't, D: Distance> JsonTree<'a, 't, D> {
+    pub fn new(
+        rtxn: &'t RoTxn<'t>,
+        database: Database<D>,
+        index: u16,
+        dimensions: usize,
+        projection: Option<&Projection>,
+        options: &'a TreePlotOptions,
+    ) -> Result<Self> {
+        if let Some(axis) = options.axes.iter().flatten().find(|axis| axis.len() != dimensions) {
+            return Err(Error::InvalidVecDimension { expected: dimensions, received: axis.len() });
+        }
+        // The normals are in the projected space, we compare them to the projected axes
+        let projected_axes = options.axes.as_ref().map(|[x, y]| match projection {
+            Some(projection) => [projection.project(x), projection.project(y)],
+            None => [x.clone(), y.clone()],
+        });
+        Ok(JsonTree { rtxn, database, index, options, projected_axes })
+    }
+
+    /// Writes the node and its children, returns the number of items under it.
+    pub fn write_node(
+        &self,
+        writer: &mut impl Write,
+        node_id: NodeId,
+        depth: usize,
+    ) -> Result<u64> {
+        let id = node_id.item;
+        if self.options.max_depth.is_some_and(|max| depth > max) {
+            let items = self.count_items(node_id)?;
+            write!(writer, r#"{{"kind":"truncated","id":{id},"depth":{depth},"items":{items}}}"#)?;
+            return Ok(items);
+        }
+
+        let key = Key::new(self.index, node_id);
+        let node = self.database.get(self.rtxn, &key).with_key(key)?;
+        match node.ok_or(Error::missing_key(key))? {
+            Node::Leaf(leaf) => {
+                write!(writer, r#"{{"kind":"item","id":{id},"depth":{depth},"items":1"#)?;
+                if self.options.items {
+                    if let Some(axes) = &self.options.axes {
+                        write!(writer, r#","points":["#)?;
+                        write_point(writer, &project(&leaf.vector.to_vec(), axes))?;
+                        write!(writer, "]")?;
+                    }
+                }
+                write!(writer, "}}")?;
+                Ok(1)
+            }
+            Node::Descendants(Descendants { descendants }) => {
+                let items = descendants.len();
+                write!(
+                    writer,
+                    r#"{{"kind":"descendants","id":{id},"depth":{depth},"items":{items}"#
+                )?;
+                if self.options.items {
+                    write!(writer, r#","ids":"#)?;
+                    write_list(writer, descendants.iter(), |w, item| write!(w, "{item}"))?;
+                    if let Some(axes) = &self.options.axes {
+                        write!(writer, r#","points":"#)?;
+                        let points: Result<Vec<_>> =
+                            descendants.iter().map(|item| self.item_point(item, axes)).collect();
+                        write_list(writer, points?, |w, point| write_point(w, &point))?;
+                    }
+                }
+                write!(writer, "}}")?;
+                Ok(items)
+            }
+            Node::SplitPlaneNormal(SplitPlaneNormal { normal, left, right, .. }) => {
+                write!(writer, r#"{{"kind":"split","id":{id},"depth":{depth}"#)?;
+                write!(writer, r#","dummy":{}"#, normal.is_zero())?;
+                if let Some(axes) = &self.projected_axes {
+                    write!(writer, r#","normal":"#)?;
+                    write_point(writer, &project(&normal.to_vec(), axes))?;
+                }
+                write!(writer, r#","left":"#)?;
+                let left = self.write_node(writer, left, depth + 1)?;
+                write!(writer, r#","right":"#)?;
+                let right = self.write_node(writer, right, depth + 1)?;
+                let items = left + right;
+                write!(writer, r#","items":{items}}}"#)?;
+                Ok(items)
+            }
+        }
+    }
+
+    fn count_items(&self, node_id: NodeId) -> Result<u64> {
+        if node_id.mode == NodeMode::Item {
+            return Ok(1);
+        }
+        let key = Key::new(self.index, node_id);
+        match self.database.get(self.rtxn, &key).with_key(key)?.ok_or(Error::missing_key(key))? {
+            Node::Leaf(_) => Ok(1),
+            Node::Descendants(Descendants { descendants }) => Ok(descendants.len()),
+            Node::SplitPlaneNormal(SplitPlaneNormal { left, right, .. }) => {
+                Ok(self.count_items(left)? + self.count_items(right)?)
+            }
+        }
+    }
+
+    fn item_point(&self, item: ItemId, axes: &[Vec<f32>; 2]) -> Result<[f32; 2]> {
+        match item_leaf(self.database, self.index, self.rtxn, item)? {
+            Some(leaf) => Ok(project(&leaf.vector.to_vec(), axes)),
+            None => Err(Error::missing_key(Key::item(self.index, item))),
+        }
+    }
+}
+
+/// Returns the coordinates of the vector on the axes, only the
+/// first values of the axes are used for the shorter vectors.
+fn project(vector: &[f32], [x, y]: &[Vec<f32>; 2]) -> [f32; 2] {
+    let dot = |axis: &[f32]| axis.iter().zip(vector).map(|(a, v)| a * v).sum();
+    [dot(x), dot(y)]
+}
+
+fn write_point(writer: &mut impl Write, [x, y]: &[f32; 2]) -> io::Result<()> {
+    write!(writer, "[")?;
+    write_f32(writer, *x)?;
+    write!(writer, ",")?;
+    write_f32(writer, *y)?;
+    write!(writer, "]")
+}
+
+/// JSON doesn't support the infinite and `NaN` numbers, they are written as `null`.
+fn write_f32(writer: &mut impl Write, value: f32) -> io::Result<()> {
+    if value.is_finite() {
+        write!(writer, "{value}")
+    } else {
+        write!(writer, "null")
+    }
+}
+
+fn write_list<W: Write, T>(
+    writer: &mut W,
+    values: impl IntoIterator<Item = T>,
+    mut write_value: impl FnMut(&mut W, T) -> io::Result<()>,
+) -> io::Result<()> {
+    write!(writer, "[")?;
+    for (i, value) in values.into_iter().enumerate() {
+        if i != 0 {
+            write!(writer, ",")?;
+        }
+        write_value(writer, value)?;
+    }
+    write!(writer, "]")
+}
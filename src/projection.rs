@@ -0,0 +1,142 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::mem::size_of;
+
+use byteorder::{BigEndian, ByteOrder};
+use heed::BoxedError;
+use rand::Rng;
+
+use crate::distance::Distance;
+use crate::error::DecodeError;
+use crate::node::Leaf;
+use crate::unaligned_vector::UnalignedVector;
+
+/// A linear projection of the vectors to fewer dimensions.
+///
+/// The items are projected before computing the split planes, making the tree
+/// nodes smaller and faster to traverse, while the original vectors are kept
+/// to compute the distances.
+#[derive(Clone)]
+pub struct Projection<'a> {
+    /// The number of dimensions of the projected vectors.
+    dimensions: usize,
+    /// The matrix stored in row-major order, one row of the
+    /// original number of dimensions per projected dimension.
+    matrix: Cow<'a, UnalignedVector<f32>>,
+}
+
+impl Projection<'static> {
+    /// Generates a random projection from `from` dimensions to `to` dimensions.
+    ///
+    /// Every coefficient is `±1 / sqrt(to)` with the same probability, which approximately
+    /// preserves the distances between the vectors (Achlioptas, 2003) and is cheap to generate.
+    pub fn random<R: Rng>(rng: &mut R, from: usize, to: usize) -> Self {
+        let scale = (to as f32).sqrt().recip();
+        let matrix = (0..from * to).map(|_| if rng.gen() { scale } else { -scale }).collect();
+        Projection { dimensions: to, matrix: UnalignedVector::from_vec(matrix) }
+    }
+}
+
+impl Projection<'_> {
+    /// Returns a projection that doesn't borrow the database anymore.
+    pub fn into_owned(self) -> Projection<'static> {
+        Projection { dimensions: self.dimensions, matrix: Cow::Owned(self.matrix.into_owned()) }
+    }
+
+    /// The number of dimensions of the projected vectors.
+    pub fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    /// The number of dimensions of the vectors this projection applies to.
+    pub fn source_dimensions(&self) -> usize {
+        self.matrix.len().checked_div(self.dimensions).unwrap_or_default()
+    }
+
+    /// Projects the vector, the extra values of a longer vector are ignored.
+    pub fn project(&self, vector: &[f32]) -> Vec<f32> {
+        let source_dimensions = self.source_dimensions();
+        let mut coefficients = self.matrix.iter();
+        (0..self.dimensions)
+            .map(|_| {
+                coefficients.by_ref().take(source_dimensions).zip(vector).map(|(m, v)| m * v).sum()
+            })
+            .collect()
+    }
+
+    /// Projects the vector of the leaf and computes a header for it.
+    pub fn project_leaf<D: Distance>(&self, leaf: &Leaf<D>) -> Leaf<'static, D> {
+        let vector = UnalignedVector::from_vec(self.project(&leaf.vector.to_vec()));
+        D::reduced_leaf(leaf, vector)
+    }
+}
+
+impl fmt::Debug for Projection<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Projection")
+            .field("dimensions", &self.dimensions)
+            .field("source_dimensions", &self.source_dimensions())
+            .field("matrix", &self.matrix)
+            .finish()
+    }
+}
+
+pub enum ProjectionCodec {}
+
+impl<'a> heed::BytesEncode<'a> for ProjectionCodec {
+    type EItem = Projection<'a>;
+
+    fn bytes_encode(item: &'a Self::EItem) -> Result<Cow<'a, [u8]>, BoxedError> {
+        let Projection { dimensions, matrix } = item;
+
+        let mut output = Vec::with_capacity(size_of::<u32>() + matrix.as_bytes().len());
+        output.extend_from_slice(&u32::try_from(*dimensions)?.to_be_bytes());
+        output.extend_from_slice(matrix.as_bytes());
+
+        Ok(Cow::Owned(output))
+    }
+}
+
+impl<'a> heed::BytesDecode<'a> for ProjectionCodec {
+    type DItem = Projection<'a>;
+
+    fn bytes_decode(bytes: &'a [u8]) -> Result<Self::DItem, BoxedError> {
+        let mut bytes = bytes;
+        let dimensions = DecodeError::take(&mut bytes, size_of::<u32>(), "dimensions")?;
+        let dimensions = BigEndian::read_u32(dimensions) as usize;
+        let matrix = UnalignedVector::from_bytes(bytes)?;
+
+        Ok(Projection { dimensions, matrix })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use heed::{BytesDecode, BytesEncode};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn projection_codec() {
+        let projection = Projection::random(&mut StdRng::seed_from_u64(42), 6, 3);
+
+        let encoded = ProjectionCodec::bytes_encode(&projection).unwrap();
+        let decoded = ProjectionCodec::bytes_decode(&encoded).unwrap();
+
+        assert_eq!(decoded.dimensions(), 3);
+        assert_eq!(decoded.source_dimensions(), 6);
+        assert_eq!(projection.matrix.as_bytes(), decoded.matrix.as_bytes());
+    }
+
+    #[test]
+    fn project() {
+        let matrix = UnalignedVector::from_vec(vec![1.0, 0.0, 0.0, 0.0, 1.0, 1.0]);
+        let projection = Projection { dimensions: 2, matrix };
+
+        assert_eq!(projection.project(&[2.0, 3.0, 4.0]), vec![2.0, 7.0]);
+        // The extra padding of the quantized vectors is ignored
+        assert_eq!(projection.project(&[2.0, 3.0, 4.0, 5.0]), vec![2.0, 7.0]);
+    }
+}
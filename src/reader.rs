@@ -1,22 +1,34 @@
 use std::cmp::Reverse;
-use std::collections::BinaryHeap;
+use std::collections::{BTreeMap, BinaryHeap};
+use std::io;
 use std::iter::repeat;
 use std::marker;
+use std::mem::size_of;
 use std::num::NonZeroUsize;
 
-use heed::types::DecodeIgnore;
+use heed::types::{Bytes, DecodeIgnore};
 use heed::RoTxn;
 use ordered_float::OrderedFloat;
+use rand::Rng;
 use roaring::RoaringBitmap;
 
+use crate::annoy::{self, AnnoyDistance};
+use crate::benchmark;
 use crate::distance::Distance;
+use crate::error::KeyContext;
 use crate::internals::{KeyCodec, Side};
 use crate::item_iter::ItemIter;
-use crate::node::{Descendants, ItemIds, Leaf, SplitPlaneNormal};
+use crate::node::{
+    Descendants, ItemIds, Leaf, SplitPlaneNormal, SPLIT_PLANE_NORMAL_F16_TAG,
+    SPLIT_PLANE_NORMAL_INT8_TAG, SPLIT_PLANE_NORMAL_TAG,
+};
 use crate::unaligned_vector::UnalignedVector;
+use crate::verify::verify;
 use crate::{
-    Database, Error, ItemId, Key, MetadataCodec, Node, NodeId, Prefix, PrefixCodec, Result, Stats,
-    TreeStats,
+    BuildConfig, BuildConfigCodec, Database, Error, IndexBytes, ItemId, Key, MetadataCodec, Node,
+    NodeId, NodeMode, Prefix, PrefixCodec, Projection, ProjectionCodec, Result, SearchTuning,
+    SearchTuningCodec, SplitLevelStats, Stats, TreeStats, VerifyReport, VersionCodec,
+    FORMAT_VERSION,
 };
 
 /// Options used to make a query against an arroy [`Reader`].
@@ -72,8 +84,8 @@ impl<'a, D: Distance> QueryBuilder<'a, D> {
     }
 
     /// During the query, arroy will inspect up to `search_k` nodes which defaults
-    /// to `n_trees * count` if not provided. `search_k` gives you a run-time
-    /// tradeoff between better accuracy and speed.
+    /// to `n_trees * count` if not provided, or to the [tuned one](Reader::search_tuning)
+    /// if any. `search_k` gives you a run-time tradeoff between better accuracy and speed.
     ///
     /// # Examples
     ///
@@ -128,14 +140,32 @@ pub struct Reader<'t, D: Distance> {
     roots: ItemIds<'t>,
     dimensions: usize,
     items: RoaringBitmap,
+    build_config: BuildConfig,
+    search_tuning: Option<SearchTuning>,
+    projection: Option<Projection<'t>>,
     _marker: marker::PhantomData<D>,
 }
 
 impl<'t, D: Distance> Reader<'t, D> {
     /// Returns a reader over the database with the specified [`Distance`] type.
     pub fn open(rtxn: &'t RoTxn, index: u16, database: Database<D>) -> Result<Reader<'t, D>> {
+        // The layout of a newer format is unknown, we must not try to decode anything else
+        let version_key = Key::version(index);
+        let version = database
+            .remap_data_type::<VersionCodec>()
+            .get(rtxn, &version_key)
+            .with_key(version_key)?
+            .unwrap_or(0);
+        if version > FORMAT_VERSION {
+            return Err(Error::UnsupportedVersion { index, version, supported: FORMAT_VERSION });
+        }
+
         let metadata_key = Key::metadata(index);
-        let metadata = match database.remap_data_type::<MetadataCodec>().get(rtxn, &metadata_key)? {
+        let metadata = match database
+            .remap_data_type::<MetadataCodec>()
+            .get(rtxn, &metadata_key)
+            .with_key(metadata_key)?
+        {
             Some(metadata) => metadata,
             None => return Err(Error::MissingMetadata(index)),
         };
@@ -156,12 +186,32 @@ impl<'t, D: Distance> Reader<'t, D> {
             return Err(Error::NeedBuild(index));
         }
 
+        let build_config_key = Key::build_config(index);
+        let build_config = database
+            .remap_data_type::<BuildConfigCodec>()
+            .get(rtxn, &build_config_key)
+            .with_key(build_config_key)?
+            .unwrap_or_default();
+        let search_tuning_key = Key::search_tuning(index);
+        let search_tuning = database
+            .remap_data_type::<SearchTuningCodec>()
+            .get(rtxn, &search_tuning_key)
+            .with_key(search_tuning_key)?;
+        let projection_key = Key::projection(index);
+        let projection = database
+            .remap_data_type::<ProjectionCodec>()
+            .get(rtxn, &projection_key)
+            .with_key(projection_key)?;
+
         Ok(Reader {
             database: database.remap_data_type(),
             index,
             roots: metadata.roots,
             dimensions: metadata.dimensions.try_into().unwrap(),
             items: metadata.items,
+            build_config,
+            search_tuning,
+            projection,
             _marker: marker::PhantomData,
         })
     }
@@ -176,6 +226,17 @@ impl<'t, D: Distance> Reader<'t, D> {
         self.roots.len()
     }
 
+    /// Returns the options used to build the trees of the index.
+    pub fn build_config(&self) -> BuildConfig {
+        self.build_config
+    }
+
+    /// Returns the `search_k` tuned with [`Self::tune_search_k`] and stored with
+    /// [`Writer::set_search_tuning`](crate::Writer::set_search_tuning).
+    pub fn search_tuning(&self) -> Option<SearchTuning> {
+        self.search_tuning
+    }
+
     /// Returns the number of vectors stored in the index.
     pub fn n_items(&self) -> u64 {
         self.items.len()
@@ -193,42 +254,144 @@ impl<'t, D: Distance> Reader<'t, D> {
 
     /// Returns the stats of the trees of this database.
     pub fn stats(&self, rtxn: &RoTxn) -> Result<Stats> {
+        /// The distributions measured over all the trees.
+        #[derive(Default)]
+        struct Distributions {
+            descendants_sizes: BTreeMap<usize, usize>,
+            leaf_depths: BTreeMap<usize, u64>,
+            /// The number of split nodes, the sum and the max of their imbalance by level.
+            split_levels: Vec<(usize, f64, f32)>,
+        }
+
         fn recursive_depth<D: Distance>(
             rtxn: &RoTxn,
             database: Database<D>,
             index: u16,
             node_id: NodeId,
+            level: usize,
+            distributions: &mut Distributions,
         ) -> Result<TreeStats> {
-            match database.get(rtxn, &Key::new(index, node_id))?.unwrap() {
+            let key = Key::new(index, node_id);
+            match database.get(rtxn, &key).with_key(key)?.ok_or(Error::missing_key(key))? {
                 Node::Leaf(_) => {
-                    Ok(TreeStats { depth: 1, dummy_normals: 0, split_nodes: 0, descendants: 0 })
+                    *distributions.leaf_depths.entry(level + 1).or_default() += 1;
+                    Ok(TreeStats {
+                        depth: 1,
+                        dummy_normals: 0,
+                        split_nodes: 0,
+                        descendants: 0,
+                        descendants_bytes: 0,
+                        descendants_roaring_bytes: 0,
+                        items: 1,
+                    })
                 }
-                Node::Descendants(_) => {
-                    Ok(TreeStats { depth: 1, dummy_normals: 0, split_nodes: 0, descendants: 1 })
+                Node::Descendants(descendants) => {
+                    let items = descendants.descendants.len();
+                    *distributions.descendants_sizes.entry(items as usize).or_default() += 1;
+                    *distributions.leaf_depths.entry(level + 1).or_default() += items;
+                    Ok(TreeStats {
+                        depth: 1,
+                        dummy_normals: 0,
+                        split_nodes: 0,
+                        descendants: 1,
+                        descendants_bytes: descendants.encoded_len(),
+                        descendants_roaring_bytes: descendants.roaring_len(),
+                        items,
+                    })
                 }
-                Node::SplitPlaneNormal(SplitPlaneNormal { normal, left, right }) => {
-                    let left = recursive_depth(rtxn, database, index, left)?;
-                    let right = recursive_depth(rtxn, database, index, right)?;
+                Node::SplitPlaneNormal(SplitPlaneNormal { normal, left, right, .. }) => {
+                    let left =
+                        recursive_depth(rtxn, database, index, left, level + 1, distributions)?;
+                    let right =
+                        recursive_depth(rtxn, database, index, right, level + 1, distributions)?;
                     let is_zero_normal = normal.is_zero() as usize;
 
+                    let items = left.items + right.items;
+                    let imbalance = match items {
+                        0 => 0.0,
+                        _ => left.items.abs_diff(right.items) as f32 / items as f32,
+                    };
+                    if distributions.split_levels.len() <= level {
+                        distributions.split_levels.resize(level + 1, (0, 0.0, 0.0));
+                    }
+                    let (split_nodes, sum, max) = &mut distributions.split_levels[level];
+                    *split_nodes += 1;
+                    *sum += imbalance as f64;
+                    *max = max.max(imbalance);
+
                     Ok(TreeStats {
                         depth: 1 + left.depth.max(right.depth),
                         dummy_normals: left.dummy_normals + right.dummy_normals + is_zero_normal,
                         split_nodes: left.split_nodes + right.split_nodes + 1,
                         descendants: left.descendants + right.descendants,
+                        descendants_bytes: left.descendants_bytes + right.descendants_bytes,
+                        descendants_roaring_bytes: left.descendants_roaring_bytes
+                            + right.descendants_roaring_bytes,
+                        items,
                     })
                 }
             }
         }
 
+        let mut distributions = Distributions::default();
         let tree_stats: Result<Vec<_>> = self
             .roots
             .iter()
             .map(NodeId::tree)
-            .map(|root| recursive_depth::<D>(rtxn, self.database, self.index, root))
+            .map(|root| {
+                recursive_depth::<D>(rtxn, self.database, self.index, root, 0, &mut distributions)
+            })
             .collect();
 
-        Ok(Stats { tree_stats: tree_stats?, leaf: self.items.len() })
+        let Distributions { descendants_sizes, leaf_depths, split_levels } = distributions;
+        let split_levels = split_levels
+            .into_iter()
+            .map(|(split_nodes, sum, max_imbalance)| SplitLevelStats {
+                split_nodes,
+                mean_imbalance: if split_nodes == 0 {
+                    0.0
+                } else {
+                    (sum / split_nodes as f64) as f32
+                },
+                max_imbalance,
+            })
+            .collect();
+
+        Ok(Stats {
+            tree_stats: tree_stats?,
+            leaf: self.items.len(),
+            bytes: self.bytes(rtxn)?,
+            descendants_sizes,
+            leaf_depths,
+            split_levels,
+        })
+    }
+
+    /// Returns the number of bytes of the keys and the values of the index by kind of node.
+    fn bytes(&self, rtxn: &RoTxn) -> Result<IndexBytes> {
+        let mut bytes = IndexBytes::default();
+        let iter = self
+            .database
+            .remap_types::<PrefixCodec, Bytes>()
+            .prefix_iter(rtxn, &Prefix::all(self.index))?
+            .remap_key_type::<KeyCodec>();
+        for result in iter {
+            let (key, value) = result?;
+            let size = (size_of::<u64>() + value.len()) as u64;
+            match key.node.mode {
+                NodeMode::Item => bytes.leafs += size,
+                NodeMode::Tree => match value.first() {
+                    Some(
+                        &(SPLIT_PLANE_NORMAL_TAG
+                        | SPLIT_PLANE_NORMAL_F16_TAG
+                        | SPLIT_PLANE_NORMAL_INT8_TAG),
+                    ) => bytes.split_normals += size,
+                    _ => bytes.descendants += size,
+                },
+                NodeMode::Metadata | NodeMode::Updated => bytes.metadata += size,
+            }
+        }
+        Ok(bytes)
     }
 
     /// Returns the number of nodes in the index. Useful to run an exhaustive search.
@@ -273,7 +436,7 @@ impl<'t, D: Distance> Reader<'t, D> {
     /// Return a [`QueryBuilder`] that lets you configure and execute a search request.
     ///
     /// You must provide the number of items you want to receive.
-    pub fn nns(&self, count: usize) -> QueryBuilder<D> {
+    pub fn nns(&self, count: usize) -> QueryBuilder<'_, D> {
         QueryBuilder { reader: self, count, search_k: None, oversampling: None, candidates: None }
     }
 
@@ -290,13 +453,24 @@ impl<'t, D: Distance> Reader<'t, D> {
         // The number of root nodes + log2 of the total number of vectors.
         let mut queue =
             BinaryHeap::with_capacity(self.roots.len() + self.items.len().ilog2() as usize);
-        let search_k = opt.search_k.map_or(opt.count * self.roots.len(), NonZeroUsize::get);
+        // The tuning is only valid for the trees it was measured on
+        let tuning = self.search_tuning.filter(|t| t.n_trees == self.roots.len());
+        let (search_k, default_oversampling) = match (opt.search_k, tuning) {
+            (Some(search_k), _) => (search_k.get(), D::DEFAULT_OVERSAMPLING),
+            // The tuned search_k was measured without oversampling
+            (None, Some(tuning)) => (tuning.search_k(opt.count), 1),
+            (None, None) => (opt.count * self.roots.len(), D::DEFAULT_OVERSAMPLING),
+        };
         let search_k = opt
             .oversampling
-            .map_or(search_k.saturating_mul(D::DEFAULT_OVERSAMPLING), |oversampling| {
+            .map_or(search_k.saturating_mul(default_oversampling), |oversampling| {
                 search_k.saturating_mul(oversampling.get())
             });
 
+        // The split planes may have been computed on the projected items
+        let projected_leaf = self.projection.as_ref().map(|p| p.project_leaf(query_leaf));
+        let tree_query = projected_leaf.as_ref().unwrap_or(query_leaf);
+
         // Insert all the root nodes and associate them to the highest distance.
         queue.extend(repeat(OrderedFloat(f32::INFINITY)).zip(self.roots.iter().map(NodeId::tree)));
 
@@ -308,9 +482,9 @@ impl<'t, D: Distance> Reader<'t, D> {
             };
 
             let key = Key::new(self.index, item);
-            match self.database.get(rtxn, &key)?.ok_or(Error::missing_key(key))? {
+            match self.database.get(rtxn, &key).with_key(key)?.ok_or(Error::missing_key(key))? {
                 Node::Leaf(_) => {
-                    if opt.candidates.map_or(true, |c| c.contains(item.item)) {
+                    if opt.candidates.is_none_or(|c| c.contains(item.item)) {
                         nns.push(item.unwrap_item());
                     }
                 }
@@ -321,8 +495,10 @@ impl<'t, D: Distance> Reader<'t, D> {
                         nns.extend(descendants.iter());
                     }
                 }
-                Node::SplitPlaneNormal(SplitPlaneNormal { normal, left, right }) => {
-                    let margin = D::margin_no_header(&normal, &query_leaf.vector);
+                Node::SplitPlaneNormal(SplitPlaneNormal { normal, left, right, .. }) => {
+                    // The split plane may have been computed on the first dimensions only
+                    let query = tree_query.vector.prefix(normal.len());
+                    let margin = D::margin_no_header(&normal, query);
                     queue.push((OrderedFloat(D::pq_distance(dist, margin, Side::Left)), left));
                     queue.push((OrderedFloat(D::pq_distance(dist, margin, Side::Right)), right));
                 }
@@ -337,7 +513,12 @@ impl<'t, D: Distance> Reader<'t, D> {
         let mut nns_distances = Vec::with_capacity(nns.len());
         for nn in nns {
             let key = Key::item(self.index, nn);
-            let leaf = match self.database.get(rtxn, &key)?.ok_or(Error::missing_key(key))? {
+            let leaf = match self
+                .database
+                .get(rtxn, &key)
+                .with_key(key)?
+                .ok_or(Error::missing_key(key))?
+            {
                 Node::Leaf(leaf) => leaf,
                 Node::Descendants(_) | Node::SplitPlaneNormal(_) => unreachable!(),
             };
@@ -363,13 +544,25 @@ impl<'t, D: Distance> Reader<'t, D> {
     pub fn plot_internals_tree_nodes(
         &self,
         rtxn: &RoTxn,
+        writer: impl std::io::Write,
+    ) -> Result<()> {
+        self.plot_tree(rtxn, 0, writer)
+    }
+
+    #[cfg(feature = "plot")]
+    /// Write the nth tree of the index in dot format into the provided writer,
+    /// the graph is empty if there are fewer trees.
+    pub fn plot_tree(
+        &self,
+        rtxn: &RoTxn,
+        nth: usize,
         mut writer: impl std::io::Write,
     ) -> Result<()> {
         writeln!(writer, "digraph {{")?;
         writeln!(writer, "\tlabel=metadata")?;
         writeln!(writer)?;
 
-        if let Some(tree) = self.roots.iter().next() {
+        if let Some(tree) = self.roots.iter().nth(nth) {
             // subgraph {
             //   a -> b
             //   a -> b
@@ -385,12 +578,12 @@ impl<'t, D: Distance> Reader<'t, D> {
 
             let mut explore = vec![Key::tree(self.index, tree)];
             while let Some(key) = explore.pop() {
-                match self.database.get(rtxn, &key)?.unwrap() {
+                match self.database.get(rtxn, &key).with_key(key)?.ok_or(Error::missing_key(key))? {
                     Node::Leaf(_) => (),
                     Node::Descendants(Descendants { descendants: _ }) => {
                         writeln!(writer, "\t\t{} [label=\"{}\"]", key.node.item, key.node.item,)?
                     }
-                    Node::SplitPlaneNormal(SplitPlaneNormal { normal, left, right }) => {
+                    Node::SplitPlaneNormal(SplitPlaneNormal { normal, left, right, .. }) => {
                         if normal.is_zero() {
                             writeln!(writer, "\t\t{} [color=red]", key.node.item)?;
                         }
@@ -422,6 +615,58 @@ impl<'t, D: Distance> Reader<'t, D> {
         Ok(())
     }
 
+    #[cfg(feature = "plot")]
+    /// Write the nth tree of the index as nested JSON objects into the provided writer,
+    /// the root is `null` if there are fewer trees.
+    ///
+    /// Every node has a `kind`, `split`, `descendants`, `item` or `truncated` for the nodes
+    /// deeper than [`TreePlotOptions::max_depth`], an `id`, a `depth` and the number of `items`
+    /// under it. The split nodes have a `left` and a `right` child.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use arroy::{Reader, TreePlotOptions, distances::Euclidean};
+    /// # let (reader, rtxn): (Reader<Euclidean>, heed::RoTxn) = todo!();
+    /// // Views the first two dimensions of the items of a 3D index
+    /// let axes = [vec![1.0, 0.0, 0.0], vec![0.0, 1.0, 0.0]];
+    /// let options = TreePlotOptions { max_depth: Some(8), items: true, axes: Some(axes) };
+    /// let file = std::fs::File::create("tree.json")?;
+    /// reader.plot_tree_json(&rtxn, 0, &options, std::io::BufWriter::new(file))?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn plot_tree_json(
+        &self,
+        rtxn: &RoTxn,
+        nth: usize,
+        options: &crate::TreePlotOptions,
+        mut writer: impl std::io::Write,
+    ) -> Result<()> {
+        let tree = crate::plot::JsonTree::new(
+            rtxn,
+            self.database,
+            self.index,
+            self.dimensions,
+            self.projection.as_ref(),
+            options,
+        )?;
+
+        write!(
+            writer,
+            r#"{{"index":{},"tree":{nth},"dimensions":{}"#,
+            self.index, self.dimensions
+        )?;
+        write!(writer, r#","root":"#)?;
+        match self.roots.iter().nth(nth) {
+            Some(root) => tree.write_node(&mut writer, NodeId::tree(root), 0).map(drop)?,
+            None => write!(writer, "null")?,
+        }
+        writeln!(writer, "}}")?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
     #[cfg(feature = "plot")]
     /// Return the number of nodes in a node.
     fn nb_sub_nodes(
@@ -434,10 +679,11 @@ impl<'t, D: Distance> Reader<'t, D> {
             return Ok(*count);
         }
 
-        match self.database.get(rtxn, &Key::new(self.index, node_id))?.unwrap() {
+        let key = Key::new(self.index, node_id);
+        match self.database.get(rtxn, &key).with_key(key)?.ok_or(Error::missing_key(key))? {
             Node::Leaf(_) => Ok(1),
             Node::Descendants(Descendants { descendants }) => Ok(descendants.len()),
-            Node::SplitPlaneNormal(SplitPlaneNormal { normal: _, left, right }) => {
+            Node::SplitPlaneNormal(SplitPlaneNormal { normal: _, left, right, .. }) => {
                 let left = self.nb_sub_nodes(rtxn, left, cache)?;
                 let right = self.nb_sub_nodes(rtxn, right, cache)?;
                 let nb_descendants = left + right;
@@ -448,6 +694,68 @@ impl<'t, D: Distance> Reader<'t, D> {
         }
     }
 
+    /// Samples items of the index as queries and returns the smallest `search_k` that finds,
+    /// on average, the `target_recall` proportion of their exact `count` nearest neighbors.
+    ///
+    /// The `search_k` is doubled until the recall is reached, then refined by bisection. The
+    /// oversampling only multiplies the `search_k`, the tuning is measured without it. If the
+    /// target can't be reached, the `search_k` that explores all the trees is returned.
+    /// The exact neighbors are computed in the current rayon thread pool, call this function
+    /// in [`ThreadPool::install`](rayon::ThreadPool::install) to use another one.
+    ///
+    /// The tuning can be stored in the index with [`Writer::set_search_tuning`](crate::Writer::set_search_tuning).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use arroy::{Reader, Writer, distances::Euclidean};
+    /// # let (writer, mut wtxn): (Writer<Euclidean>, heed::RwTxn) = todo!();
+    /// # let database: arroy::Database<Euclidean> = todo!();
+    /// # let mut rng: rand::rngs::StdRng = todo!();
+    /// let reader = Reader::open(&wtxn, 0, database)?;
+    /// let tuning = reader.tune_search_k(&wtxn, 10, 0.95, &mut rng)?;
+    /// println!("search_k {} reaches a recall of {}", tuning.search_k, tuning.recall);
+    /// drop(reader);
+    /// writer.set_search_tuning(&mut wtxn, Some(tuning))?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn tune_search_k<R: Rng>(
+        &self,
+        rtxn: &RoTxn,
+        count: usize,
+        target_recall: f32,
+        rng: &mut R,
+    ) -> Result<SearchTuning>
+    where
+        D::Header: Sync,
+    {
+        benchmark::tune_search_k(rtxn, self, count, target_recall, rng)
+    }
+
+    /// Walks every tree of the index and returns a report of the problems found,
+    /// like the tree nodes that are not part of any tree or the items some trees can't reach.
+    ///
+    /// The trees of the pending build are also walked to find the tree nodes they share
+    /// with the other trees. The problems can be fixed with [`Writer::repair`](crate::Writer::repair).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use arroy::{Reader, distances::Euclidean};
+    /// # let (reader, rtxn): (Reader<Euclidean>, heed::RoTxn) = todo!();
+    /// let report = reader.verify(&rtxn)?;
+    /// if !report.is_valid() {
+    ///     println!("the trees {:?} must be built again", report.broken_trees);
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn verify(&self, rtxn: &RoTxn) -> Result<VerifyReport> {
+        let roots: Vec<_> = self.roots.iter().collect();
+        let verification =
+            verify(rtxn, self.database, self.index, self.dimensions, &roots, &self.items)?;
+        Ok(verification.report)
+    }
+
     /// Verify that the whole reader is correctly formed:
     /// - We can access all the items.
     /// - All the tree nodes are part of a tree.
@@ -488,6 +796,21 @@ impl<'t, D: Distance> Reader<'t, D> {
             tree_ids -= trees;
         }
 
+        // The trees of an unfinished build are not visible yet but they must not share nodes
+        let pending_key = Key::pending_build(self.index);
+        if let Some(pending) = self
+            .database
+            .remap_data_type::<crate::PendingBuildCodec>()
+            .get(rtxn, &pending_key)
+            .with_key(pending_key)?
+        {
+            for root in pending.roots.iter() {
+                let (trees, _items) = self.gather_items_and_tree_ids(rtxn, NodeId::tree(root))?;
+                assert!(tree_ids.is_superset(&trees), "A pending tree contains an invalid tree node. Either doesn't exist or was already used in another tree");
+                tree_ids -= trees;
+            }
+        }
+
         assert!(tree_ids.is_empty(), "There is {tree_ids:?} tree nodes floating around");
         Ok(())
     }
@@ -500,7 +823,8 @@ impl<'t, D: Distance> Reader<'t, D> {
         rtxn: &RoTxn,
         node_id: NodeId,
     ) -> Result<(RoaringBitmap, RoaringBitmap)> {
-        match self.database.get(rtxn, &Key::new(self.index, node_id))?.unwrap() {
+        let key = Key::new(self.index, node_id);
+        match self.database.get(rtxn, &key).with_key(key)?.ok_or(Error::missing_key(key))? {
             Node::Leaf(_) => Ok((
                 RoaringBitmap::new(),
                 RoaringBitmap::from_sorted_iter(Some(node_id.item)).unwrap(),
@@ -509,7 +833,7 @@ impl<'t, D: Distance> Reader<'t, D> {
                 RoaringBitmap::from_sorted_iter(Some(node_id.item)).unwrap(),
                 descendants.into_owned(),
             )),
-            Node::SplitPlaneNormal(SplitPlaneNormal { normal: _, left, right }) => {
+            Node::SplitPlaneNormal(SplitPlaneNormal { normal: _, left, right, .. }) => {
                 let left = self.gather_items_and_tree_ids(rtxn, left)?;
                 let right = self.gather_items_and_tree_ids(rtxn, right)?;
 
@@ -531,13 +855,47 @@ impl<'t, D: Distance> Reader<'t, D> {
     }
 }
 
+impl<D: AnnoyDistance> Reader<'_, D> {
+    /// Writes the items and the trees of the index in the file format of
+    /// [Annoy](https://github.com/spotify/annoy), that `AnnoyIndex.load` can memory-map.
+    ///
+    /// The Annoy index must be created with the number of dimensions of this one and the metric
+    /// given by [`AnnoyDistance::METRIC`]. Annoy only supports the item IDs `0..n`, the IDs missing
+    /// from the index are stored as zeroed vectors that no search returns.
+    ///
+    /// The indexes built with [`ArroyBuilder::reduce_dimensions`](crate::ArroyBuilder::reduce_dimensions)
+    /// cannot be exported, Annoy doesn't project the queries before traversing the trees.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use arroy::{Reader, distances::Euclidean};
+    /// # let (reader, rtxn): (Reader<Euclidean>, heed::RoTxn) = todo!();
+    /// let file = std::fs::File::create("index.ann")?;
+    /// reader.export_annoy(&rtxn, file)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn export_annoy(&self, rtxn: &RoTxn, writer: impl io::Write) -> Result<()> {
+        if self.projection.is_some() {
+            return Err(Error::AnnoyExport {
+                index: self.index,
+                reason: "the trees are built on projected vectors",
+            });
+        }
+
+        let roots: Vec<_> = self.roots.iter().collect();
+        annoy::export(rtxn, self.database, self.index, self.dimensions, &roots, &self.items, writer)
+    }
+}
+
 pub fn item_leaf<'a, D: Distance>(
     database: Database<D>,
     index: u16,
     rtxn: &'a RoTxn,
     item: ItemId,
 ) -> Result<Option<Leaf<'a, D>>> {
-    match database.get(rtxn, &Key::item(index, item))? {
+    let key = Key::item(index, item);
+    match database.get(rtxn, &key).with_key(key)? {
         Some(Node::Leaf(leaf)) => Ok(Some(leaf)),
         Some(Node::SplitPlaneNormal(_)) => Ok(None),
         Some(Node::Descendants(_)) => Ok(None),
@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 /// The different stats of an arroy database.
 #[derive(Debug, Clone)]
 pub struct Stats {
@@ -5,6 +7,15 @@ pub struct Stats {
     pub leaf: u64,
     /// The stats of each individual tree.
     pub tree_stats: Vec<TreeStats>,
+    /// The number of bytes the index takes in the database.
+    pub bytes: IndexBytes,
+    /// The number of descendants nodes of all the trees by number of items they contain.
+    pub descendants_sizes: BTreeMap<usize, usize>,
+    /// The number of items of all the trees by depth they are found at, the roots being at depth `1`.
+    pub leaf_depths: BTreeMap<usize, u64>,
+    /// How evenly the split nodes of all the trees divide their items,
+    /// by level, the roots being at level `0`.
+    pub split_levels: Vec<SplitLevelStats>,
 }
 
 /// The different stats of a tree in an arroy database.
@@ -19,4 +30,48 @@ pub struct TreeStats {
     pub split_nodes: usize,
     /// Number of descendants nodes in the tree.
     pub descendants: usize,
+    /// Number of bytes the ids of the descendants nodes take in the database.
+    pub descendants_bytes: usize,
+    /// Number of bytes the ids of the descendants nodes would take
+    /// if they were all stored as roaring bitmaps.
+    pub descendants_roaring_bytes: usize,
+    /// Number of items the tree leads to, it is the number of items
+    /// of the index unless the tree is broken.
+    pub items: u64,
+}
+
+/// The number of bytes the keys and the values of an index take in the database,
+/// without the overhead of the LMDB pages.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct IndexBytes {
+    /// The bytes of the items and their vectors.
+    pub leafs: u64,
+    /// The bytes of the split nodes and their normals.
+    pub split_normals: u64,
+    /// The bytes of the descendants nodes.
+    pub descendants: u64,
+    /// The bytes of the metadata, the build options, the projection and the updated items.
+    pub metadata: u64,
+}
+
+impl IndexBytes {
+    /// The number of bytes of the whole index.
+    pub fn total(&self) -> u64 {
+        self.leafs + self.split_normals + self.descendants + self.metadata
+    }
+}
+
+/// How evenly the split nodes at a level of the trees divide their items.
+///
+/// The imbalance of a split node is `|left - right| / (left + right)` where `left`
+/// and `right` are the numbers of items on each side. It is `0` when both sides have
+/// the same number of items and `1` when all the items are on the same side.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct SplitLevelStats {
+    /// The number of split nodes at this level.
+    pub split_nodes: usize,
+    /// The mean imbalance of the split nodes.
+    pub mean_imbalance: f32,
+    /// The imbalance of the least balanced split node.
+    pub max_imbalance: f32,
 }
@@ -0,0 +1,362 @@
+use std::fmt::Write;
+
+use rand::Rng;
+use roaring::RoaringBitmap;
+
+use super::*;
+use crate::annoy::AnnoyFile;
+use crate::distances::{Cosine, DotProduct, Euclidean};
+use crate::{AnnoyDistance, AnnoyMetric, Key, Node, NodeId, Writer};
+
+/// Returns the items Annoy finds in a tree when its search is not limited.
+fn tree_items(file: &AnnoyFile, root: usize) -> RoaringBitmap {
+    let mut items = RoaringBitmap::new();
+    let mut explore = vec![root];
+    while let Some(i) = explore.pop() {
+        if file.is_item(i) {
+            items.insert(i as u32);
+        } else if file.is_descendants(i) {
+            items.extend(file.children(i).map(|id| id as u32));
+        } else {
+            explore.extend(file.children(i));
+        }
+    }
+    items
+}
+
+fn export<D: AnnoyDistance>(reader: &Reader<D>, rtxn: &heed::RoTxn) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    reader.export_annoy(rtxn, &mut bytes).unwrap();
+    bytes
+}
+
+#[test]
+fn layouts() {
+    let mut s = String::new();
+    for metric in [AnnoyMetric::Angular, AnnoyMetric::Euclidean, AnnoyMetric::Dot] {
+        writeln!(
+            s,
+            "{}: {:?}, K={}",
+            metric.name(),
+            metric.layout(3),
+            metric.layout(3).max_descendants()
+        )
+        .unwrap();
+    }
+    insta::assert_snapshot!(s, @r###"
+    angular: NodeLayout { size: 24, children: 4, extra: 4, vector: 12 }, K=5
+    euclidean: NodeLayout { size: 28, children: 8, extra: 4, vector: 16 }, K=5
+    dot: NodeLayout { size: 28, children: 4, extra: 12, vector: 16 }, K=6
+    "###);
+}
+
+#[test]
+fn export_small_index() {
+    let handle = create_database::<Euclidean>();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 2);
+    writer.add_item(&mut wtxn, 0, &[0.0, 1.0]).unwrap();
+    writer.add_item(&mut wtxn, 2, &[2.0, 3.0]).unwrap();
+    writer.builder(&mut rng()).n_trees(2).build(&mut wtxn).unwrap();
+
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, handle.database).unwrap();
+    let bytes = export(&reader, &wtxn);
+    let file = AnnoyFile::new(Euclidean::METRIC, 2, &bytes).unwrap();
+    let mut s = String::new();
+    for i in 0..file.n_nodes() {
+        let (n, a, v) = (file.n_descendants(i), file.extra(i), file.vector(i));
+        let children: Vec<_> = file.children(i).collect();
+        writeln!(s, "{i}: n_descendants: {n}, a: {a}, children: {children:?}, v: {v:?}").unwrap();
+    }
+    writeln!(s, "n_items: {}, roots: {:?}", file.n_items, file.roots).unwrap();
+    insta::assert_snapshot!(s, @r###"
+    0: n_descendants: 1, a: 0, children: [0, 0], v: [0.0, 1.0]
+    1: n_descendants: 0, a: 0, children: [], v: [0.0, 0.0]
+    2: n_descendants: 1, a: 0, children: [0, 0], v: [2.0, 3.0]
+    3: n_descendants: 3, a: 0, children: [0, 2, 0], v: [0.0, 0.0]
+    4: n_descendants: 3, a: 0, children: [0, 2, 0], v: [0.0, 0.0]
+    n_items: 3, roots: [4]
+    "###);
+}
+
+#[test]
+fn export_euclidean() {
+    let handle = create_database::<Euclidean>();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 3);
+    let mut rng = rng();
+    for i in 0..100 {
+        let vector: [f32; 3] = rng.gen();
+        writer.add_item(&mut wtxn, i, &vector).unwrap();
+    }
+    writer.builder(&mut rng).n_trees(3).build(&mut wtxn).unwrap();
+
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, handle.database).unwrap();
+    let bytes = export(&reader, &wtxn);
+    let file = AnnoyFile::new(Euclidean::METRIC, 3, &bytes).unwrap();
+    assert_eq!(file.n_items, 100);
+    assert_eq!(file.roots.len(), 3);
+    for &root in &file.roots {
+        assert_eq!(&tree_items(&file, root), reader.item_ids());
+    }
+    for item in reader.item_ids() {
+        let vector = reader.item_vector(&wtxn, item).unwrap().unwrap();
+        assert_eq!(file.vector(item as usize), vector);
+        assert_eq!(file.n_descendants(item as usize), 1);
+    }
+
+    // The copies of the roots are at the end of the file, in the same order
+    let n_nodes = file.n_nodes();
+    let metadata = handle.database.remap_data_type::<MetadataCodec>();
+    let roots: Vec<_> =
+        metadata.get(&wtxn, &Key::metadata(0)).unwrap().unwrap().roots.iter().collect();
+    for (i, &root) in roots.iter().enumerate() {
+        let annoy_root = n_nodes - roots.len() + i;
+        assert_same_tree(&file, annoy_root, &wtxn, handle.database, NodeId::tree(root));
+    }
+}
+
+/// Asserts that the Annoy node has the same split planes and items as the arroy one.
+fn assert_same_tree(
+    file: &AnnoyFile,
+    i: usize,
+    rtxn: &heed::RoTxn,
+    database: Database<Euclidean>,
+    node_id: NodeId,
+) {
+    if node_id.mode == NodeMode::Item {
+        assert_eq!(i, node_id.item as usize);
+        return;
+    }
+
+    match database.get(rtxn, &Key::new(0, node_id)).unwrap().unwrap() {
+        Node::Leaf(_) => panic!("a tree cannot contain a leaf"),
+        Node::Descendants(descendants) => {
+            let children: Vec<_> = file.children(i).map(|id| id as u32).collect();
+            assert_eq!(children, descendants.descendants.iter().collect::<Vec<_>>());
+        }
+        Node::SplitPlaneNormal(split) => {
+            assert!(file.n_descendants(i) > file.layout.max_descendants());
+            assert_eq!(file.vector(i), split.normal.to_vec());
+            assert_eq!(file.extra(i), 0.0);
+            let children: Vec<_> = file.children(i).collect();
+            assert_same_tree(file, children[0], rtxn, database, split.left);
+            assert_same_tree(file, children[1], rtxn, database, split.right);
+        }
+    }
+}
+
+#[test]
+fn export_missing_ids() {
+    let handle = create_database::<Cosine>();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 16);
+    for i in [0, 3, 10] {
+        writer.add_item(&mut wtxn, i, &[i as f32 + 1.0; 16]).unwrap();
+    }
+    writer.builder(&mut rng()).n_trees(2).build(&mut wtxn).unwrap();
+
+    let reader = Reader::<Cosine>::open(&wtxn, 0, handle.database).unwrap();
+    let bytes = export(&reader, &wtxn);
+    let file = AnnoyFile::new(Cosine::METRIC, 16, &bytes).unwrap();
+    assert_eq!(file.n_items, 11);
+    for &root in &file.roots {
+        assert_eq!(&tree_items(&file, root), reader.item_ids());
+    }
+    // The angular metric stores the squared norm of the items
+    assert_eq!(file.extra(3), 16.0 * 4.0 * 4.0);
+    assert_eq!(file.n_descendants(1), 0);
+    assert_eq!(file.vector(1), vec![0.0; 16]);
+}
+
+#[test]
+fn export_large_descendants() {
+    let handle = create_database::<DotProduct>();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 2);
+    for i in 0..200 {
+        writer.add_item(&mut wtxn, i, &[i as f32, (i % 7) as f32]).unwrap();
+    }
+    writer.builder(&mut rng()).n_trees(2).split_after(50).build(&mut wtxn).unwrap();
+
+    let reader = Reader::<DotProduct>::open(&wtxn, 0, handle.database).unwrap();
+    let bytes = export(&reader, &wtxn);
+    let file = AnnoyFile::new(DotProduct::METRIC, 2, &bytes).unwrap();
+    assert_eq!(file.n_items, 200);
+    assert_eq!(file.roots.len(), 2);
+    for &root in &file.roots {
+        assert_eq!(&tree_items(&file, root), reader.item_ids());
+    }
+    // The dot factor of the item with the largest norm is zero
+    assert_eq!(file.extra(199), 0.0);
+    assert!(file.extra(0) > 0.0);
+}
+
+#[test]
+fn export_projected_index() {
+    let handle = create_database::<Euclidean>();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 4);
+    for i in 0..10 {
+        writer.add_item(&mut wtxn, i, &[i as f32; 4]).unwrap();
+    }
+    writer.builder(&mut rng()).reduce_dimensions(2).build(&mut wtxn).unwrap();
+
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, handle.database).unwrap();
+    let error = reader.export_annoy(&wtxn, Vec::new()).unwrap_err();
+    insta::assert_snapshot!(error, @"The index 0 cannot be exported to Annoy: the trees are built on projected vectors");
+}
+
+#[test]
+fn import_exported_index() {
+    let handle = create_database::<Cosine>();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 4);
+    let mut rng = rng();
+    for i in 0..200 {
+        let vector: [f32; 4] = rng.gen();
+        writer.add_item(&mut wtxn, i, &vector).unwrap();
+    }
+    writer.builder(&mut rng).n_trees(4).build(&mut wtxn).unwrap();
+
+    let reader = Reader::<Cosine>::open(&wtxn, 0, handle.database).unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("index.ann");
+    std::fs::write(&path, export(&reader, &wtxn)).unwrap();
+    let stats = reader.stats(&wtxn).unwrap();
+    drop(reader);
+
+    let imported = Writer::<Cosine>::new(handle.database, 1, 4);
+    imported.import_annoy(&mut wtxn, &path).unwrap();
+
+    let reader = Reader::<Cosine>::open(&wtxn, 0, handle.database).unwrap();
+    let imported = Reader::<Cosine>::open(&wtxn, 1, handle.database).unwrap();
+    imported.assert_validity(&wtxn).unwrap();
+    assert!(imported.verify(&wtxn).unwrap().is_valid());
+    assert_eq!(imported.item_ids(), reader.item_ids());
+    for item in reader.item_ids() {
+        assert_eq!(
+            imported.item_vector(&wtxn, item).unwrap(),
+            reader.item_vector(&wtxn, item).unwrap()
+        );
+    }
+
+    // The trees have the same shape
+    let imported_stats = imported.stats(&wtxn).unwrap();
+    assert_eq!(format!("{:?}", imported_stats.tree_stats), format!("{:?}", stats.tree_stats));
+    assert_eq!(imported_stats.descendants_sizes, stats.descendants_sizes);
+    assert_eq!(imported_stats.leaf_depths, stats.leaf_depths);
+    assert_eq!(imported_stats.split_levels, stats.split_levels);
+    assert_eq!(imported_stats.bytes.leafs, stats.bytes.leafs);
+
+    for query in [[0.2, 0.3, 0.4, 0.1], [0.8, 0.1, 0.0, 0.5]] {
+        let nns = reader.nns(10).by_vector(&wtxn, &query).unwrap();
+        assert_eq!(imported.nns(10).by_vector(&wtxn, &query).unwrap(), nns);
+    }
+}
+
+#[test]
+fn import_small_index() {
+    let handle = create_database::<Cosine>();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 16);
+    for i in [0, 3, 10] {
+        writer.add_item(&mut wtxn, i, &[i as f32 + 1.0; 16]).unwrap();
+    }
+    writer.builder(&mut rng()).n_trees(2).build(&mut wtxn).unwrap();
+
+    let reader = Reader::<Cosine>::open(&wtxn, 0, handle.database).unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("index.ann");
+    std::fs::write(&path, export(&reader, &wtxn)).unwrap();
+    drop(reader);
+
+    // The index is replaced by the content of the file
+    writer.clear(&mut wtxn).unwrap();
+    writer.add_item(&mut wtxn, 42, &[1.0; 16]).unwrap();
+    writer.import_annoy(&mut wtxn, &path).unwrap();
+
+    let reader = Reader::<Cosine>::open(&wtxn, 0, handle.database).unwrap();
+    reader.assert_validity(&wtxn).unwrap();
+    insta::assert_debug_snapshot!(reader.item_ids(), @"RoaringBitmap<[0, 3, 10]>");
+    drop(reader);
+    wtxn.commit().unwrap();
+    insta::assert_snapshot!(handle, @r###"
+    ==================
+    Dumping index 0
+    Root: Metadata { dimensions: 16, items: RoaringBitmap<[0, 3, 10]>, roots: [0], distance: "cosine" }
+    Version: 1
+    Tree 0: Descendants(Descendants { descendants: [0, 3, 10] })
+    Item 0: Leaf(Leaf { header: NodeHeaderCosine { norm: 4.0 }, vector: [1.0000, 1.0000, 1.0000, 1.0000, 1.0000, 1.0000, 1.0000, 1.0000, 1.0000, 1.0000, "other ..."] })
+    Item 3: Leaf(Leaf { header: NodeHeaderCosine { norm: 16.0 }, vector: [4.0000, 4.0000, 4.0000, 4.0000, 4.0000, 4.0000, 4.0000, 4.0000, 4.0000, 4.0000, "other ..."] })
+    Item 10: Leaf(Leaf { header: NodeHeaderCosine { norm: 44.0 }, vector: [11.0000, 11.0000, 11.0000, 11.0000, 11.0000, 11.0000, 11.0000, 11.0000, 11.0000, 11.0000, "other ..."] })
+    "###);
+}
+
+#[test]
+fn import_annoy_file() {
+    // The file Annoy saves for the euclidean items 0 to 4 in one dimension, split in two
+    // buckets by a plane with an offset, followed by the copy of the root. The third ID
+    // of the second bucket overflows on the vector.
+    let mut nodes: Vec<(i32, f32, [u32; 2], f32)> =
+        (0..5).map(|i| (1, 0.0, [0, 0], i as f32)).collect();
+    nodes.extend([
+        (2, 0.0, [0, 1], 0.0),
+        (3, 0.0, [2, 3], f32::from_bits(4)),
+        (5, -1.5, [5, 6], 1.0),
+        (5, -1.5, [5, 6], 1.0),
+    ]);
+    let mut bytes = Vec::new();
+    for (n_descendants, a, children, v) in nodes {
+        bytes.extend(n_descendants.to_le_bytes());
+        bytes.extend(a.to_le_bytes());
+        bytes.extend(children.iter().flat_map(|c| c.to_le_bytes()));
+        bytes.extend(v.to_le_bytes());
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("index.ann");
+    std::fs::write(&path, &bytes).unwrap();
+
+    let handle = create_database::<Euclidean>();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::<Euclidean>::new(handle.database, 0, 1);
+    writer.import_annoy(&mut wtxn, &path).unwrap();
+
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, handle.database).unwrap();
+    reader.assert_validity(&wtxn).unwrap();
+    let nns = reader.nns(2).by_vector(&wtxn, &[3.2]).unwrap();
+    insta::assert_debug_snapshot!(nns, @r###"
+    [
+        (
+            3,
+            0.20000005,
+        ),
+        (
+            4,
+            0.79999995,
+        ),
+    ]
+    "###);
+    drop(reader);
+    wtxn.commit().unwrap();
+    insta::assert_snapshot!(handle, @r###"
+    ==================
+    Dumping index 0
+    Root: Metadata { dimensions: 1, items: RoaringBitmap<[0, 1, 2, 3, 4]>, roots: [0], distance: "euclidean" }
+    Version: 1
+    Tree 0: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(1), right: Tree(2), normal: [1.0000] })
+    Tree 1: Descendants(Descendants { descendants: [0, 1] })
+    Tree 2: Descendants(Descendants { descendants: [2, 3, 4] })
+    Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000] })
+    Item 1: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [1.0000] })
+    Item 2: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [2.0000] })
+    Item 3: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [3.0000] })
+    Item 4: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [4.0000] })
+    "###);
+
+    // The file doesn't have the size of nodes of two dimensions
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::<Euclidean>::new(handle.database, 1, 2);
+    let error = writer.import_annoy(&mut wtxn, &path).unwrap_err();
+    insta::assert_snapshot!(error, @"Cannot import the Annoy file in the index 1: the size of the file is not a multiple of the size of the nodes of these dimensions");
+}
@@ -0,0 +1,185 @@
+use std::num::NonZeroUsize;
+
+use rand::Rng;
+
+use super::*;
+use crate::distances::Euclidean;
+use crate::{Benchmark, Error, GroundTruth, Writer};
+
+#[test]
+fn ground_truth() {
+    let handle = create_database::<Euclidean>();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 2);
+    for i in 0..2000 {
+        writer.add_item(&mut wtxn, i, &[(i % 40) as f32, (i / 40) as f32]).unwrap();
+    }
+    writer.builder(&mut rng()).n_trees(1).build(&mut wtxn).unwrap();
+
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, handle.database).unwrap();
+    let queries = vec![vec![0.0, 0.0], vec![10.2, 20.1], vec![100.0, 100.0]];
+    let ground_truth = GroundTruth::compute(&wtxn, &reader, queries, 3, None).unwrap();
+    insta::assert_debug_snapshot!(ground_truth.neighbors(), @r###"
+    [
+        [
+            0,
+            1,
+            40,
+        ],
+        [
+            810,
+            811,
+            850,
+        ],
+        [
+            1999,
+            1959,
+            1998,
+        ],
+    ]
+    "###);
+    insta::assert_snapshot!(ground_truth.recall(1, &[811, 0, 850]), @"0.6666667");
+
+    let thread_pool = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+    let ground_truth =
+        GroundTruth::from_items(&wtxn, &reader, [41], 5, Some(&thread_pool)).unwrap();
+    insta::assert_debug_snapshot!(ground_truth.neighbors(), @r###"
+    [
+        [
+            41,
+            1,
+            40,
+            42,
+            81,
+        ],
+    ]
+    "###);
+
+    let error = GroundTruth::from_items(&wtxn, &reader, [41, 2000], 5, None).unwrap_err();
+    insta::assert_snapshot!(error, @"Invalid ground truth: the item 2000 doesn't exist");
+    let error = GroundTruth::compute(&wtxn, &reader, vec![vec![0.0]], 5, None).unwrap_err();
+    insta::assert_snapshot!(error, @"Invalid vector dimensions. Got 1 but expected 2");
+    let error = GroundTruth::new(vec![vec![0.0, 0.0]], Vec::new(), 5).unwrap_err();
+    assert!(matches!(error, Error::InvalidGroundTruth { .. }));
+}
+
+#[test]
+fn benchmark_grid() {
+    let handle = create_database::<Euclidean>();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 2);
+    // Random points to avoid the ties between the neighbors
+    let mut points = rng();
+    for i in 0..500 {
+        writer.add_item(&mut wtxn, i, &[points.gen(), points.gen()]).unwrap();
+    }
+    writer.builder(&mut rng()).n_trees(1).build(&mut wtxn).unwrap();
+
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, handle.database).unwrap();
+    let ground_truth =
+        GroundTruth::from_items(&wtxn, &reader, (0..500).step_by(7), 10, None).unwrap();
+    drop(reader);
+
+    let mut benchmark = Benchmark::new(&ground_truth);
+    benchmark
+        .n_trees([1, 5])
+        .search_k([NonZeroUsize::new(10), NonZeroUsize::new(2500)])
+        .oversampling([None, NonZeroUsize::new(4)]);
+    let results = benchmark.run_with_builds(&mut wtxn, &writer, &mut rng()).unwrap();
+
+    let params: Vec<_> = results
+        .iter()
+        .map(|r| (r.n_trees, r.search_k.map(NonZeroUsize::get), r.oversampling, r.count))
+        .collect();
+    insta::assert_debug_snapshot!(params, @r###"
+    [
+        (
+            1,
+            Some(
+                10,
+            ),
+            None,
+            10,
+        ),
+        (
+            1,
+            Some(
+                10,
+            ),
+            Some(
+                4,
+            ),
+            10,
+        ),
+        (
+            1,
+            Some(
+                2500,
+            ),
+            None,
+            10,
+        ),
+        (
+            1,
+            Some(
+                2500,
+            ),
+            Some(
+                4,
+            ),
+            10,
+        ),
+        (
+            5,
+            Some(
+                10,
+            ),
+            None,
+            10,
+        ),
+        (
+            5,
+            Some(
+                10,
+            ),
+            Some(
+                4,
+            ),
+            10,
+        ),
+        (
+            5,
+            Some(
+                2500,
+            ),
+            None,
+            10,
+        ),
+        (
+            5,
+            Some(
+                2500,
+            ),
+            Some(
+                4,
+            ),
+            10,
+        ),
+    ]
+    "###);
+    // Exploring all the items of all the trees always finds the exact neighbors
+    for result in &results {
+        if result.search_k == NonZeroUsize::new(2500) {
+            assert_eq!(result.recall, 1.0, "{result}");
+        }
+        assert!(result.recall > 0.0 && result.recall <= 1.0, "{result}");
+        assert!(result.p50 <= result.p99, "{result}");
+    }
+
+    // The index was built with the last number of trees
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, handle.database).unwrap();
+    assert_eq!(reader.n_trees(), 5);
+    let results = Benchmark::new(&ground_truth).run(&wtxn, &reader).unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].search_k, None);
+}
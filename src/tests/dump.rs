@@ -0,0 +1,103 @@
+use heed::types::Bytes;
+use heed::RoTxn;
+use rand::Rng;
+
+use super::*;
+use crate::distances::{Cosine, Euclidean};
+use crate::{Key, NormalPrecision, Prefix, PrefixCodec, Writer};
+
+/// Returns the node IDs and the raw values of an index.
+fn values<D: Distance>(rtxn: &RoTxn, database: Database<D>, index: u16) -> Vec<(String, Vec<u8>)> {
+    database
+        .remap_types::<PrefixCodec, Bytes>()
+        .prefix_iter(rtxn, &Prefix::all(index))
+        .unwrap()
+        .remap_key_type::<crate::internals::KeyCodec>()
+        .map(|result| {
+            let (key, value): (Key, &[u8]) = result.unwrap();
+            (format!("{:?}", key.node), value.to_vec())
+        })
+        .collect()
+}
+
+#[test]
+fn dump_and_restore() {
+    let handle = create_database::<Euclidean>();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 8);
+    let mut rng = rng();
+    for i in 0..300 {
+        let vector: [f32; 8] = rng.gen();
+        writer.add_item(&mut wtxn, i, &vector).unwrap();
+    }
+    writer
+        .builder(&mut rng)
+        .n_trees(3)
+        .reduce_dimensions(4)
+        .normal_precision(NormalPrecision::F16)
+        .build(&mut wtxn)
+        .unwrap();
+    // The updated items are dumped too
+    writer.add_item(&mut wtxn, 300, &[0.5; 8]).unwrap();
+    writer.del_item(&mut wtxn, 12).unwrap();
+
+    let mut dump = Vec::new();
+    writer.dump(&wtxn, &mut dump).unwrap();
+
+    // The index is restored under another number and replaces its content
+    let other = Writer::new(handle.database, 3, 8);
+    other.add_item(&mut wtxn, 1000, &[1.0; 8]).unwrap();
+    other.restore(&mut wtxn, &dump[..]).unwrap();
+    assert_eq!(values(&wtxn, handle.database, 3), values(&wtxn, handle.database, 0));
+    wtxn.commit().unwrap();
+
+    // And in another environment
+    let other_handle = create_database::<Euclidean>();
+    let mut wtxn = other_handle.env.write_txn().unwrap();
+    let writer = Writer::new(other_handle.database, 0, 8);
+    writer.restore(&mut wtxn, &dump[..]).unwrap();
+    writer.builder(&mut rng).build(&mut wtxn).unwrap();
+
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, other_handle.database).unwrap();
+    assert!(reader.verify(&wtxn).unwrap().is_valid());
+    insta::assert_debug_snapshot!(reader.item_ids().len(), @"300");
+    assert!(reader.item_ids().contains(300) && !reader.item_ids().contains(12));
+}
+
+#[test]
+fn restore_invalid_dumps() {
+    let handle = create_database::<Euclidean>();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 2);
+    writer.add_item(&mut wtxn, 0, &[0.0, 1.0]).unwrap();
+    writer.add_item(&mut wtxn, 1, &[1.0, 1.0]).unwrap();
+    writer.builder(&mut rng()).build(&mut wtxn).unwrap();
+    let mut dump = Vec::new();
+    writer.dump(&wtxn, &mut dump).unwrap();
+
+    let error = writer.restore(&mut wtxn, &b"not a dump at all"[..]).unwrap_err();
+    insta::assert_snapshot!(error, @"Invalid dump: not an arroy dump");
+
+    let error = Writer::new(handle.database, 1, 3).restore(&mut wtxn, &dump[..]).unwrap_err();
+    insta::assert_snapshot!(error, @"Invalid vector dimensions. Got 2 but expected 3");
+
+    let cosine = Writer::<Cosine>::new(handle.database.remap_data_type(), 1, 2);
+    let error = cosine.restore(&mut wtxn, &dump[..]).unwrap_err();
+    insta::assert_snapshot!(error, @"Invalid distance provided. Got cosine but expected euclidean");
+
+    let error = writer.restore(&mut wtxn, &dump[..dump.len() - 10]).unwrap_err();
+    insta::assert_snapshot!(error, @"failed to fill whole buffer");
+
+    // A vector of the first item is modified
+    let mut corrupted = dump.clone();
+    let position = corrupted.windows(4).position(|w| w == 1.0f32.to_le_bytes()).unwrap();
+    corrupted[position] ^= 1;
+    let error = writer.restore(&mut wtxn, &corrupted[..]).unwrap_err();
+    insta::assert_snapshot!(error, @"Invalid dump: the checksum doesn't match, the dump is corrupted");
+
+    // The version of the dump is too recent
+    let mut newer = dump.clone();
+    newer[8] = 2;
+    let error = writer.restore(&mut wtxn, &newer[..]).unwrap_err();
+    insta::assert_snapshot!(error, @"Invalid dump: the dump is in the version 2 but only the versions up to 1 are supported");
+}
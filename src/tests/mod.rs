@@ -7,9 +7,15 @@ use rand::SeedableRng;
 use tempfile::TempDir;
 
 use crate::roaring::RoaringBitmapCodec;
-use crate::{Database, Distance, MetadataCodec, NodeCodec, NodeMode, Reader};
+use crate::{
+    BuildConfig, BuildConfigCodec, Database, Distance, MetadataCodec, NodeCodec, NodeMode,
+    PendingBuildCodec, ProjectionCodec, Reader, SearchTuningCodec, VersionCodec,
+};
 
+mod annoy;
+mod benchmark;
 mod binary_quantized;
+mod dump;
 mod reader;
 mod writer;
 
@@ -77,6 +83,59 @@ impl<D: Distance> fmt::Display for DatabaseHandle<D> {
                         .unwrap();
                     writeln!(f, "updated_item_ids: {updated_item_ids:?}")?;
                 }
+                NodeMode::Metadata if key.node.item == 2 => {
+                    let pending = self
+                        .database
+                        .remap_data_type::<PendingBuildCodec>()
+                        .get(&rtxn, &key)
+                        .unwrap()
+                        .unwrap();
+                    writeln!(f, "Pending: {:?}", pending.roots)?;
+                    if let Some(projection) = pending.new_projection {
+                        writeln!(f, "Pending projection: {projection:?}")?;
+                    }
+                }
+                NodeMode::Metadata if key.node.item == 3 => {
+                    let config = self
+                        .database
+                        .remap_data_type::<BuildConfigCodec>()
+                        .get(&rtxn, &key)
+                        .unwrap()
+                        .unwrap();
+                    // Almost every test sets the number of trees, the other options
+                    // are only shown by the tests that use them
+                    if config != (BuildConfig { n_trees: config.n_trees, ..BuildConfig::default() })
+                    {
+                        writeln!(f, "Build config: {config:?}")?;
+                    }
+                }
+                NodeMode::Metadata if key.node.item == 4 => {
+                    let projection = self
+                        .database
+                        .remap_data_type::<ProjectionCodec>()
+                        .get(&rtxn, &key)
+                        .unwrap()
+                        .unwrap();
+                    writeln!(f, "{projection:?}")?;
+                }
+                NodeMode::Metadata if key.node.item == 5 => {
+                    let version = self
+                        .database
+                        .remap_data_type::<VersionCodec>()
+                        .get(&rtxn, &key)
+                        .unwrap()
+                        .unwrap();
+                    writeln!(f, "Version: {version}")?;
+                }
+                NodeMode::Metadata if key.node.item == 6 => {
+                    let tuning = self
+                        .database
+                        .remap_data_type::<SearchTuningCodec>()
+                        .get(&rtxn, &key)
+                        .unwrap()
+                        .unwrap();
+                    writeln!(f, "{tuning:?}")?;
+                }
                 NodeMode::Updated | NodeMode::Metadata => panic!(),
             }
         }
@@ -1,12 +1,16 @@
 use std::fmt::Display;
 use std::num::NonZeroUsize;
 
+use heed::types::Bytes;
+use rand::Rng;
 use roaring::RoaringBitmap;
 
 use super::*;
 use crate::distance::Cosine;
 use crate::distances::{Euclidean, Manhattan};
-use crate::{ItemId, Reader, Writer};
+use crate::{
+    ItemId, Key, MetadataCodec, NormalPrecision, Reader, VersionCodec, Writer, FORMAT_VERSION,
+};
 
 pub struct NnsRes(pub Option<Vec<(ItemId, f32)>>);
 
@@ -74,6 +78,49 @@ fn open_db_with_wrong_distance() {
     "###);
 }
 
+#[test]
+fn open_corrupted_db() {
+    let handle = create_database::<Euclidean>();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 2);
+    for i in 0..10 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 0.0]).unwrap();
+    }
+    writer.builder(&mut rng()).n_trees(1).build(&mut wtxn).unwrap();
+
+    // A node written by a newer version of arroy
+    let metadata = handle.database.remap_data_type::<MetadataCodec>();
+    let root =
+        metadata.get(&wtxn, &Key::metadata(0)).unwrap().unwrap().roots.iter().next().unwrap();
+    let database = handle.database.remap_data_type::<Bytes>();
+    database.put(&mut wtxn, &Key::tree(0, root), &[42, 0, 0]).unwrap();
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, handle.database).unwrap();
+    let ret = reader.nns(5).by_vector(&wtxn, &[1.0, 0.0]).unwrap_err();
+    insta::assert_snapshot!(ret, @"Corrupted database: Tree(8) of index `0` cannot be decoded: unknown node tag 42");
+
+    database.put(&mut wtxn, &Key::metadata(0), b"euclidean\0\0\0").unwrap();
+    let ret = Reader::<Euclidean>::open(&wtxn, 0, handle.database).map(|_| ()).unwrap_err();
+    insta::assert_snapshot!(ret, @"Corrupted database: Metadata(0) of index `0` cannot be decoded: dimensions: expected 4 bytes but only 2 remain");
+}
+
+#[test]
+fn open_db_with_a_newer_version() {
+    let handle = create_database::<Euclidean>();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 2);
+    writer.add_item(&mut wtxn, 0, &[0.0, 0.0]).unwrap();
+    writer.builder(&mut rng()).build(&mut wtxn).unwrap();
+
+    // The metadata of a newer format cannot be decoded
+    let database = handle.database.remap_data_type::<Bytes>();
+    database.put(&mut wtxn, &Key::metadata(0), &[0, 1, 2]).unwrap();
+    let versions = handle.database.remap_data_type::<VersionCodec>();
+    versions.put(&mut wtxn, &Key::version(0), &(FORMAT_VERSION + 1)).unwrap();
+
+    let ret = Reader::<Euclidean>::open(&wtxn, 0, handle.database).map(|_| ()).unwrap_err();
+    insta::assert_snapshot!(ret, @"The index 0 is written in the format version 2 but only the versions up to 1 are supported");
+}
+
 #[test]
 fn search_in_db_with_a_single_vector() {
     // https://github.com/meilisearch/meilisearch/pull/4296
@@ -139,6 +186,90 @@ fn two_dimension_on_a_line() {
     "###);
 }
 
+#[test]
+fn search_with_split_on_the_first_dimensions() {
+    let handle = create_database();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 4);
+    // The first dimensions carry most of the meaning, the last ones only refine it
+    for i in 0..100 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 0.0, (i % 3) as f32, (i % 5) as f32]).unwrap();
+    }
+
+    writer.builder(&mut rng()).n_trees(10).split_dimensions(2).build(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    let rtxn = handle.env.read_txn().unwrap();
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, handle.database).unwrap();
+    assert_eq!(reader.build_config().split_dimensions, Some(2));
+
+    // The candidates are ranked with all the dimensions
+    let ret = reader.nns(5).by_vector(&rtxn, &[50.0, 0.0, 0.0, 0.0]).unwrap();
+    insta::assert_snapshot!(NnsRes(Some(ret)), @r###"
+    id(50): distance(2)
+    id(49): distance(4.2426405)
+    id(53): distance(4.690416)
+    id(55): distance(5.0990195)
+    id(54): distance(5.656854)
+    "###);
+}
+
+#[test]
+fn search_with_reduced_dimensions() {
+    let handle = create_database();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 8);
+    for i in 0..100 {
+        let x = i as f32;
+        writer.add_item(&mut wtxn, i, &[x, x / 2.0, 0.0, (i % 3) as f32, x, 1.0, -x, 0.0]).unwrap();
+    }
+
+    writer.builder(&mut rng()).n_trees(10).reduce_dimensions(3).build(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    let rtxn = handle.env.read_txn().unwrap();
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, handle.database).unwrap();
+    assert_eq!(reader.build_config().reduced_dimensions, Some(3));
+
+    // The candidates are ranked with the original vectors
+    let ret = reader.nns(5).search_k(NonZeroUsize::new(100).unwrap()).by_item(&rtxn, 50).unwrap();
+    insta::assert_snapshot!(NnsRes(ret), @r###"
+    id(50): distance(0)
+    id(49): distance(2.0615528)
+    id(51): distance(2.6925824)
+    id(52): distance(3.7416575)
+    id(48): distance(4.1231055)
+    "###);
+}
+
+#[test]
+fn search_with_compact_normals() {
+    let handle = create_database();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    for (index, precision) in
+        [NormalPrecision::Full, NormalPrecision::F16, NormalPrecision::Int8].into_iter().enumerate()
+    {
+        let writer = Writer::new(handle.database, index as u16, 4);
+        for i in 0..200 {
+            let x = i as f32;
+            writer.add_item(&mut wtxn, i, &[x.sin(), x.cos(), (x / 7.0).sin(), x / 200.0]).unwrap();
+        }
+        writer.builder(&mut rng()).n_trees(5).normal_precision(precision).build(&mut wtxn).unwrap();
+    }
+    wtxn.commit().unwrap();
+
+    // The trees are the same, only the normals are a bit less precise
+    let rtxn = handle.env.read_txn().unwrap();
+    let results: Vec<_> = (0..3)
+        .map(|index| {
+            let reader = Reader::<Euclidean>::open(&rtxn, index, handle.database).unwrap();
+            reader.nns(10).by_item(&rtxn, 42).unwrap().unwrap()
+        })
+        .collect();
+    assert_eq!(results[0], results[1]);
+    assert_eq!(results[0], results[2]);
+}
+
 #[test]
 fn two_dimension_on_a_column() {
     let handle = create_database();
@@ -187,6 +318,36 @@ fn get_item_ids() {
     insta::assert_debug_snapshot!(ret, @"RoaringBitmap<[0, 1, 2, 3, 4, 5, 6, 7, 8, 9]>");
 }
 
+#[test]
+fn stats_measure_the_compact_descendants() {
+    let handle = create_database();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 2);
+    for i in 0..100 {
+        writer.add_item(&mut wtxn, i, &[i as f32, (i * 7 % 13) as f32]).unwrap();
+    }
+
+    writer.builder(&mut rng()).n_trees(1).build(&mut wtxn).unwrap();
+
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, handle.database).unwrap();
+    let stats = reader.stats(&wtxn).unwrap();
+    let tree = stats.tree_stats[0];
+
+    assert!(tree.descendants > 0);
+    assert!(tree.descendants_bytes < tree.descendants_roaring_bytes);
+    insta::assert_debug_snapshot!(tree, @r###"
+    TreeStats {
+        depth: 11,
+        dummy_normals: 67,
+        split_nodes: 68,
+        descendants: 41,
+        descendants_bytes: 72,
+        descendants_roaring_bytes: 760,
+        items: 100,
+    }
+    "###);
+}
+
 #[test]
 fn filtering() {
     let handle = create_database();
@@ -275,3 +436,264 @@ fn try_reading_in_a_non_built_database() {
     )
     "###);
 }
+
+#[test]
+fn list_indexes() {
+    let handle = create_database::<Euclidean>();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 2);
+    for i in 0..5 {
+        writer.add_item(&mut wtxn, i, &[0.0, i as f32]).unwrap();
+    }
+    writer.builder(&mut rng()).n_trees(2).build(&mut wtxn).unwrap();
+    let writer = Writer::new(handle.database, 3, 2);
+    writer.add_item(&mut wtxn, 0, &[1.0, 1.0]).unwrap();
+    let writer = Writer::new(handle.database, u16::MAX, 2);
+    writer.add_item(&mut wtxn, 0, &[1.0, 1.0]).unwrap();
+    writer.builder(&mut rng()).build(&mut wtxn).unwrap();
+    let writer = Writer::new(handle.database, 0, 2);
+    writer.del_item(&mut wtxn, 4).unwrap();
+
+    let indexes = crate::list_indexes(&wtxn, handle.database).unwrap();
+    insta::assert_debug_snapshot!(indexes, @r###"
+    [
+        IndexInfo {
+            index: 0,
+            format_version: 1,
+            distance: Some(
+                "euclidean",
+            ),
+            dimensions: Some(
+                2,
+            ),
+            n_items: Some(
+                5,
+            ),
+            n_trees: Some(
+                2,
+            ),
+            needs_build: true,
+        },
+        IndexInfo {
+            index: 3,
+            format_version: 0,
+            distance: None,
+            dimensions: None,
+            n_items: None,
+            n_trees: None,
+            needs_build: true,
+        },
+        IndexInfo {
+            index: 65535,
+            format_version: 1,
+            distance: Some(
+                "euclidean",
+            ),
+            dimensions: Some(
+                2,
+            ),
+            n_items: Some(
+                1,
+            ),
+            n_trees: Some(
+                1,
+            ),
+            needs_build: false,
+        },
+    ]
+    "###);
+}
+
+#[test]
+fn tune_search_k() {
+    let handle = create_database::<Euclidean>();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 2);
+    let mut points = rng();
+    for i in 0..500 {
+        writer.add_item(&mut wtxn, i, &[points.gen(), points.gen()]).unwrap();
+    }
+    writer.builder(&mut rng()).n_trees(5).build(&mut wtxn).unwrap();
+
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, handle.database).unwrap();
+    let tuning = reader.tune_search_k(&wtxn, 10, 0.9, &mut rng()).unwrap();
+    insta::assert_debug_snapshot!(tuning, @r###"
+    SearchTuning {
+        count: 10,
+        n_trees: 5,
+        search_k: 268,
+        target_recall: 0.9,
+        recall: 0.9060003,
+    }
+    "###);
+    assert!(tuning.recall >= 0.9);
+    let unreachable = reader.tune_search_k(&wtxn, 10, 1.5, &mut rng()).unwrap();
+    insta::assert_debug_snapshot!(unreachable, @r###"
+    SearchTuning {
+        count: 10,
+        n_trees: 5,
+        search_k: 2500,
+        target_recall: 1.5,
+        recall: 1.0,
+    }
+    "###);
+    drop(reader);
+
+    writer.set_search_tuning(&mut wtxn, Some(tuning)).unwrap();
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, handle.database).unwrap();
+    assert_eq!(reader.search_tuning(), Some(tuning));
+    // The queries without a search_k explore the tuned number of nodes, scaled to their count
+    for count in [3, 10] {
+        let tuned = reader.nns(count).by_item(&wtxn, 0).unwrap();
+        let search_k = NonZeroUsize::new((tuning.search_k.get() * count).div_ceil(10)).unwrap();
+        let explicit = reader
+            .nns(count)
+            .search_k(search_k)
+            .oversampling(NonZeroUsize::MIN)
+            .by_item(&wtxn, 0)
+            .unwrap();
+        assert_eq!(tuned, explicit);
+    }
+    drop(reader);
+
+    // The tuning is ignored once the trees change
+    writer.builder(&mut rng()).n_trees(1).build(&mut wtxn).unwrap();
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, handle.database).unwrap();
+    assert_eq!(reader.search_tuning(), Some(tuning));
+    let default = reader.nns(10).by_item(&wtxn, 0).unwrap();
+    let explicit =
+        reader.nns(10).search_k(NonZeroUsize::new(10).unwrap()).by_item(&wtxn, 0).unwrap();
+    assert_eq!(default, explicit);
+    drop(reader);
+
+    writer.set_search_tuning(&mut wtxn, None).unwrap();
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, handle.database).unwrap();
+    assert_eq!(reader.search_tuning(), None);
+    drop(reader);
+
+    // The tuning is deleted with the trees it was measured on
+    writer.set_search_tuning(&mut wtxn, Some(tuning)).unwrap();
+    let writer = writer.prepare_changing_distance::<Manhattan>(&mut wtxn).unwrap();
+    writer.builder(&mut rng()).n_trees(5).build(&mut wtxn).unwrap();
+    let reader = Reader::<Manhattan>::open(&wtxn, 0, handle.database.remap_data_type()).unwrap();
+    assert_eq!(reader.search_tuning(), None);
+}
+
+#[test]
+fn stats_measure_the_sizes_and_the_shape_of_the_trees() {
+    let handle = create_database::<Euclidean>();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 2);
+    for i in 0..100 {
+        writer.add_item(&mut wtxn, i, &[i as f32, (i * 7 % 13) as f32]).unwrap();
+    }
+    writer.builder(&mut rng()).n_trees(2).build(&mut wtxn).unwrap();
+    // Another index must not be measured
+    let other = Writer::new(handle.database, 1, 2);
+    other.add_item(&mut wtxn, 0, &[0.0, 0.0]).unwrap();
+    other.builder(&mut rng()).build(&mut wtxn).unwrap();
+
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, handle.database).unwrap();
+    let stats = reader.stats(&wtxn).unwrap();
+    insta::assert_debug_snapshot!(stats.bytes, @r###"
+    IndexBytes {
+        leafs: 2100,
+        split_normals: 3537,
+        descendants: 937,
+        metadata: 279,
+    }
+    "###);
+    insta::assert_debug_snapshot!(stats.descendants_sizes, @r###"
+    {
+        0: 10,
+        2: 77,
+    }
+    "###);
+    insta::assert_debug_snapshot!(stats.leaf_depths, @r###"
+    {
+        2: 1,
+        3: 1,
+        6: 15,
+        7: 42,
+        8: 77,
+        9: 47,
+        10: 14,
+        11: 3,
+    }
+    "###);
+    insta::assert_debug_snapshot!(&stats.split_levels[..3], @r###"
+    [
+        SplitLevelStats {
+            split_nodes: 2,
+            mean_imbalance: 0.49,
+            max_imbalance: 0.98,
+        },
+        SplitLevelStats {
+            split_nodes: 3,
+            mean_imbalance: 0.37023568,
+            max_imbalance: 0.96,
+        },
+        SplitLevelStats {
+            split_nodes: 5,
+            mean_imbalance: 0.14569604,
+            max_imbalance: 0.4074074,
+        },
+    ]
+    "###);
+
+    // Every tree leads to all the items
+    for tree in &stats.tree_stats {
+        assert_eq!(tree.items, 100);
+    }
+    assert_eq!(stats.leaf_depths.values().sum::<u64>(), 2 * 100);
+    let n_descendants = stats.tree_stats.iter().map(|t| t.descendants).sum::<usize>();
+    assert_eq!(stats.descendants_sizes.values().sum::<usize>(), n_descendants);
+    let split_nodes = stats.tree_stats.iter().map(|t| t.split_nodes).sum::<usize>();
+    assert_eq!(stats.split_levels.iter().map(|l| l.split_nodes).sum::<usize>(), split_nodes);
+    assert_eq!(stats.split_levels[0].split_nodes, 2);
+    // Each item is a key of 8 bytes, a tag, a header of 4 bytes and a vector of 2 f32
+    assert_eq!(stats.bytes.leafs, 100 * (8 + 1 + 4 + 2 * 4));
+}
+
+#[cfg(feature = "plot")]
+#[test]
+fn plot_tree_json() {
+    use crate::TreePlotOptions;
+
+    let handle = create_database::<Euclidean>();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 2);
+    for i in 0..12 {
+        writer.add_item(&mut wtxn, i, &[i as f32, (i * 7 % 5) as f32]).unwrap();
+    }
+    writer.builder(&mut rng()).n_trees(1).split_after(2).build(&mut wtxn).unwrap();
+
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, handle.database).unwrap();
+    let plot = |options: &TreePlotOptions, nth: usize| {
+        let mut json = Vec::new();
+        reader
+            .plot_tree_json(&wtxn, nth, options, &mut json)
+            .map(|()| String::from_utf8(json).unwrap())
+    };
+
+    let json = plot(&TreePlotOptions::default(), 0).unwrap();
+    insta::assert_snapshot!(json, @r###"
+    {"index":0,"tree":0,"dimensions":2,"root":{"kind":"split","id":14,"depth":0,"dummy":false,"left":{"kind":"item","id":0,"depth":1,"items":1},"right":{"kind":"split","id":13,"depth":1,"dummy":true,"left":{"kind":"split","id":2,"depth":2,"dummy":true,"left":{"kind":"descendants","id":0,"depth":3,"items":2},"right":{"kind":"descendants","id":1,"depth":3,"items":2},"items":4},"right":{"kind":"split","id":12,"depth":2,"dummy":true,"left":{"kind":"item","id":1,"depth":3,"items":1},"right":{"kind":"split","id":11,"depth":3,"dummy":true,"left":{"kind":"split","id":4,"depth":4,"dummy":false,"left":{"kind":"descendants","id":3,"depth":5,"items":2},"right":{"kind":"item","id":5,"depth":5,"items":1},"items":3},"right":{"kind":"split","id":10,"depth":4,"dummy":true,"left":{"kind":"split","id":8,"depth":5,"dummy":true,"left":{"kind":"descendants","id":5,"depth":6,"items":0},"right":{"kind":"split","id":7,"depth":6,"dummy":true,"left":{"kind":"item","id":11,"depth":7,"items":1},"right":{"kind":"descendants","id":6,"depth":7,"items":2},"items":3},"items":3},"right":{"kind":"descendants","id":9,"depth":5,"items":0},"items":3},"items":6},"items":7},"items":11},"items":12}}
+    "###);
+
+    let axes = [vec![1.0, 0.0], vec![0.0, 1.0]];
+    let options = TreePlotOptions { max_depth: Some(1), items: true, axes: Some(axes) };
+    let json = plot(&options, 0).unwrap();
+    insta::assert_snapshot!(json, @r###"
+    {"index":0,"tree":0,"dimensions":2,"root":{"kind":"split","id":14,"depth":0,"dummy":false,"normal":[0.9970566,0.07666904],"left":{"kind":"item","id":0,"depth":1,"items":1,"points":[[0,0]]},"right":{"kind":"split","id":13,"depth":1,"dummy":true,"normal":[0,0],"left":{"kind":"truncated","id":2,"depth":2,"items":4},"right":{"kind":"truncated","id":12,"depth":2,"items":7},"items":11},"items":12}}
+    "###);
+
+    let json = plot(&TreePlotOptions::default(), 1).unwrap();
+    insta::assert_snapshot!(json, @r###"
+    {"index":0,"tree":1,"dimensions":2,"root":null}
+    "###);
+
+    let options = TreePlotOptions { axes: Some([vec![1.0], vec![0.0]]), ..Default::default() };
+    let error = plot(&options, 0).unwrap_err();
+    insta::assert_snapshot!(error, @"Invalid vector dimensions. Got 1 but expected 2");
+}
@@ -1,13 +1,25 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::num::NonZeroUsize;
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
 
+use heed::types::Bytes;
 use heed::EnvOpenOptions;
 use insta::assert_snapshot;
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use roaring::RoaringBitmap;
 
 use super::{create_database, rng};
 use crate::distance::{BinaryQuantizedCosine, Cosine, DotProduct, Euclidean};
-use crate::{Database, Reader, Writer};
+use crate::node::Descendants;
+use crate::roaring::RoaringBitmapCodec;
+use crate::{
+    BuildConfig, Database, DimensionChange, Key, MetadataCodec, Node, NormalPrecision, Reader,
+    VectorFormat, VectorReader, VersionCodec, Writer, FORMAT_VERSION,
+};
 
 #[test]
 fn clear_small_database() {
@@ -53,6 +65,7 @@ fn use_u32_max_minus_one_for_a_vec() {
     ==================
     Dumping index 0
     Root: Metadata { dimensions: 3, items: RoaringBitmap<[4294967294]>, roots: [0], distance: "euclidean" }
+    Version: 1
     Tree 0: Descendants(Descendants { descendants: [4294967294] })
     Item 4294967294: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 1.0000, 2.0000] })
     "###);
@@ -72,6 +85,7 @@ fn use_u32_max_for_a_vec() {
     ==================
     Dumping index 0
     Root: Metadata { dimensions: 3, items: RoaringBitmap<[4294967295]>, roots: [0], distance: "euclidean" }
+    Version: 1
     Tree 0: Descendants(Descendants { descendants: [4294967295] })
     Item 4294967295: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 1.0000, 2.0000] })
     "###);
@@ -91,6 +105,7 @@ fn write_one_vector() {
     ==================
     Dumping index 0
     Root: Metadata { dimensions: 3, items: RoaringBitmap<[0]>, roots: [0], distance: "euclidean" }
+    Version: 1
     Tree 0: Descendants(Descendants { descendants: [0] })
     Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 1.0000, 2.0000] })
     "###);
@@ -110,6 +125,7 @@ fn write_one_vector_in_one_tree() {
     ==================
     Dumping index 0
     Root: Metadata { dimensions: 3, items: RoaringBitmap<[0]>, roots: [0], distance: "euclidean" }
+    Version: 1
     Tree 0: Descendants(Descendants { descendants: [0] })
     Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 1.0000, 2.0000] })
     "###);
@@ -129,6 +145,7 @@ fn write_one_vector_in_multiple_trees() {
     ==================
     Dumping index 0
     Root: Metadata { dimensions: 3, items: RoaringBitmap<[0]>, roots: [0], distance: "euclidean" }
+    Version: 1
     Tree 0: Descendants(Descendants { descendants: [0] })
     Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 1.0000, 2.0000] })
     "###);
@@ -152,6 +169,7 @@ fn write_vectors_until_there_is_a_descendants() {
     ==================
     Dumping index 0
     Root: Metadata { dimensions: 3, items: RoaringBitmap<[0, 1, 2]>, roots: [0], distance: "euclidean" }
+    Version: 1
     Tree 0: Descendants(Descendants { descendants: [0, 1, 2] })
     Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 0.0000, 0.0000] })
     Item 1: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [1.0000, 1.0000, 1.0000] })
@@ -177,6 +195,7 @@ fn write_vectors_until_there_is_a_split() {
     ==================
     Dumping index 0
     Root: Metadata { dimensions: 3, items: RoaringBitmap<[0, 1, 2, 3]>, roots: [1], distance: "euclidean" }
+    Version: 1
     Tree 0: Descendants(Descendants { descendants: [1, 2, 3] })
     Tree 1: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(0), right: Item(0), normal: [-0.5774, -0.5774, -0.5774] })
     Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 0.0000, 0.0000] })
@@ -229,26 +248,31 @@ fn write_multiple_indexes() {
     ==================
     Dumping index 0
     Root: Metadata { dimensions: 3, items: RoaringBitmap<[0]>, roots: [0], distance: "euclidean" }
+    Version: 1
     Tree 0: Descendants(Descendants { descendants: [0] })
     Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 1.0000, 2.0000] })
     ==================
     Dumping index 1
     Root: Metadata { dimensions: 3, items: RoaringBitmap<[0]>, roots: [0], distance: "euclidean" }
+    Version: 1
     Tree 0: Descendants(Descendants { descendants: [0] })
     Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 1.0000, 2.0000] })
     ==================
     Dumping index 2
     Root: Metadata { dimensions: 3, items: RoaringBitmap<[0]>, roots: [0], distance: "euclidean" }
+    Version: 1
     Tree 0: Descendants(Descendants { descendants: [0] })
     Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 1.0000, 2.0000] })
     ==================
     Dumping index 3
     Root: Metadata { dimensions: 3, items: RoaringBitmap<[0]>, roots: [0], distance: "euclidean" }
+    Version: 1
     Tree 0: Descendants(Descendants { descendants: [0] })
     Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 1.0000, 2.0000] })
     ==================
     Dumping index 4
     Root: Metadata { dimensions: 3, items: RoaringBitmap<[0]>, roots: [0], distance: "euclidean" }
+    Version: 1
     Tree 0: Descendants(Descendants { descendants: [0] })
     Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 1.0000, 2.0000] })
     "###);
@@ -293,6 +317,7 @@ fn overwrite_one_item_incremental() {
     ==================
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[0, 1, 2, 3, 4, 5]>, roots: [4], distance: "euclidean" }
+    Version: 1
     Tree 0: Descendants(Descendants { descendants: [1, 3] })
     Tree 1: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(0), right: Item(2), normal: [0.0000, 0.0000] })
     Tree 2: Descendants(Descendants { descendants: [4, 5] })
@@ -319,6 +344,7 @@ fn overwrite_one_item_incremental() {
     ==================
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[0, 1, 2, 3, 4, 5]>, roots: [4], distance: "euclidean" }
+    Version: 1
     Tree 1: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(1), right: Tree(5), normal: [0.0000, 0.0000] })
     Tree 2: Descendants(Descendants { descendants: [4, 5] })
     Tree 3: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(1), right: Tree(2), normal: [0.0000, 0.0000] })
@@ -348,6 +374,7 @@ fn delete_one_item_in_a_one_item_db() {
     ==================
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[0]>, roots: [0], distance: "euclidean" }
+    Version: 1
     Tree 0: Descendants(Descendants { descendants: [0] })
     Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 0.0000] })
     "###);
@@ -364,6 +391,7 @@ fn delete_one_item_in_a_one_item_db() {
     ==================
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[]>, roots: [], distance: "euclidean" }
+    Version: 1
     "###);
 
     let rtxn = handle.env.read_txn().unwrap();
@@ -389,6 +417,7 @@ fn delete_document_in_an_empty_index_74() {
     ==================
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[0]>, roots: [0], distance: "euclidean" }
+    Version: 1
     Tree 0: Descendants(Descendants { descendants: [0] })
     Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 0.0000] })
     "###);
@@ -414,9 +443,11 @@ fn delete_document_in_an_empty_index_74() {
     ==================
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[]>, roots: [], distance: "euclidean" }
+    Version: 1
     ==================
     Dumping index 1
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[]>, roots: [], distance: "euclidean" }
+    Version: 1
     "###);
 
     let rtxn = handle.env.read_txn().unwrap();
@@ -442,6 +473,7 @@ fn delete_one_item_in_a_descendant() {
     ==================
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[0, 1]>, roots: [0], distance: "euclidean" }
+    Version: 1
     Tree 0: Descendants(Descendants { descendants: [0, 1] })
     Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 0.0000] })
     Item 1: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [1.0000, 0.0000] })
@@ -459,6 +491,7 @@ fn delete_one_item_in_a_descendant() {
     ==================
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[1]>, roots: [0], distance: "euclidean" }
+    Version: 1
     Tree 0: Descendants(Descendants { descendants: [1] })
     Item 1: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [1.0000, 0.0000] })
     "###);
@@ -482,6 +515,7 @@ fn delete_one_leaf_in_a_split() {
     ==================
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[0, 1, 2]>, roots: [1], distance: "euclidean" }
+    Version: 1
     Tree 0: Descendants(Descendants { descendants: [1, 2] })
     Tree 1: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(0), right: Tree(0), normal: [1.0000, 0.0000] })
     Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 0.0000] })
@@ -502,6 +536,7 @@ fn delete_one_leaf_in_a_split() {
     ==================
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[1, 2]>, roots: [0], distance: "euclidean" }
+    Version: 1
     Tree 0: Descendants(Descendants { descendants: [1, 2] })
     Item 1: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [1.0000, 0.0000] })
     Item 2: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [2.0000, 0.0000] })
@@ -524,6 +559,7 @@ fn delete_one_item_in_a_single_document_database() {
     ==================
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[0]>, roots: [0], distance: "cosine" }
+    Version: 1
     Tree 0: Descendants(Descendants { descendants: [0] })
     Item 0: Leaf(Leaf { header: NodeHeaderCosine { norm: 0.0 }, vector: [0.0000, 0.0000] })
     "###);
@@ -540,6 +576,7 @@ fn delete_one_item_in_a_single_document_database() {
     ==================
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[]>, roots: [], distance: "cosine" }
+    Version: 1
     "###);
 }
 
@@ -561,6 +598,7 @@ fn delete_one_item() {
     ==================
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[0, 1, 2, 3, 4, 5]>, roots: [4], distance: "euclidean" }
+    Version: 1
     Tree 0: Descendants(Descendants { descendants: [1, 3] })
     Tree 1: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(0), right: Item(2), normal: [0.0000, 0.0000] })
     Tree 2: Descendants(Descendants { descendants: [4, 5] })
@@ -586,6 +624,7 @@ fn delete_one_item() {
     ==================
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[0, 1, 2, 4, 5]>, roots: [4], distance: "euclidean" }
+    Version: 1
     Tree 1: Descendants(Descendants { descendants: [1, 2] })
     Tree 2: Descendants(Descendants { descendants: [4, 5] })
     Tree 3: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(1), right: Tree(2), normal: [0.0000, 0.0000] })
@@ -610,6 +649,7 @@ fn delete_one_item() {
     ==================
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[0, 2, 4, 5]>, roots: [4], distance: "euclidean" }
+    Version: 1
     Tree 2: Descendants(Descendants { descendants: [4, 5] })
     Tree 3: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(2), right: Tree(2), normal: [0.0000, 0.0000] })
     Tree 4: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(0), right: Tree(3), normal: [1.0000, 0.0000] })
@@ -620,6 +660,66 @@ fn delete_one_item() {
     "###);
 }
 
+#[test]
+fn delete_items_in_bulk() {
+    let handle = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 2);
+
+    for i in 0..10 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 0.]).unwrap();
+    }
+    // We must not touch the items of the other indexes
+    let other_writer = Writer::new(handle.database, 1, 2);
+    other_writer.add_item(&mut wtxn, 3, &[3.0, 0.]).unwrap();
+    writer.builder(&mut rng).n_trees(1).build(&mut wtxn).unwrap();
+    other_writer.builder(&mut rng).n_trees(1).build(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 2);
+
+    // 3 to 5 are consecutive, 8 is alone and 42 doesn't exist
+    let to_delete = RoaringBitmap::from_iter([3, 4, 5, 8, 42]);
+    let deleted = writer.del_items(&mut wtxn, &to_delete).unwrap();
+    assert_eq!(deleted, 4);
+    assert!(writer.need_build(&wtxn).unwrap(), "because items have been deleted");
+
+    // Deleting the same items twice doesn't delete anything
+    let deleted = writer.del_items(&mut wtxn, &to_delete).unwrap();
+    assert_eq!(deleted, 0);
+
+    writer.builder(&mut rng).n_trees(1).build(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    insta::assert_snapshot!(handle, @r###"
+    ==================
+    Dumping index 0
+    Root: Metadata { dimensions: 2, items: RoaringBitmap<[0, 1, 2, 6, 7, 9]>, roots: [8], distance: "euclidean" }
+    Version: 1
+    Tree 0: Descendants(Descendants { descendants: [1, 7] })
+    Tree 3: Descendants(Descendants { descendants: [2, 6] })
+    Tree 4: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(0), right: Tree(3), normal: [0.0000, 0.0000] })
+    Tree 6: Descendants(Descendants { descendants: [0, 9] })
+    Tree 7: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(4), right: Tree(6), normal: [0.0000, 0.0000] })
+    Tree 8: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(7), right: Tree(9), normal: [0.0000, 0.0000] })
+    Tree 9: Descendants(Descendants { descendants: [] })
+    Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 0.0000] })
+    Item 1: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [1.0000, 0.0000] })
+    Item 2: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [2.0000, 0.0000] })
+    Item 6: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [6.0000, 0.0000] })
+    Item 7: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [7.0000, 0.0000] })
+    Item 9: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [9.0000, 0.0000] })
+    ==================
+    Dumping index 1
+    Root: Metadata { dimensions: 2, items: RoaringBitmap<[3]>, roots: [0], distance: "euclidean" }
+    Version: 1
+    Tree 0: Descendants(Descendants { descendants: [3] })
+    Item 3: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [3.0000, 0.0000] })
+    "###);
+}
+
 #[test]
 fn add_one_item_incrementally_in_an_empty_db() {
     let handle = create_database::<Euclidean>();
@@ -633,6 +733,7 @@ fn add_one_item_incrementally_in_an_empty_db() {
     ==================
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[]>, roots: [], distance: "euclidean" }
+    Version: 1
     "###);
 
     let mut wtxn = handle.env.write_txn().unwrap();
@@ -645,6 +746,7 @@ fn add_one_item_incrementally_in_an_empty_db() {
     ==================
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[0]>, roots: [0], distance: "euclidean" }
+    Version: 1
     Tree 0: Descendants(Descendants { descendants: [0] })
     Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 0.0000] })
     "###);
@@ -664,6 +766,7 @@ fn add_one_item_incrementally_in_a_one_item_db() {
     ==================
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[0]>, roots: [0], distance: "euclidean" }
+    Version: 1
     Tree 0: Descendants(Descendants { descendants: [0] })
     Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 0.0000] })
     "###);
@@ -678,6 +781,7 @@ fn add_one_item_incrementally_in_a_one_item_db() {
     ==================
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[0, 1]>, roots: [0], distance: "euclidean" }
+    Version: 1
     Tree 0: Descendants(Descendants { descendants: [0, 1] })
     Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 0.0000] })
     Item 1: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [1.0000, 0.0000] })
@@ -699,6 +803,7 @@ fn add_one_item_incrementally_to_create_a_split_node() {
     ==================
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[0, 1]>, roots: [0], distance: "euclidean" }
+    Version: 1
     Tree 0: Descendants(Descendants { descendants: [0, 1] })
     Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 0.0000] })
     Item 1: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [1.0000, 0.0000] })
@@ -714,6 +819,7 @@ fn add_one_item_incrementally_to_create_a_split_node() {
     ==================
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[0, 1, 2]>, roots: [2], distance: "euclidean" }
+    Version: 1
     Tree 1: Descendants(Descendants { descendants: [1, 2] })
     Tree 2: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(0), right: Tree(1), normal: [1.0000, 0.0000] })
     Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 0.0000] })
@@ -739,6 +845,7 @@ fn add_one_item_incrementally() {
     ==================
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[0, 1, 2, 3, 4, 5]>, roots: [4], distance: "euclidean" }
+    Version: 1
     Tree 0: Descendants(Descendants { descendants: [1, 3] })
     Tree 1: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(0), right: Item(2), normal: [0.0000, 0.0000] })
     Tree 2: Descendants(Descendants { descendants: [4, 5] })
@@ -764,6 +871,7 @@ fn add_one_item_incrementally() {
     ==================
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[0, 1, 2, 3, 4, 5, 25]>, roots: [4], distance: "euclidean" }
+    Version: 1
     Tree 0: Descendants(Descendants { descendants: [1, 3] })
     Tree 1: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(0), right: Tree(5), normal: [0.0000, 0.0000] })
     Tree 2: Descendants(Descendants { descendants: [4, 5] })
@@ -791,6 +899,7 @@ fn add_one_item_incrementally() {
     ==================
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[0, 1, 2, 3, 4, 5, 8, 25]>, roots: [4], distance: "euclidean" }
+    Version: 1
     Tree 0: Descendants(Descendants { descendants: [1, 3] })
     Tree 1: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(0), right: Tree(7), normal: [0.0000, 0.0000] })
     Tree 2: Descendants(Descendants { descendants: [4, 5] })
@@ -827,6 +936,7 @@ fn delete_extraneous_tree() {
     ==================
     Dumping index 0
     Root: Metadata { dimensions: 4, items: RoaringBitmap<[0, 1, 2, 3, 4]>, roots: [1, 3, 5], distance: "euclidean" }
+    Version: 1
     Tree 0: Descendants(Descendants { descendants: [1, 2, 3, 4] })
     Tree 1: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(0), right: Tree(0), normal: [1.0000, 0.0000, 0.0000, 0.0000] })
     Tree 2: Descendants(Descendants { descendants: [1, 2, 3, 4] })
@@ -849,6 +959,7 @@ fn delete_extraneous_tree() {
     ==================
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[0, 1, 2, 3, 4]>, roots: [3, 5], distance: "euclidean" }
+    Version: 1
     Tree 2: Descendants(Descendants { descendants: [1, 2, 3, 4] })
     Tree 3: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(2), right: Item(0), normal: [-1.0000, 0.0000, 0.0000, 0.0000] })
     Tree 4: Descendants(Descendants { descendants: [1, 2, 3, 4] })
@@ -869,6 +980,7 @@ fn delete_extraneous_tree() {
     ==================
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[0, 1, 2, 3, 4]>, roots: [5], distance: "euclidean" }
+    Version: 1
     Tree 4: Descendants(Descendants { descendants: [1, 2, 3, 4] })
     Tree 5: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(4), right: Item(0), normal: [-1.0000, 0.0000, 0.0000, 0.0000] })
     Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 0.0000, 0.0000, 0.0000] })
@@ -896,6 +1008,7 @@ fn reuse_node_id() {
     ==================
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[0, 1, 2, 3, 4, 5]>, roots: [4], distance: "euclidean" }
+    Version: 1
     Tree 0: Descendants(Descendants { descendants: [1, 3] })
     Tree 1: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(0), right: Item(2), normal: [0.0000, 0.0000] })
     Tree 2: Descendants(Descendants { descendants: [4, 5] })
@@ -921,6 +1034,7 @@ fn reuse_node_id() {
     ==================
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[0, 2, 3, 4, 5]>, roots: [4], distance: "euclidean" }
+    Version: 1
     Tree 1: Descendants(Descendants { descendants: [2, 3] })
     Tree 2: Descendants(Descendants { descendants: [4, 5] })
     Tree 3: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(1), right: Tree(2), normal: [0.0000, 0.0000] })
@@ -944,6 +1058,7 @@ fn reuse_node_id() {
     ==================
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[0, 1, 2, 3, 4, 5]>, roots: [4], distance: "euclidean" }
+    Version: 1
     Tree 0: Descendants(Descendants { descendants: [2, 3] })
     Tree 2: Descendants(Descendants { descendants: [4, 5] })
     Tree 3: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(5), right: Tree(2), normal: [0.0000, 0.0000] })
@@ -968,6 +1083,7 @@ fn reuse_node_id() {
     ==================
     Dumping index 0
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[0, 1, 2, 3, 4, 5]>, roots: [4, 9], distance: "euclidean" }
+    Version: 1
     Tree 0: Descendants(Descendants { descendants: [2, 3] })
     Tree 1: Descendants(Descendants { descendants: [1, 3] })
     Tree 2: Descendants(Descendants { descendants: [4, 5] })
@@ -1007,6 +1123,213 @@ fn need_build() {
     assert!(writer.need_build(&wtxn).unwrap(), "because an item has been updated");
 }
 
+#[test]
+fn upgrade_unversioned_index() {
+    let handle = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 2);
+    for i in 0..3 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 0.0]).unwrap();
+    }
+    writer.builder(&mut rng).n_trees(1).build(&mut wtxn).unwrap();
+    assert_eq!(writer.format_version(&wtxn).unwrap(), FORMAT_VERSION);
+    assert!(!writer.need_upgrade(&wtxn).unwrap());
+
+    // The older versions of arroy didn't store the version and kept the updated items in a bitmap
+    writer.add_item(&mut wtxn, 3, &[3.0, 0.0]).unwrap();
+    let database = handle.database.remap_data_type::<Bytes>();
+    database.delete(&mut wtxn, &Key::updated(0, 3)).unwrap();
+    database.delete(&mut wtxn, &Key::version(0)).unwrap();
+    let legacy = handle.database.remap_data_type::<RoaringBitmapCodec>();
+    legacy.put(&mut wtxn, &Key::legacy_updated_items(0), &RoaringBitmap::from_iter([3])).unwrap();
+
+    assert_eq!(writer.format_version(&wtxn).unwrap(), 0);
+    assert!(writer.need_upgrade(&wtxn).unwrap());
+    assert!(!writer.need_build(&wtxn).unwrap(), "the legacy updated items are not visible");
+    let err = writer.builder(&mut rng).prepare(&wtxn).map(|_| ()).unwrap_err();
+    assert_snapshot!(err, @"The index 0 is written in an older format and must be upgraded");
+
+    writer.upgrade(&mut wtxn).unwrap();
+    assert_eq!(writer.format_version(&wtxn).unwrap(), FORMAT_VERSION);
+    assert!(!writer.need_upgrade(&wtxn).unwrap());
+    assert!(writer.need_build(&wtxn).unwrap(), "the legacy updated items were migrated");
+
+    writer.builder(&mut rng).build(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+    insta::assert_snapshot!(handle, @r###"
+    ==================
+    Dumping index 0
+    Root: Metadata { dimensions: 2, items: RoaringBitmap<[0, 1, 2, 3]>, roots: [1], distance: "euclidean" }
+    Version: 1
+    Tree 1: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(0), right: Tree(5), normal: [1.0000, 0.0000] })
+    Tree 2: Descendants(Descendants { descendants: [1, 2] })
+    Tree 3: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(3), right: Tree(2), normal: [0.0000, 0.0000] })
+    Tree 4: Descendants(Descendants { descendants: [] })
+    Tree 5: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(3), right: Tree(4), normal: [0.0000, 0.0000] })
+    Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 0.0000] })
+    Item 1: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [1.0000, 0.0000] })
+    Item 2: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [2.0000, 0.0000] })
+    Item 3: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [3.0000, 0.0000] })
+    "###);
+}
+
+#[test]
+fn upgrade_a_newer_index() {
+    let handle = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 2);
+    writer.add_item(&mut wtxn, 0, &[0.0, 0.0]).unwrap();
+    writer.builder(&mut rng).build(&mut wtxn).unwrap();
+
+    let versions = handle.database.remap_data_type::<VersionCodec>();
+    versions.put(&mut wtxn, &Key::version(0), &(FORMAT_VERSION + 1)).unwrap();
+
+    assert!(!writer.need_upgrade(&wtxn).unwrap());
+    let err = writer.upgrade(&mut wtxn).unwrap_err();
+    assert_snapshot!(err, @"The index 0 is written in the format version 2 but only the versions up to 1 are supported");
+    let err = writer.builder(&mut rng).build(&mut wtxn).unwrap_err();
+    assert_snapshot!(err, @"The index 0 is written in the format version 2 but only the versions up to 1 are supported");
+}
+
+#[test]
+fn repair_broken_trees() {
+    let handle = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 2);
+    for i in 0..20 {
+        writer.add_item(&mut wtxn, i, &[i as f32, (i * 7 % 13) as f32]).unwrap();
+    }
+    writer.builder(&mut rng).n_trees(3).build(&mut wtxn).unwrap();
+
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, handle.database).unwrap();
+    assert!(reader.verify(&wtxn).unwrap().is_valid());
+    drop(reader);
+    let metadata = handle.database.remap_data_type::<MetadataCodec>();
+    let roots: Vec<_> =
+        metadata.get(&wtxn, &Key::metadata(0)).unwrap().unwrap().roots.iter().collect();
+
+    // Removes a tree node of the first tree, creates an orphan
+    // node and a dangling item in the metadata
+    let database = handle.database.remap_data_type::<Bytes>();
+    let left = match handle.database.get(&wtxn, &Key::tree(0, roots[0])).unwrap().unwrap() {
+        Node::SplitPlaneNormal(root) => root.left,
+        _ => panic!("the root must be a split plane"),
+    };
+    database.delete(&mut wtxn, &Key::new(0, left)).unwrap();
+    database.put(&mut wtxn, &Key::tree(0, 1000), &[1]).unwrap();
+    writer.del_item(&mut wtxn, 19).unwrap();
+    database.delete(&mut wtxn, &Key::updated(0, 19)).unwrap();
+
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, handle.database).unwrap();
+    let report = reader.verify(&wtxn).unwrap();
+    drop(reader);
+    insta::assert_debug_snapshot!(report, @r###"
+    VerifyReport {
+        broken_trees: [
+            24,
+        ],
+        orphan_tree_nodes: RoaringBitmap<[0, 1, 2, 3, 4, 5, 1000]>,
+        shared_tree_nodes: RoaringBitmap<[]>,
+        dangling_tree_nodes: RoaringBitmap<[6]>,
+        corrupted_tree_nodes: RoaringBitmap<[]>,
+        missing_items: RoaringBitmap<[2, 5, 8, 14, 16, 17, 18]>,
+        unknown_items: RoaringBitmap<[]>,
+        dangling_items: RoaringBitmap<[19]>,
+        invalid_items: RoaringBitmap<[]>,
+    }
+    "###);
+
+    let repaired = writer.repair(&mut wtxn, &mut rng).unwrap();
+    assert_eq!(report, repaired);
+
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, handle.database).unwrap();
+    let report = reader.verify(&wtxn).unwrap();
+    assert!(report.is_valid(), "{report:?}");
+    assert_eq!(metadata.get(&wtxn, &Key::metadata(0)).unwrap().unwrap().roots.len(), 3);
+    assert_eq!(reader.item_ids().len(), 19);
+    reader.assert_validity(&wtxn).unwrap();
+}
+
+#[test]
+fn repair_leaves_invalid_items_untouched() {
+    let handle = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 2);
+    for i in 0..5 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 0.0]).unwrap();
+    }
+    writer.builder(&mut rng).n_trees(1).build(&mut wtxn).unwrap();
+
+    let wrong_dimensions = Writer::new(handle.database, 0, 3);
+    wrong_dimensions.add_item(&mut wtxn, 2, &[2.0, 0.0, 0.0]).unwrap();
+    handle.database.remap_data_type::<Bytes>().delete(&mut wtxn, &Key::updated(0, 2)).unwrap();
+
+    let report = writer.repair(&mut wtxn, &mut rng).unwrap();
+    insta::assert_debug_snapshot!(report.invalid_items, @"RoaringBitmap<[2]>");
+    assert!(!report.is_valid());
+    assert!(!writer.need_build(&wtxn).unwrap());
+}
+
+#[test]
+fn collect_garbage() {
+    let handle = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 2);
+    for i in 0..20 {
+        writer.add_item(&mut wtxn, i, &[i as f32, (i * 7 % 13) as f32]).unwrap();
+    }
+    writer.builder(&mut rng).n_trees(2).build(&mut wtxn).unwrap();
+
+    let report = writer.collect_garbage(&mut wtxn).unwrap();
+    assert_eq!(report, Default::default());
+
+    let database = handle.database.remap_data_type::<Bytes>();
+    database.put(&mut wtxn, &Key::tree(0, 1000), &[1, 0]).unwrap();
+    database.put(&mut wtxn, &Key::tree(0, 1001), &[255]).unwrap();
+
+    let report = writer.collect_garbage(&mut wtxn).unwrap();
+    insta::assert_debug_snapshot!(report, @r###"
+    GarbageReport {
+        deleted_tree_nodes: RoaringBitmap<[1000, 1001]>,
+        reclaimed_bytes: 19,
+    }
+    "###);
+
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, handle.database).unwrap();
+    assert!(reader.verify(&wtxn).unwrap().is_valid());
+    reader.assert_validity(&wtxn).unwrap();
+}
+
+#[test]
+fn collect_garbage_keeps_the_pending_build() {
+    let handle = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 2);
+    for i in 0..20 {
+        writer.add_item(&mut wtxn, i, &[i as f32, (i * 7 % 13) as f32]).unwrap();
+    }
+    let one = NonZeroUsize::new(1).unwrap();
+    assert!(!writer.builder(&mut rng).n_trees(3).build_step(&mut wtxn, one).unwrap());
+    handle
+        .database
+        .remap_data_type::<Bytes>()
+        .put(&mut wtxn, &Key::tree(0, 1000), &[1, 0])
+        .unwrap();
+
+    let report = writer.collect_garbage(&mut wtxn).unwrap();
+    insta::assert_debug_snapshot!(report.deleted_tree_nodes, @"RoaringBitmap<[1000]>");
+
+    writer.builder(&mut rng).n_trees(3).build(&mut wtxn).unwrap();
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, handle.database).unwrap();
+    assert!(reader.verify(&wtxn).unwrap().is_valid());
+}
+
 #[test]
 fn append() {
     let handle = create_database::<Euclidean>();
@@ -1024,6 +1347,7 @@ fn append() {
     ==================
     Dumping index 1
     Root: Metadata { dimensions: 2, items: RoaringBitmap<[0, 1]>, roots: [0], distance: "euclidean" }
+    Version: 1
     Tree 0: Descendants(Descendants { descendants: [0, 1] })
     Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 0.0000] })
     Item 1: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.1000, 0.1000] })
@@ -1050,6 +1374,47 @@ fn append() {
     writer.append_item(&mut wtxn, 0, &[0.0, 0.0]).unwrap();
 }
 
+#[test]
+fn import_vectors() {
+    let mut file = Vec::new();
+    for vector in [[0.0f32, 1.0], [2.0, 3.0], [4.0, 5.0]] {
+        file.extend(2i32.to_le_bytes());
+        file.extend(vector.iter().flat_map(|x| x.to_le_bytes()));
+    }
+
+    let handle = create_database::<Euclidean>();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 2);
+    writer.add_item(&mut wtxn, 11, &[9.0, 9.0]).unwrap();
+
+    // The existing item prevents appending the vectors, they are inserted and replace it
+    let vectors = VectorReader::new(&file[..], VectorFormat::Fvecs).unwrap();
+    assert_eq!(writer.import_vectors(&mut wtxn, vectors, 10).unwrap(), 3);
+    writer.builder(&mut rng()).build(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+    insta::assert_snapshot!(handle, @r###"
+    ==================
+    Dumping index 0
+    Root: Metadata { dimensions: 2, items: RoaringBitmap<[10, 11, 12]>, roots: [1, 5], distance: "euclidean" }
+    Version: 1
+    Tree 0: Descendants(Descendants { descendants: [11, 12] })
+    Tree 1: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(10), right: Tree(0), normal: [0.0000, 0.0000] })
+    Tree 2: Descendants(Descendants { descendants: [10, 12] })
+    Tree 3: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(2), right: Item(11), normal: [0.0000, 0.0000] })
+    Tree 4: Descendants(Descendants { descendants: [] })
+    Tree 5: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(3), right: Tree(4), normal: [0.0000, 0.0000] })
+    Item 10: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 1.0000] })
+    Item 11: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [2.0000, 3.0000] })
+    Item 12: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [4.0000, 5.0000] })
+    "###);
+
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 3);
+    let vectors = VectorReader::new(&file[..], VectorFormat::Fvecs).unwrap();
+    let err = writer.import_vectors(&mut wtxn, vectors, 0).unwrap_err();
+    assert_snapshot!(err, @"Invalid vector dimensions. Got 2 but expected 3");
+}
+
 #[test]
 fn prepare_changing_distance() {
     let handle = create_database::<Cosine>();
@@ -1089,6 +1454,141 @@ fn prepare_changing_distance() {
     writer.builder(&mut rng).build(&mut wtxn).unwrap();
 }
 
+#[test]
+fn prepare_changing_dimensions() {
+    let handle = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 3);
+    writer.add_item(&mut wtxn, 0, &[0.0, 1.0, 2.0]).unwrap();
+    writer.add_item(&mut wtxn, 1, &[1.0, 2.0, 3.0]).unwrap();
+    writer.builder(&mut rng).build(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 3);
+    let err =
+        writer.prepare_changing_dimensions(&mut wtxn, 4, DimensionChange::Truncate).unwrap_err();
+    assert_snapshot!(err, @"Cannot change the dimensions of the index from 3 to 4: vectors cannot be truncated to more dimensions");
+    let writer = Writer::new(handle.database, 0, 3);
+    let err =
+        writer.prepare_changing_dimensions(&mut wtxn, 2, DimensionChange::ZeroPad).unwrap_err();
+    assert_snapshot!(err, @"Cannot change the dimensions of the index from 3 to 2: vectors cannot be padded to fewer dimensions");
+    let writer = Writer::new(handle.database, 0, 3);
+    let err = writer
+        .prepare_changing_dimensions(&mut wtxn, 2, DimensionChange::Project(&[1.0; 3]))
+        .unwrap_err();
+    assert_snapshot!(err, @"Cannot change the dimensions of the index from 3 to 2: the projection matrix must be of size new x old dimensions");
+    let writer = Writer::new(handle.database, 0, 3);
+    let err =
+        writer.prepare_changing_dimensions(&mut wtxn, 0, DimensionChange::Truncate).unwrap_err();
+    assert_snapshot!(err, @"Cannot change the dimensions of the index from 3 to 0: the vectors must have at least one dimension");
+    let writer = Writer::new(handle.database, 0, 3);
+    let err = writer
+        .prepare_changing_dimensions(&mut wtxn, 0, DimensionChange::Project(&[]))
+        .unwrap_err();
+    assert_snapshot!(err, @"Cannot change the dimensions of the index from 3 to 0: the vectors must have at least one dimension");
+
+    let writer = Writer::new(handle.database, 0, 3);
+    let writer =
+        writer.prepare_changing_dimensions(&mut wtxn, 2, DimensionChange::Truncate).unwrap();
+    assert!(writer.need_build(&wtxn).unwrap(), "after changing the dimensions");
+    writer.builder(&mut rng).build(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    insta::assert_snapshot!(handle, @r###"
+    ==================
+    Dumping index 0
+    Root: Metadata { dimensions: 2, items: RoaringBitmap<[0, 1]>, roots: [0], distance: "euclidean" }
+    Version: 1
+    Tree 0: Descendants(Descendants { descendants: [0, 1] })
+    Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 1.0000] })
+    Item 1: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [1.0000, 2.0000] })
+    "###);
+
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer =
+        writer.prepare_changing_dimensions(&mut wtxn, 4, DimensionChange::ZeroPad).unwrap();
+    writer.builder(&mut rng).build(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    insta::assert_snapshot!(handle, @r###"
+    ==================
+    Dumping index 0
+    Root: Metadata { dimensions: 4, items: RoaringBitmap<[0, 1]>, roots: [0], distance: "euclidean" }
+    Version: 1
+    Tree 0: Descendants(Descendants { descendants: [0, 1] })
+    Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 1.0000, 0.0000, 0.0000] })
+    Item 1: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [1.0000, 2.0000, 0.0000, 0.0000] })
+    "###);
+
+    // Swaps the two first dimensions and sums the others
+    #[rustfmt::skip]
+    let matrix = [
+        0.0, 1.0, 0.0, 0.0,
+        1.0, 0.0, 0.0, 0.0,
+        0.0, 0.0, 1.0, 1.0,
+    ];
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = writer
+        .prepare_changing_dimensions(&mut wtxn, 3, DimensionChange::Project(&matrix))
+        .unwrap();
+    writer.builder(&mut rng).build(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    insta::assert_snapshot!(handle, @r###"
+    ==================
+    Dumping index 0
+    Root: Metadata { dimensions: 3, items: RoaringBitmap<[0, 1]>, roots: [0], distance: "euclidean" }
+    Version: 1
+    Tree 0: Descendants(Descendants { descendants: [0, 1] })
+    Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [1.0000, 0.0000, 0.0000] })
+    Item 1: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [2.0000, 1.0000, 0.0000] })
+    "###);
+
+    // A tree node stored under an item key must not make the writer panic
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let descendants =
+        Node::Descendants(Descendants { descendants: Cow::Owned(RoaringBitmap::from_iter([0])) });
+    handle.database.put(&mut wtxn, &Key::item(0, 1), &descendants).unwrap();
+    let err = writer
+        .prepare_changing_dimensions(&mut wtxn, 2, DimensionChange::Truncate)
+        .map(|_| ())
+        .unwrap_err();
+    assert_snapshot!(err, @"Corrupted database: Item(1) of index `0` cannot be decoded: an item is not a leaf");
+}
+
+#[test]
+fn prepare_changing_dimensions_forgets_the_dimensions_options() {
+    let handle = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 4);
+    for i in 0..100 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 1.1, (i % 7) as f32, 0.5]).unwrap();
+    }
+    writer.builder(&mut rng).n_trees(2).split_dimensions(4).build(&mut wtxn).unwrap();
+    writer.builder(&mut rng).reduce_dimensions(3).build(&mut wtxn).unwrap();
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, handle.database).unwrap();
+    let config = reader.build_config();
+    assert_eq!(
+        (config.n_trees, config.split_dimensions, config.reduced_dimensions),
+        (Some(2), Some(4), Some(3))
+    );
+    drop(reader);
+
+    let writer =
+        writer.prepare_changing_dimensions(&mut wtxn, 2, DimensionChange::Truncate).unwrap();
+    writer.builder(&mut rng).build(&mut wtxn).unwrap();
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, handle.database).unwrap();
+    reader.assert_validity(&wtxn).unwrap();
+    let config = reader.build_config();
+    assert_eq!(
+        (config.n_trees, config.split_dimensions, config.reduced_dimensions),
+        (Some(2), None, None)
+    );
+}
+
 #[test]
 fn cancel_indexing_process() {
     let handle = create_database::<Euclidean>();
@@ -1119,3 +1619,1028 @@ fn cancel_indexing_process() {
         .unwrap_err();
     assert_snapshot!(err, @"The corresponding build process has been cancelled");
 }
+
+#[test]
+fn prepare_build_from_a_read_transaction() {
+    let handle = create_database::<Euclidean>();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 2);
+    for i in 0..100 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 1.1]).unwrap();
+    }
+    wtxn.commit().unwrap();
+
+    // We build the same trees in another index with the classic build
+    let expected = create_database::<Euclidean>();
+    let mut wtxn = expected.env.write_txn().unwrap();
+    let expected_writer = Writer::new(expected.database, 0, 2);
+    for i in 0..100 {
+        expected_writer.add_item(&mut wtxn, i, &[i as f32, 1.1]).unwrap();
+    }
+    expected_writer.builder(&mut rng()).n_trees(3).build(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    let mut rng = rng();
+    let rtxn = handle.env.read_txn().unwrap();
+    let mut builder = writer.builder(&mut rng);
+    let prepared = builder.n_trees(3).prepare(&rtxn).unwrap();
+    drop(rtxn);
+
+    let mut wtxn = handle.env.write_txn().unwrap();
+    prepared.apply(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    assert_eq!(handle.to_string(), expected.to_string());
+}
+
+#[test]
+fn apply_outdated_build() {
+    let handle = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 2);
+    for i in 0..100 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 1.1]).unwrap();
+    }
+    wtxn.commit().unwrap();
+
+    let rtxn = handle.env.read_txn().unwrap();
+    let mut builder = writer.builder(&mut rng);
+    let prepared = builder.prepare(&rtxn).unwrap();
+    drop(rtxn);
+
+    // Updating an item that is already marked as updated must be detected
+    let mut wtxn = handle.env.write_txn().unwrap();
+    writer.add_item(&mut wtxn, 3, &[3.0, 0.0]).unwrap();
+    let err = prepared.apply(&mut wtxn).unwrap_err();
+    assert_snapshot!(err, @"The index 0 was modified since the build was prepared, it must be prepared again");
+    wtxn.commit().unwrap();
+
+    // Once prepared again we can apply it
+    let rtxn = handle.env.read_txn().unwrap();
+    let prepared = builder.prepare(&rtxn).unwrap();
+    drop(rtxn);
+
+    let mut wtxn = handle.env.write_txn().unwrap();
+    prepared.apply(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    let rtxn = handle.env.read_txn().unwrap();
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, handle.database).unwrap();
+    reader.assert_validity(&rtxn).unwrap();
+    assert_eq!(reader.item_vector(&rtxn, 3).unwrap(), Some(vec![3.0, 0.0]));
+}
+
+#[test]
+fn prepare_build_of_items_not_preprocessed() {
+    let handle = create_database::<DotProduct>();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 2);
+    for i in 0..100 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 1.1]).unwrap();
+    }
+    wtxn.commit().unwrap();
+
+    let mut rng = rng();
+    let rtxn = handle.env.read_txn().unwrap();
+    let mut builder = writer.builder(&mut rng);
+    let err = builder.prepare(&rtxn).map(|_| ()).unwrap_err();
+    assert_snapshot!(err, @"The items of the index 0 must be preprocessed before preparing the build");
+    drop(rtxn);
+
+    let mut wtxn = handle.env.write_txn().unwrap();
+    writer.preprocess(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    let rtxn = handle.env.read_txn().unwrap();
+    let prepared = builder.prepare(&rtxn).unwrap();
+    drop(rtxn);
+
+    let mut wtxn = handle.env.write_txn().unwrap();
+    prepared.apply(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    let rtxn = handle.env.read_txn().unwrap();
+    let reader = Reader::<DotProduct>::open(&rtxn, 0, handle.database).unwrap();
+    reader.assert_validity(&rtxn).unwrap();
+}
+
+#[test]
+fn build_in_multiple_steps() {
+    let handle = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 2);
+    for i in 0..5 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 1.1]).unwrap();
+    }
+    writer.builder(&mut rng).n_trees(1).build(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    let one_tree_per_step = NonZeroUsize::new(1).unwrap();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let finished =
+        writer.builder(&mut rng).n_trees(3).build_step(&mut wtxn, one_tree_per_step).unwrap();
+    assert!(!finished);
+    wtxn.commit().unwrap();
+
+    // The readers must still see the previous tree
+    let rtxn = handle.env.read_txn().unwrap();
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, handle.database).unwrap();
+    assert_eq!(reader.n_trees(), 1);
+    drop(rtxn);
+
+    insta::assert_snapshot!(handle, @r###"
+    ==================
+    Dumping index 0
+    Root: Metadata { dimensions: 2, items: RoaringBitmap<[0, 1, 2, 3, 4]>, roots: [3], distance: "euclidean" }
+    Pending: [9]
+    Version: 1
+    Tree 0: Descendants(Descendants { descendants: [1, 4] })
+    Tree 1: Descendants(Descendants { descendants: [2, 3] })
+    Tree 2: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(0), right: Tree(1), normal: [0.0000, 0.0000] })
+    Tree 3: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(0), right: Tree(2), normal: [1.0000, -0.0000] })
+    Tree 4: Descendants(Descendants { descendants: [] })
+    Tree 5: Descendants(Descendants { descendants: [3, 4] })
+    Tree 6: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(2), right: Tree(5), normal: [0.0000, 0.0000] })
+    Tree 7: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(4), right: Tree(6), normal: [0.0000, 0.0000] })
+    Tree 8: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(7), right: Item(1), normal: [0.0000, 0.0000] })
+    Tree 9: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(0), right: Tree(8), normal: [1.0000, 0.0000] })
+    Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 1.1000] })
+    Item 1: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [1.0000, 1.1000] })
+    Item 2: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [2.0000, 1.1000] })
+    Item 3: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [3.0000, 1.1000] })
+    Item 4: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [4.0000, 1.1000] })
+    "###);
+
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let finished =
+        writer.builder(&mut rng).n_trees(3).build_step(&mut wtxn, one_tree_per_step).unwrap();
+    assert!(finished);
+    wtxn.commit().unwrap();
+
+    insta::assert_snapshot!(handle, @r###"
+    ==================
+    Dumping index 0
+    Root: Metadata { dimensions: 2, items: RoaringBitmap<[0, 1, 2, 3, 4]>, roots: [3, 9, 13], distance: "euclidean" }
+    Version: 1
+    Tree 0: Descendants(Descendants { descendants: [1, 4] })
+    Tree 1: Descendants(Descendants { descendants: [2, 3] })
+    Tree 2: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(0), right: Tree(1), normal: [0.0000, 0.0000] })
+    Tree 3: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(0), right: Tree(2), normal: [1.0000, -0.0000] })
+    Tree 4: Descendants(Descendants { descendants: [] })
+    Tree 5: Descendants(Descendants { descendants: [3, 4] })
+    Tree 6: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(2), right: Tree(5), normal: [0.0000, 0.0000] })
+    Tree 7: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(4), right: Tree(6), normal: [0.0000, 0.0000] })
+    Tree 8: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(7), right: Item(1), normal: [0.0000, 0.0000] })
+    Tree 9: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(0), right: Tree(8), normal: [1.0000, 0.0000] })
+    Tree 10: Descendants(Descendants { descendants: [1, 3] })
+    Tree 11: Descendants(Descendants { descendants: [2, 4] })
+    Tree 12: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(10), right: Tree(11), normal: [0.0000, 0.0000] })
+    Tree 13: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(0), right: Tree(12), normal: [1.0000, 0.0000] })
+    Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 1.1000] })
+    Item 1: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [1.0000, 1.1000] })
+    Item 2: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [2.0000, 1.1000] })
+    Item 3: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [3.0000, 1.1000] })
+    Item 4: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [4.0000, 1.1000] })
+    "###);
+}
+
+#[test]
+fn restart_build_steps_after_an_update() {
+    let handle = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 2);
+    for i in 0..5 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 1.1]).unwrap();
+    }
+    writer.builder(&mut rng).n_trees(1).build(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    let one_tree_per_step = NonZeroUsize::new(1).unwrap();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let finished =
+        writer.builder(&mut rng).n_trees(3).build_step(&mut wtxn, one_tree_per_step).unwrap();
+    assert!(!finished);
+    wtxn.commit().unwrap();
+
+    insta::assert_snapshot!(handle, @r###"
+    ==================
+    Dumping index 0
+    Root: Metadata { dimensions: 2, items: RoaringBitmap<[0, 1, 2, 3, 4]>, roots: [3], distance: "euclidean" }
+    Pending: [9]
+    Version: 1
+    Tree 0: Descendants(Descendants { descendants: [1, 4] })
+    Tree 1: Descendants(Descendants { descendants: [2, 3] })
+    Tree 2: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(0), right: Tree(1), normal: [0.0000, 0.0000] })
+    Tree 3: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(0), right: Tree(2), normal: [1.0000, -0.0000] })
+    Tree 4: Descendants(Descendants { descendants: [] })
+    Tree 5: Descendants(Descendants { descendants: [3, 4] })
+    Tree 6: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(2), right: Tree(5), normal: [0.0000, 0.0000] })
+    Tree 7: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(4), right: Tree(6), normal: [0.0000, 0.0000] })
+    Tree 8: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(7), right: Item(1), normal: [0.0000, 0.0000] })
+    Tree 9: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(0), right: Tree(8), normal: [1.0000, 0.0000] })
+    Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 1.1000] })
+    Item 1: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [1.0000, 1.1000] })
+    Item 2: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [2.0000, 1.1000] })
+    Item 3: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [3.0000, 1.1000] })
+    Item 4: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [4.0000, 1.1000] })
+    "###);
+
+    // The pending tree doesn't contain the new item and must be discarded
+    let mut wtxn = handle.env.write_txn().unwrap();
+    writer.add_item(&mut wtxn, 5, &[5.0, 1.1]).unwrap();
+    let finished =
+        writer.builder(&mut rng).n_trees(3).build_step(&mut wtxn, one_tree_per_step).unwrap();
+    assert!(!finished);
+    let finished =
+        writer.builder(&mut rng).n_trees(3).build_step(&mut wtxn, one_tree_per_step).unwrap();
+    assert!(finished);
+    wtxn.commit().unwrap();
+
+    insta::assert_snapshot!(handle, @r###"
+    ==================
+    Dumping index 0
+    Root: Metadata { dimensions: 2, items: RoaringBitmap<[0, 1, 2, 3, 4, 5]>, roots: [3, 10, 15], distance: "euclidean" }
+    Version: 1
+    Tree 1: Descendants(Descendants { descendants: [2, 3] })
+    Tree 2: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(21), right: Tree(1), normal: [0.0000, 0.0000] })
+    Tree 3: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(0), right: Tree(2), normal: [1.0000, -0.0000] })
+    Tree 4: Descendants(Descendants { descendants: [1, 2] })
+    Tree 5: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(4), right: Tree(4), normal: [0.0000, 0.0000] })
+    Tree 6: Descendants(Descendants { descendants: [] })
+    Tree 7: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(5), right: Tree(6), normal: [0.0000, 0.0000] })
+    Tree 8: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(5), right: Tree(7), normal: [0.0000, 0.0000] })
+    Tree 9: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(3), right: Tree(8), normal: [0.0000, 0.0000] })
+    Tree 10: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(9), right: Item(0), normal: [-1.0000, 0.0000] })
+    Tree 11: Descendants(Descendants { descendants: [2, 5] })
+    Tree 12: Descendants(Descendants { descendants: [3, 4] })
+    Tree 13: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(11), right: Tree(12), normal: [0.0000, 0.0000] })
+    Tree 14: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(13), right: Item(1), normal: [0.0000, 0.0000] })
+    Tree 15: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(14), right: Item(0), normal: [-1.0000, 0.0000] })
+    Tree 16: Descendants(Descendants { descendants: [] })
+    Tree 17: Descendants(Descendants { descendants: [1, 4] })
+    Tree 18: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(5), right: Tree(17), normal: [0.0000, 0.0000] })
+    Tree 19: Descendants(Descendants { descendants: [] })
+    Tree 20: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(18), right: Tree(19), normal: [0.0000, 0.0000] })
+    Tree 21: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(16), right: Tree(20), normal: [0.0000, 0.0000] })
+    Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 1.1000] })
+    Item 1: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [1.0000, 1.1000] })
+    Item 2: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [2.0000, 1.1000] })
+    Item 3: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [3.0000, 1.1000] })
+    Item 4: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [4.0000, 1.1000] })
+    Item 5: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [5.0000, 1.1000] })
+    "###);
+}
+
+#[test]
+fn build_config_is_used_by_the_next_builds() {
+    let handle = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 2);
+    for i in 0..10 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 1.1]).unwrap();
+    }
+    writer.builder(&mut rng).n_trees(3).split_after(4).build(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    let rtxn = handle.env.read_txn().unwrap();
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, handle.database).unwrap();
+    let config = BuildConfig {
+        n_trees: Some(3),
+        split_after: Some(4),
+        split_dimensions: None,
+        reduced_dimensions: None,
+        normal_precision: NormalPrecision::Full,
+        seed: None,
+        deterministic: false,
+    };
+    assert_eq!(reader.build_config(), config);
+    drop(rtxn);
+
+    // Without any option the trees must keep the same shape
+    let mut wtxn = handle.env.write_txn().unwrap();
+    writer.add_item(&mut wtxn, 10, &[10.0, 1.1]).unwrap();
+    writer.builder(&mut rng).build(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    let rtxn = handle.env.read_txn().unwrap();
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, handle.database).unwrap();
+    assert_eq!(reader.build_config(), config);
+    assert_eq!(reader.n_trees(), 3);
+    drop(rtxn);
+
+    // The options that are set override the stored ones
+    let mut wtxn = handle.env.write_txn().unwrap();
+    writer.builder(&mut rng).n_trees(5).build(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    let rtxn = handle.env.read_txn().unwrap();
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, handle.database).unwrap();
+    let config = BuildConfig {
+        n_trees: Some(5),
+        split_after: Some(4),
+        split_dimensions: None,
+        reduced_dimensions: None,
+        normal_precision: NormalPrecision::Full,
+        seed: None,
+        deterministic: false,
+    };
+    assert_eq!(reader.build_config(), config);
+    assert_eq!(reader.n_trees(), 5);
+}
+
+#[test]
+fn build_config_in_the_dump() {
+    let handle = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 2);
+    for i in 0..3 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 1.1]).unwrap();
+    }
+    writer.builder(&mut rng).n_trees(1).split_after(2).build(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    // The options other than the number of trees are shown in the dump
+    insta::assert_snapshot!(handle, @r###"
+    ==================
+    Dumping index 0
+    Root: Metadata { dimensions: 2, items: RoaringBitmap<[0, 1, 2]>, roots: [1], distance: "euclidean" }
+    Build config: BuildConfig { n_trees: Some(1), split_after: Some(2), split_dimensions: None, reduced_dimensions: None, normal_precision: Full, seed: None, deterministic: false }
+    Version: 1
+    Tree 0: Descendants(Descendants { descendants: [1, 2] })
+    Tree 1: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(0), right: Tree(0), normal: [1.0000, -0.0000] })
+    Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 1.1000] })
+    Item 1: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [1.0000, 1.1000] })
+    Item 2: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [2.0000, 1.1000] })
+    "###);
+}
+
+#[test]
+fn build_with_a_seed() {
+    let mut dumps = Vec::new();
+    for rng_seed in [1, 2] {
+        let handle = create_database::<Euclidean>();
+        let mut rng = StdRng::seed_from_u64(rng_seed);
+        let mut wtxn = handle.env.write_txn().unwrap();
+        let writer = Writer::new(handle.database, 0, 2);
+        for i in 0..10 {
+            writer.add_item(&mut wtxn, i, &[i as f32, (i % 3) as f32]).unwrap();
+        }
+        writer.builder(&mut rng).n_trees(2).seed(42).build(&mut wtxn).unwrap();
+        wtxn.commit().unwrap();
+
+        // The seed is reused to add new trees but they must differ from the first ones
+        let mut wtxn = handle.env.write_txn().unwrap();
+        writer.builder(&mut rng).n_trees(4).build(&mut wtxn).unwrap();
+        wtxn.commit().unwrap();
+
+        let rtxn = handle.env.read_txn().unwrap();
+        let reader = Reader::<Euclidean>::open(&rtxn, 0, handle.database).unwrap();
+        assert_eq!(reader.build_config().seed, Some(42));
+        drop(rtxn);
+        dumps.push(handle.to_string());
+    }
+
+    assert_eq!(dumps[0], dumps[1]);
+    insta::assert_snapshot!(dumps[0], @r###"
+    ==================
+    Dumping index 0
+    Root: Metadata { dimensions: 2, items: RoaringBitmap<[0, 1, 2, 3, 4, 5, 6, 7, 8, 9]>, roots: [8, 19, 28, 39], distance: "euclidean" }
+    Build config: BuildConfig { n_trees: Some(4), split_after: None, split_dimensions: None, reduced_dimensions: None, normal_precision: Full, seed: Some(42), deterministic: false }
+    Version: 1
+    Tree 0: Descendants(Descendants { descendants: [2, 9] })
+    Tree 1: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(0), right: Item(8), normal: [0.0000, 0.0000] })
+    Tree 2: Descendants(Descendants { descendants: [1, 7] })
+    Tree 3: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(1), right: Tree(2), normal: [0.0000, 0.0000] })
+    Tree 4: Descendants(Descendants { descendants: [3, 6] })
+    Tree 5: Descendants(Descendants { descendants: [4, 5] })
+    Tree 6: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(4), right: Tree(5), normal: [0.0000, 0.0000] })
+    Tree 7: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(3), right: Tree(6), normal: [0.0000, 0.0000] })
+    Tree 8: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(0), right: Tree(7), normal: [0.9975, 0.0707] })
+    Tree 9: Descendants(Descendants { descendants: [] })
+    Tree 10: Descendants(Descendants { descendants: [5, 8] })
+    Tree 11: Descendants(Descendants { descendants: [2, 7] })
+    Tree 12: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(10), right: Tree(11), normal: [0.0000, 0.0000] })
+    Tree 13: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(9), right: Tree(12), normal: [0.0000, 0.0000] })
+    Tree 14: Descendants(Descendants { descendants: [3, 4] })
+    Tree 15: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(14), right: Item(9), normal: [0.0000, 0.0000] })
+    Tree 16: Descendants(Descendants { descendants: [1, 6] })
+    Tree 17: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(15), right: Tree(16), normal: [0.0000, 0.0000] })
+    Tree 18: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(13), right: Tree(17), normal: [0.0000, 0.0000] })
+    Tree 19: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(18), right: Item(0), normal: [-0.9982, -0.0605] })
+    Tree 20: Descendants(Descendants { descendants: [1, 6] })
+    Tree 21: Descendants(Descendants { descendants: [4, 9] })
+    Tree 22: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(20), right: Tree(21), normal: [0.0000, 0.0000] })
+    Tree 23: Descendants(Descendants { descendants: [5, 8] })
+    Tree 24: Descendants(Descendants { descendants: [3, 7] })
+    Tree 25: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(24), right: Item(2), normal: [0.0000, 0.0000] })
+    Tree 26: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(23), right: Tree(25), normal: [0.0000, 0.0000] })
+    Tree 27: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(22), right: Tree(26), normal: [0.0000, 0.0000] })
+    Tree 28: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(27), right: Item(0), normal: [-0.9997, -0.0240] })
+    Tree 29: Descendants(Descendants { descendants: [] })
+    Tree 30: Descendants(Descendants { descendants: [2, 5] })
+    Tree 31: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(4), right: Tree(30), normal: [0.0000, 0.0000] })
+    Tree 32: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(29), right: Tree(31), normal: [0.0000, 0.0000] })
+    Tree 33: Descendants(Descendants { descendants: [6, 8] })
+    Tree 34: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(1), right: Tree(33), normal: [0.0000, 0.0000] })
+    Tree 35: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(7), right: Tree(34), normal: [0.0000, 0.0000] })
+    Tree 36: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(32), right: Tree(35), normal: [0.0000, 0.0000] })
+    Tree 37: Descendants(Descendants { descendants: [3, 9] })
+    Tree 38: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(36), right: Tree(37), normal: [0.0000, 0.0000] })
+    Tree 39: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(38), right: Item(0), normal: [-0.9998, 0.0176] })
+    Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 0.0000] })
+    Item 1: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [1.0000, 1.0000] })
+    Item 2: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [2.0000, 2.0000] })
+    Item 3: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [3.0000, 0.0000] })
+    Item 4: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [4.0000, 1.0000] })
+    Item 5: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [5.0000, 2.0000] })
+    Item 6: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [6.0000, 0.0000] })
+    Item 7: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [7.0000, 1.0000] })
+    Item 8: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [8.0000, 2.0000] })
+    Item 9: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [9.0000, 0.0000] })
+    "###);
+}
+
+#[test]
+fn deterministic_build_whatever_the_number_of_threads() {
+    let build = |n_threads: usize, n_trees: Option<usize>| {
+        let handle = create_database::<Euclidean>();
+        let writer = Writer::new(handle.database, 0, 10);
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(n_threads).build().unwrap();
+        pool.install(|| {
+            let mut rng = rng();
+            let mut wtxn = handle.env.write_txn().unwrap();
+            for id in 0..200 {
+                let vector: [f32; 10] = std::array::from_fn(|_| rng.gen());
+                writer.add_item(&mut wtxn, id, &vector).unwrap();
+            }
+            let mut builder = writer.builder(&mut rng);
+            builder.seed(42).deterministic(true);
+            if let Some(n_trees) = n_trees {
+                builder.n_trees(n_trees);
+            }
+            builder.build(&mut wtxn).unwrap();
+            drop(builder);
+            wtxn.commit().unwrap();
+
+            // The updates must be deterministic too
+            let mut wtxn = handle.env.write_txn().unwrap();
+            for id in (0..300).step_by(3) {
+                let vector: [f32; 10] = std::array::from_fn(|_| rng.gen());
+                writer.add_item(&mut wtxn, id, &vector).unwrap();
+            }
+            writer.del_item(&mut wtxn, 1).unwrap();
+            writer.builder(&mut rng).build(&mut wtxn).unwrap();
+            wtxn.commit().unwrap();
+        });
+
+        let rtxn = handle.env.read_txn().unwrap();
+        let reader = Reader::<Euclidean>::open(&rtxn, 0, handle.database).unwrap();
+        reader.assert_validity(&rtxn).unwrap();
+        handle
+            .database
+            .remap_types::<heed::types::Bytes, heed::types::Bytes>()
+            .iter(&rtxn)
+            .unwrap()
+            .map(|entry| entry.map(|(key, value)| (key.to_vec(), value.to_vec())))
+            .collect::<heed::Result<Vec<_>>>()
+            .unwrap()
+    };
+
+    for n_trees in [None, Some(7)] {
+        let expected = build(1, n_trees);
+        for n_threads in [2, 3, 8] {
+            assert!(
+                build(n_threads, n_trees) == expected,
+                "{n_threads} threads, {n_trees:?} trees"
+            );
+        }
+    }
+}
+
+#[test]
+fn build_in_a_dedicated_thread_pool() {
+    let handle = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 10);
+    for id in 0..100 {
+        let vector: [f32; 10] = std::array::from_fn(|_| rng.gen());
+        writer.add_item(&mut wtxn, id, &vector).unwrap();
+    }
+
+    let thread_pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(2)
+        .thread_name(|i| format!("arroy-build-{i}"))
+        .build()
+        .unwrap();
+    let current_thread = std::thread::current().name().map(ToOwned::to_owned);
+    let threads = Mutex::new(HashSet::new());
+    writer
+        .builder(&mut rng)
+        .n_trees(4)
+        .thread_pool(&thread_pool)
+        .cancel(|| {
+            threads.lock().unwrap().insert(std::thread::current().name().map(ToOwned::to_owned));
+            false
+        })
+        .build(&mut wtxn)
+        .unwrap();
+    wtxn.commit().unwrap();
+
+    let threads = threads.into_inner().unwrap();
+    assert!(threads.iter().flatten().any(|name| name.starts_with("arroy-build-")));
+    for name in threads {
+        assert!(
+            name == current_thread || name.as_ref().is_some_and(|n| n.starts_with("arroy-build-")),
+            "the trees were built in the {name:?} thread"
+        );
+    }
+}
+
+#[test]
+fn split_on_the_first_dimensions() {
+    let handle = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 4);
+    for i in 0..8 {
+        writer
+            .add_item(&mut wtxn, i, &[(i * 3 % 8) as f32, (i * 5 % 7) as f32, 0.5, -0.5])
+            .unwrap();
+    }
+    writer.builder(&mut rng).n_trees(1).split_dimensions(2).build(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    insta::assert_snapshot!(handle, @r###"
+    ==================
+    Dumping index 0
+    Root: Metadata { dimensions: 4, items: RoaringBitmap<[0, 1, 2, 3, 4, 5, 6, 7]>, roots: [4], distance: "euclidean" }
+    Build config: BuildConfig { n_trees: Some(1), split_after: None, split_dimensions: Some(2), reduced_dimensions: None, normal_precision: Full, seed: None, deterministic: false }
+    Version: 1
+    Tree 0: Descendants(Descendants { descendants: [2, 5] })
+    Tree 1: Descendants(Descendants { descendants: [1, 3, 6, 7] })
+    Tree 2: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(0), right: Tree(1), normal: [0.0000, 0.0000] })
+    Tree 3: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(2), right: Item(4), normal: [0.0000, 0.0000] })
+    Tree 4: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(0), right: Tree(3), normal: [0.6356, 0.7720] })
+    Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 0.0000, 0.5000, -0.5000] })
+    Item 1: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [3.0000, 5.0000, 0.5000, -0.5000] })
+    Item 2: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [6.0000, 3.0000, 0.5000, -0.5000] })
+    Item 3: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [1.0000, 1.0000, 0.5000, -0.5000] })
+    Item 4: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [4.0000, 6.0000, 0.5000, -0.5000] })
+    Item 5: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [7.0000, 4.0000, 0.5000, -0.5000] })
+    Item 6: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [2.0000, 2.0000, 0.5000, -0.5000] })
+    Item 7: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [5.0000, 0.0000, 0.5000, -0.5000] })
+    "###);
+
+    // The new items must be inserted with the first dimensions only too
+    let mut wtxn = handle.env.write_txn().unwrap();
+    for i in 8..12 {
+        writer
+            .add_item(&mut wtxn, i, &[(i * 3 % 8) as f32, (i * 5 % 7) as f32, 0.5, -0.5])
+            .unwrap();
+    }
+    writer.builder(&mut rng).build(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    insta::assert_snapshot!(handle, @r###"
+    ==================
+    Dumping index 0
+    Root: Metadata { dimensions: 4, items: RoaringBitmap<[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11]>, roots: [4], distance: "euclidean" }
+    Build config: BuildConfig { n_trees: Some(1), split_after: None, split_dimensions: Some(2), reduced_dimensions: None, normal_precision: Full, seed: None, deterministic: false }
+    Version: 1
+    Tree 0: Descendants(Descendants { descendants: [2, 5, 8] })
+    Tree 1: Descendants(Descendants { descendants: [1, 3, 6, 7] })
+    Tree 2: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(0), right: Tree(1), normal: [0.0000, 0.0000] })
+    Tree 3: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(2), right: Tree(5), normal: [0.0000, 0.0000] })
+    Tree 4: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(0), right: Tree(3), normal: [0.6356, 0.7720] })
+    Tree 5: Descendants(Descendants { descendants: [4, 9, 10, 11] })
+    Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 0.0000, 0.5000, -0.5000] })
+    Item 1: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [3.0000, 5.0000, 0.5000, -0.5000] })
+    Item 2: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [6.0000, 3.0000, 0.5000, -0.5000] })
+    Item 3: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [1.0000, 1.0000, 0.5000, -0.5000] })
+    Item 4: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [4.0000, 6.0000, 0.5000, -0.5000] })
+    Item 5: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [7.0000, 4.0000, 0.5000, -0.5000] })
+    Item 6: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [2.0000, 2.0000, 0.5000, -0.5000] })
+    Item 7: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [5.0000, 0.0000, 0.5000, -0.5000] })
+    Item 8: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 5.0000, 0.5000, -0.5000] })
+    Item 9: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [3.0000, 3.0000, 0.5000, -0.5000] })
+    Item 10: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [6.0000, 1.0000, 0.5000, -0.5000] })
+    Item 11: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [1.0000, 6.0000, 0.5000, -0.5000] })
+    "###);
+}
+
+#[test]
+fn invalid_dimensions_options() {
+    let handle = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 4);
+    for i in 0..8 {
+        writer.add_item(&mut wtxn, i, &[i as f32, 1.0, 0.5, -(i as f32)]).unwrap();
+    }
+
+    let err = writer.builder(&mut rng).split_dimensions(0).build(&mut wtxn).unwrap_err();
+    assert_snapshot!(err, @"Invalid build option: split_dimensions must be in 1..=4 for an index of 4 dimensions, got 0");
+    let err = writer.builder(&mut rng).split_dimensions(5).build(&mut wtxn).unwrap_err();
+    assert_snapshot!(err, @"Invalid build option: split_dimensions must be in 1..=4 for an index of 4 dimensions, got 5");
+    let err = writer.builder(&mut rng).reduce_dimensions(0).build(&mut wtxn).unwrap_err();
+    assert_snapshot!(err, @"Invalid build option: reduce_dimensions must be in 1..4 for an index of 4 dimensions, got 0");
+    let err = writer.builder(&mut rng).reduce_dimensions(4).build(&mut wtxn).unwrap_err();
+    assert_snapshot!(err, @"Invalid build option: reduce_dimensions must be in 1..4 for an index of 4 dimensions, got 4");
+    let rtxn = handle.env.read_txn().unwrap();
+    let err = writer.builder(&mut rng).reduce_dimensions(4).prepare(&rtxn).map(|_| ()).unwrap_err();
+    assert_snapshot!(err, @"Invalid build option: reduce_dimensions must be in 1..4 for an index of 4 dimensions, got 4");
+    drop(rtxn);
+
+    writer.builder(&mut rng).split_dimensions(4).reduce_dimensions(3).build(&mut wtxn).unwrap();
+}
+
+#[test]
+fn reduce_dimensions() {
+    let handle = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 4);
+    for i in 0..8 {
+        writer.add_item(&mut wtxn, i, &[i as f32, (i * 3 % 5) as f32, 0.5, -(i as f32)]).unwrap();
+    }
+    writer.builder(&mut rng).n_trees(1).reduce_dimensions(2).build(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    insta::assert_snapshot!(handle, @r###"
+    ==================
+    Dumping index 0
+    Root: Metadata { dimensions: 4, items: RoaringBitmap<[0, 1, 2, 3, 4, 5, 6, 7]>, roots: [4], distance: "euclidean" }
+    Build config: BuildConfig { n_trees: Some(1), split_after: None, split_dimensions: None, reduced_dimensions: Some(2), normal_precision: Full, seed: None, deterministic: false }
+    Projection { dimensions: 2, source_dimensions: 4, matrix: [0.7071, -0.7071, 0.7071, 0.7071, 0.7071, -0.7071, 0.7071, 0.7071] }
+    Version: 1
+    Tree 0: Descendants(Descendants { descendants: [2, 4, 6] })
+    Tree 1: Descendants(Descendants { descendants: [1, 3, 7] })
+    Tree 2: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(0), right: Tree(1), normal: [0.0000, 0.0000] })
+    Tree 3: Descendants(Descendants { descendants: [0, 5] })
+    Tree 4: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(2), right: Tree(3), normal: [0.7071, 0.7071] })
+    Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 0.0000, 0.5000, -0.0000] })
+    Item 1: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [1.0000, 3.0000, 0.5000, -1.0000] })
+    Item 2: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [2.0000, 1.0000, 0.5000, -2.0000] })
+    Item 3: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [3.0000, 4.0000, 0.5000, -3.0000] })
+    Item 4: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [4.0000, 2.0000, 0.5000, -4.0000] })
+    Item 5: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [5.0000, 0.0000, 0.5000, -5.0000] })
+    Item 6: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [6.0000, 3.0000, 0.5000, -6.0000] })
+    Item 7: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [7.0000, 1.0000, 0.5000, -7.0000] })
+    "###);
+
+    // The projection is kept to insert the new items
+    let mut wtxn = handle.env.write_txn().unwrap();
+    for i in 8..12 {
+        writer.add_item(&mut wtxn, i, &[i as f32, (i * 3 % 5) as f32, 0.5, -(i as f32)]).unwrap();
+    }
+    writer.builder(&mut rng).build(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    insta::assert_snapshot!(handle, @r###"
+    ==================
+    Dumping index 0
+    Root: Metadata { dimensions: 4, items: RoaringBitmap<[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11]>, roots: [4], distance: "euclidean" }
+    Build config: BuildConfig { n_trees: Some(1), split_after: None, split_dimensions: None, reduced_dimensions: Some(2), normal_precision: Full, seed: None, deterministic: false }
+    Projection { dimensions: 2, source_dimensions: 4, matrix: [0.7071, -0.7071, 0.7071, 0.7071, 0.7071, -0.7071, 0.7071, 0.7071] }
+    Version: 1
+    Tree 0: Descendants(Descendants { descendants: [2, 4, 6, 11] })
+    Tree 2: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(0), right: Tree(7), normal: [0.0000, 0.0000] })
+    Tree 3: Descendants(Descendants { descendants: [0, 5, 10] })
+    Tree 4: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(2), right: Tree(3), normal: [0.7071, 0.7071] })
+    Tree 5: Descendants(Descendants { descendants: [1, 3] })
+    Tree 6: Descendants(Descendants { descendants: [7, 8, 9] })
+    Tree 7: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(5), right: Tree(6), normal: [0.0000, 0.0000] })
+    Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 0.0000, 0.5000, -0.0000] })
+    Item 1: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [1.0000, 3.0000, 0.5000, -1.0000] })
+    Item 2: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [2.0000, 1.0000, 0.5000, -2.0000] })
+    Item 3: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [3.0000, 4.0000, 0.5000, -3.0000] })
+    Item 4: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [4.0000, 2.0000, 0.5000, -4.0000] })
+    Item 5: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [5.0000, 0.0000, 0.5000, -5.0000] })
+    Item 6: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [6.0000, 3.0000, 0.5000, -6.0000] })
+    Item 7: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [7.0000, 1.0000, 0.5000, -7.0000] })
+    Item 8: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [8.0000, 4.0000, 0.5000, -8.0000] })
+    Item 9: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [9.0000, 2.0000, 0.5000, -9.0000] })
+    Item 10: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [10.0000, 0.0000, 0.5000, -10.0000] })
+    Item 11: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [11.0000, 3.0000, 0.5000, -11.0000] })
+    "###);
+
+    // Changing the number of dimensions rebuilds all the trees on a new projection
+    let mut wtxn = handle.env.write_txn().unwrap();
+    writer.builder(&mut rng).reduce_dimensions(3).build(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    insta::assert_snapshot!(handle, @r###"
+    ==================
+    Dumping index 0
+    Root: Metadata { dimensions: 4, items: RoaringBitmap<[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11]>, roots: [13], distance: "euclidean" }
+    Build config: BuildConfig { n_trees: Some(1), split_after: None, split_dimensions: None, reduced_dimensions: Some(3), normal_precision: Full, seed: None, deterministic: false }
+    Projection { dimensions: 3, source_dimensions: 4, matrix: [-0.5774, 0.5774, -0.5774, -0.5774, 0.5774, 0.5774, -0.5774, -0.5774, -0.5774, -0.5774, "other ..."] }
+    Version: 1
+    Tree 1: Descendants(Descendants { descendants: [3, 7, 9, 11] })
+    Tree 8: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(8), right: Tree(1), normal: [0.0000, 0.0000, 0.0000] })
+    Tree 9: Descendants(Descendants { descendants: [1, 5, 6, 10] })
+    Tree 10: Descendants(Descendants { descendants: [2, 4] })
+    Tree 11: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(9), right: Tree(10), normal: [0.0000, 0.0000, 0.0000] })
+    Tree 12: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(8), right: Tree(11), normal: [0.0000, 0.0000, 0.0000] })
+    Tree 13: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(0), right: Tree(12), normal: [0.0354, 0.9987, -0.0354] })
+    Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 0.0000, 0.5000, -0.0000] })
+    Item 1: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [1.0000, 3.0000, 0.5000, -1.0000] })
+    Item 2: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [2.0000, 1.0000, 0.5000, -2.0000] })
+    Item 3: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [3.0000, 4.0000, 0.5000, -3.0000] })
+    Item 4: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [4.0000, 2.0000, 0.5000, -4.0000] })
+    Item 5: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [5.0000, 0.0000, 0.5000, -5.0000] })
+    Item 6: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [6.0000, 3.0000, 0.5000, -6.0000] })
+    Item 7: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [7.0000, 1.0000, 0.5000, -7.0000] })
+    Item 8: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [8.0000, 4.0000, 0.5000, -8.0000] })
+    Item 9: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [9.0000, 2.0000, 0.5000, -9.0000] })
+    Item 10: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [10.0000, 0.0000, 0.5000, -10.0000] })
+    Item 11: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [11.0000, 3.0000, 0.5000, -11.0000] })
+    "###);
+}
+
+#[test]
+fn reduce_dimensions_in_multiple_steps() {
+    let handle = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 4);
+    for i in 0..8 {
+        writer.add_item(&mut wtxn, i, &[i as f32, (i * 3 % 5) as f32, 0.5, -(i as f32)]).unwrap();
+    }
+    writer.builder(&mut rng).n_trees(1).reduce_dimensions(2).build(&mut wtxn).unwrap();
+    wtxn.commit().unwrap();
+
+    // The trees built on the new projection are pending until all of them are built
+    let one_tree_per_step = NonZeroUsize::new(1).unwrap();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let finished = writer
+        .builder(&mut rng)
+        .n_trees(2)
+        .reduce_dimensions(3)
+        .build_step(&mut wtxn, one_tree_per_step)
+        .unwrap();
+    assert!(!finished);
+    wtxn.commit().unwrap();
+
+    // The readers must still see the previous tree and its projection
+    let rtxn = handle.env.read_txn().unwrap();
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, handle.database).unwrap();
+    assert_eq!(reader.n_trees(), 1);
+    reader.assert_validity(&rtxn).unwrap();
+    drop(rtxn);
+
+    insta::assert_snapshot!(handle, @r###"
+    ==================
+    Dumping index 0
+    Root: Metadata { dimensions: 4, items: RoaringBitmap<[0, 1, 2, 3, 4, 5, 6, 7]>, roots: [4], distance: "euclidean" }
+    Pending: [8]
+    Pending projection: Some(Projection { dimensions: 3, source_dimensions: 4, matrix: [-0.5774, -0.5774, 0.5774, -0.5774, -0.5774, 0.5774, -0.5774, -0.5774, 0.5774, 0.5774, "other ..."] })
+    Build config: BuildConfig { n_trees: Some(1), split_after: None, split_dimensions: None, reduced_dimensions: Some(2), normal_precision: Full, seed: None, deterministic: false }
+    Projection { dimensions: 2, source_dimensions: 4, matrix: [0.7071, -0.7071, 0.7071, 0.7071, 0.7071, -0.7071, 0.7071, 0.7071] }
+    Version: 1
+    Tree 0: Descendants(Descendants { descendants: [2, 4, 6] })
+    Tree 1: Descendants(Descendants { descendants: [1, 3, 7] })
+    Tree 2: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(0), right: Tree(1), normal: [0.0000, 0.0000] })
+    Tree 3: Descendants(Descendants { descendants: [0, 5] })
+    Tree 4: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(2), right: Tree(3), normal: [0.7071, 0.7071] })
+    Tree 5: Descendants(Descendants { descendants: [1, 2, 3] })
+    Tree 6: Descendants(Descendants { descendants: [4, 5, 6, 7] })
+    Tree 7: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(5), right: Tree(6), normal: [0.0000, 0.0000, 0.0000] })
+    Tree 8: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(0), right: Tree(7), normal: [-0.0785, 0.0785, 0.9938] })
+    Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 0.0000, 0.5000, -0.0000] })
+    Item 1: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [1.0000, 3.0000, 0.5000, -1.0000] })
+    Item 2: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [2.0000, 1.0000, 0.5000, -2.0000] })
+    Item 3: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [3.0000, 4.0000, 0.5000, -3.0000] })
+    Item 4: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [4.0000, 2.0000, 0.5000, -4.0000] })
+    Item 5: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [5.0000, 0.0000, 0.5000, -5.0000] })
+    Item 6: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [6.0000, 3.0000, 0.5000, -6.0000] })
+    Item 7: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [7.0000, 1.0000, 0.5000, -7.0000] })
+    "###);
+
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let finished = writer
+        .builder(&mut rng)
+        .n_trees(2)
+        .reduce_dimensions(3)
+        .build_step(&mut wtxn, one_tree_per_step)
+        .unwrap();
+    assert!(finished);
+    wtxn.commit().unwrap();
+
+    let rtxn = handle.env.read_txn().unwrap();
+    let reader = Reader::<Euclidean>::open(&rtxn, 0, handle.database).unwrap();
+    assert_eq!(reader.n_trees(), 2);
+    reader.assert_validity(&rtxn).unwrap();
+    drop(rtxn);
+
+    insta::assert_snapshot!(handle, @r###"
+    ==================
+    Dumping index 0
+    Root: Metadata { dimensions: 4, items: RoaringBitmap<[0, 1, 2, 3, 4, 5, 6, 7]>, roots: [8, 13], distance: "euclidean" }
+    Build config: BuildConfig { n_trees: Some(2), split_after: None, split_dimensions: None, reduced_dimensions: Some(3), normal_precision: Full, seed: None, deterministic: false }
+    Projection { dimensions: 3, source_dimensions: 4, matrix: [-0.5774, -0.5774, 0.5774, -0.5774, -0.5774, 0.5774, -0.5774, -0.5774, 0.5774, 0.5774, "other ..."] }
+    Version: 1
+    Tree 5: Descendants(Descendants { descendants: [1, 2, 3] })
+    Tree 6: Descendants(Descendants { descendants: [4, 5, 6, 7] })
+    Tree 7: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(5), right: Tree(6), normal: [0.0000, 0.0000, 0.0000] })
+    Tree 8: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(0), right: Tree(7), normal: [-0.0785, 0.0785, 0.9938] })
+    Tree 9: Descendants(Descendants { descendants: [2, 7] })
+    Tree 10: Descendants(Descendants { descendants: [1, 3, 4, 5] })
+    Tree 11: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(10), right: Item(6), normal: [0.0000, 0.0000, 0.0000] })
+    Tree 12: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(9), right: Tree(11), normal: [0.0000, 0.0000, 0.0000] })
+    Tree 13: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(0), right: Tree(12), normal: [-0.0695, 0.0695, 0.9952] })
+    Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 0.0000, 0.5000, -0.0000] })
+    Item 1: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [1.0000, 3.0000, 0.5000, -1.0000] })
+    Item 2: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [2.0000, 1.0000, 0.5000, -2.0000] })
+    Item 3: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [3.0000, 4.0000, 0.5000, -3.0000] })
+    Item 4: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [4.0000, 2.0000, 0.5000, -4.0000] })
+    Item 5: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [5.0000, 0.0000, 0.5000, -5.0000] })
+    Item 6: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [6.0000, 3.0000, 0.5000, -6.0000] })
+    Item 7: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [7.0000, 1.0000, 0.5000, -7.0000] })
+    "###);
+}
+
+#[test]
+fn compact_normals() {
+    let handle = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 3);
+    for i in 0..6 {
+        writer.add_item(&mut wtxn, i, &[i as f32, (i * 3 % 5) as f32, 0.5]).unwrap();
+    }
+    writer
+        .builder(&mut rng)
+        .n_trees(1)
+        .normal_precision(NormalPrecision::F16)
+        .build(&mut wtxn)
+        .unwrap();
+    wtxn.commit().unwrap();
+
+    insta::assert_snapshot!(handle, @r###"
+    ==================
+    Dumping index 0
+    Root: Metadata { dimensions: 3, items: RoaringBitmap<[0, 1, 2, 3, 4, 5]>, roots: [2], distance: "euclidean" }
+    Build config: BuildConfig { n_trees: Some(1), split_after: None, split_dimensions: None, reduced_dimensions: None, normal_precision: F16, seed: None, deterministic: false }
+    Version: 1
+    Tree 0: Descendants(Descendants { descendants: [0, 1, 3] })
+    Tree 1: Descendants(Descendants { descendants: [2, 4, 5] })
+    Tree 2: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(0), right: Tree(1), normal: [0.7988, -0.6016, 0.0000], precision: F16 })
+    Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 0.0000, 0.5000] })
+    Item 1: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [1.0000, 3.0000, 0.5000] })
+    Item 2: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [2.0000, 1.0000, 0.5000] })
+    Item 3: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [3.0000, 4.0000, 0.5000] })
+    Item 4: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [4.0000, 2.0000, 0.5000] })
+    Item 5: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [5.0000, 0.0000, 0.5000] })
+    "###);
+
+    // The existing normals keep their precision
+    let mut wtxn = handle.env.write_txn().unwrap();
+    for i in 6..10 {
+        writer.add_item(&mut wtxn, i, &[i as f32, (i * 3 % 5) as f32, 0.5]).unwrap();
+    }
+    writer
+        .builder(&mut rng)
+        .n_trees(2)
+        .normal_precision(NormalPrecision::Int8)
+        .build(&mut wtxn)
+        .unwrap();
+    wtxn.commit().unwrap();
+
+    insta::assert_snapshot!(handle, @r###"
+    ==================
+    Dumping index 0
+    Root: Metadata { dimensions: 3, items: RoaringBitmap<[0, 1, 2, 3, 4, 5, 6, 7, 8, 9]>, roots: [2, 13], distance: "euclidean" }
+    Build config: BuildConfig { n_trees: Some(2), split_after: None, split_dimensions: None, reduced_dimensions: None, normal_precision: Int8, seed: None, deterministic: false }
+    Version: 1
+    Tree 0: Descendants(Descendants { descendants: [0, 1, 3] })
+    Tree 2: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(0), right: Tree(6), normal: [0.7988, -0.6016, 0.0000], precision: F16 })
+    Tree 3: Descendants(Descendants { descendants: [2, 4, 8] })
+    Tree 4: Descendants(Descendants { descendants: [5, 6, 7] })
+    Tree 5: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(4), right: Item(9), normal: [0.0000, 0.0000, 0.0000], precision: Int8 })
+    Tree 6: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(3), right: Tree(5), normal: [0.0000, 0.0000, 0.0000], precision: Int8 })
+    Tree 7: Descendants(Descendants { descendants: [2, 4, 9] })
+    Tree 8: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(7), right: Item(3), normal: [0.0000, 0.0000, 0.0000], precision: Int8 })
+    Tree 9: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(7), right: Tree(8), normal: [0.0000, 0.0000, 0.0000], precision: Int8 })
+    Tree 10: Descendants(Descendants { descendants: [5, 6, 8] })
+    Tree 11: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(1), right: Tree(10), normal: [0.0000, 0.0000, 0.0000], precision: Int8 })
+    Tree 12: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(9), right: Tree(11), normal: [0.0000, 0.0000, 0.0000], precision: Int8 })
+    Tree 13: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Tree(12), right: Item(0), normal: [-0.9982, -0.0629, 0.0000], precision: Int8 })
+    Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 0.0000, 0.5000] })
+    Item 1: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [1.0000, 3.0000, 0.5000] })
+    Item 2: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [2.0000, 1.0000, 0.5000] })
+    Item 3: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [3.0000, 4.0000, 0.5000] })
+    Item 4: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [4.0000, 2.0000, 0.5000] })
+    Item 5: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [5.0000, 0.0000, 0.5000] })
+    Item 6: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [6.0000, 3.0000, 0.5000] })
+    Item 7: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [7.0000, 1.0000, 0.5000] })
+    Item 8: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [8.0000, 4.0000, 0.5000] })
+    Item 9: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [9.0000, 2.0000, 0.5000] })
+    "###);
+}
+
+#[test]
+fn swap_indexes_larger_than_a_batch() {
+    let handle = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let large = Writer::new(handle.database, 0, 2);
+    for i in 0..3000 {
+        large.add_item(&mut wtxn, i, &[i as f32, 1.0]).unwrap();
+    }
+    large.builder(&mut rng).n_trees(2).build(&mut wtxn).unwrap();
+    let small = Writer::new(handle.database, 1, 2);
+    for i in 1500..1510 {
+        small.add_item(&mut wtxn, i, &[-(i as f32), 2.0]).unwrap();
+    }
+    small.builder(&mut rng).build(&mut wtxn).unwrap();
+    let expected_large = large.iter(&wtxn).unwrap().collect::<crate::Result<Vec<_>>>().unwrap();
+    let expected_small = small.iter(&wtxn).unwrap().collect::<crate::Result<Vec<_>>>().unwrap();
+
+    large.swap_with(&mut wtxn, 1).unwrap();
+    let reader = Reader::<Euclidean>::open(&wtxn, 1, handle.database).unwrap();
+    reader.assert_validity(&wtxn).unwrap();
+    let items = reader.iter(&wtxn).unwrap().collect::<crate::Result<Vec<_>>>().unwrap();
+    assert_eq!(items, expected_large);
+    let reader = Reader::<Euclidean>::open(&wtxn, 0, handle.database).unwrap();
+    reader.assert_validity(&wtxn).unwrap();
+    let items = reader.iter(&wtxn).unwrap().collect::<crate::Result<Vec<_>>>().unwrap();
+    assert_eq!(items, expected_small);
+}
+
+#[test]
+fn copy_move_and_swap_indexes() {
+    let handle = create_database::<Euclidean>();
+    let mut rng = rng();
+    let mut wtxn = handle.env.write_txn().unwrap();
+    let writer = Writer::new(handle.database, 0, 2);
+    writer.add_item(&mut wtxn, 0, &[0.0, 0.0]).unwrap();
+    writer.add_item(&mut wtxn, 1, &[1.0, 1.0]).unwrap();
+    writer.builder(&mut rng).build(&mut wtxn).unwrap();
+    let other = Writer::new(handle.database, 1, 2);
+    other.add_item(&mut wtxn, 10, &[10.0, 10.0]).unwrap();
+    other.builder(&mut rng).build(&mut wtxn).unwrap();
+
+    // The content of the index 1 is replaced
+    writer.copy_to(&mut wtxn, 1).unwrap();
+    writer.copy_to(&mut wtxn, 0).unwrap();
+    wtxn.commit().unwrap();
+    insta::assert_snapshot!(handle, @r###"
+    ==================
+    Dumping index 0
+    Root: Metadata { dimensions: 2, items: RoaringBitmap<[0, 1]>, roots: [0], distance: "euclidean" }
+    Version: 1
+    Tree 0: Descendants(Descendants { descendants: [0, 1] })
+    Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 0.0000] })
+    Item 1: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [1.0000, 1.0000] })
+    ==================
+    Dumping index 1
+    Root: Metadata { dimensions: 2, items: RoaringBitmap<[0, 1]>, roots: [0], distance: "euclidean" }
+    Version: 1
+    Tree 0: Descendants(Descendants { descendants: [0, 1] })
+    Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 0.0000] })
+    Item 1: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [1.0000, 1.0000] })
+    "###);
+
+    let mut wtxn = handle.env.write_txn().unwrap();
+    // The updated items are copied too
+    writer.add_item(&mut wtxn, 2, &[2.0, 2.0]).unwrap();
+    writer.move_to(&mut wtxn, 3).unwrap();
+    let error = Reader::<Euclidean>::open(&wtxn, 3, handle.database).unwrap_err();
+    assert_snapshot!(error, @"The trees have not been built after an update on index 3");
+    let error = Reader::<Euclidean>::open(&wtxn, 0, handle.database).unwrap_err();
+    assert_snapshot!(error, @"Metadata are missing on index 0, You must build your database before attempting to read it");
+
+    let writer = Writer::<Euclidean>::new(handle.database, 3, 2);
+    writer.builder(&mut rng).build(&mut wtxn).unwrap();
+    other.add_item(&mut wtxn, 11, &[11.0, 11.0]).unwrap();
+    other.builder(&mut rng).build(&mut wtxn).unwrap();
+    writer.swap_with(&mut wtxn, 1).unwrap();
+    wtxn.commit().unwrap();
+    insta::assert_snapshot!(handle, @r###"
+    ==================
+    Dumping index 1
+    Root: Metadata { dimensions: 2, items: RoaringBitmap<[0, 1, 2]>, roots: [2], distance: "euclidean" }
+    Version: 1
+    Tree 1: Descendants(Descendants { descendants: [1, 2] })
+    Tree 2: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(0), right: Tree(1), normal: [0.7071, 0.7071] })
+    Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 0.0000] })
+    Item 1: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [1.0000, 1.0000] })
+    Item 2: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [2.0000, 2.0000] })
+    ==================
+    Dumping index 3
+    Root: Metadata { dimensions: 2, items: RoaringBitmap<[0, 1, 11]>, roots: [2], distance: "euclidean" }
+    Version: 1
+    Tree 1: Descendants(Descendants { descendants: [1, 11] })
+    Tree 2: SplitPlaneNormal(SplitPlaneNormal<euclidean> { left: Item(0), right: Tree(1), normal: [0.7071, 0.7071] })
+    Item 0: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [0.0000, 0.0000] })
+    Item 1: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [1.0000, 1.0000] })
+    Item 11: Leaf(Leaf { header: NodeHeaderEuclidean { bias: 0.0 }, vector: [11.0000, 11.0000] })
+    "###);
+}
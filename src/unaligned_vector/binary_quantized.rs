@@ -15,7 +15,9 @@ const QUANTIZED_WORD_BYTES: usize = std::mem::size_of::<QuantizedWord>();
 pub enum BinaryQuantized {}
 
 impl UnalignedVectorCodec for BinaryQuantized {
-    fn from_bytes(bytes: &[u8]) -> Result<Cow<UnalignedVector<Self>>, SizeMismatch> {
+    const WORD_SIZE: usize = QUANTIZED_WORD_BYTES;
+
+    fn from_bytes(bytes: &[u8]) -> Result<Cow<'_, UnalignedVector<Self>>, SizeMismatch> {
         let rem = bytes.len() % QUANTIZED_WORD_BYTES;
         if rem == 0 {
             // safety: `UnalignedVector` is transparent
@@ -68,6 +70,11 @@ impl UnalignedVectorCodec for BinaryQuantized {
         (vec.vector.len() / QUANTIZED_WORD_BYTES) * QUANTIZED_WORD_BITS
     }
 
+    fn prefix(vec: &UnalignedVector<Self>, len: usize) -> &UnalignedVector<Self> {
+        let end = vec.vector.len().min(len.div_ceil(QUANTIZED_WORD_BITS) * QUANTIZED_WORD_BYTES);
+        UnalignedVector::from_bytes_unchecked(&vec.vector[..end])
+    }
+
     fn is_zero(vec: &UnalignedVector<Self>) -> bool {
         vec.as_bytes().iter().all(|b| *b == 0)
     }
@@ -9,8 +9,10 @@ use byteorder::{ByteOrder, NativeEndian};
 use super::{SizeMismatch, UnalignedVector, UnalignedVectorCodec};
 
 impl UnalignedVectorCodec for f32 {
+    const WORD_SIZE: usize = size_of::<f32>();
+
     /// Creates an unaligned slice of f32 wrapper from a slice of bytes.
-    fn from_bytes(bytes: &[u8]) -> Result<Cow<UnalignedVector<Self>>, SizeMismatch> {
+    fn from_bytes(bytes: &[u8]) -> Result<Cow<'_, UnalignedVector<Self>>, SizeMismatch> {
         let rem = bytes.len() % size_of::<f32>();
         if rem == 0 {
             // safety: `UnalignedF32Slice` is transparent
@@ -22,7 +24,7 @@ impl UnalignedVectorCodec for f32 {
 
     /// Creates an unaligned slice of f32 wrapper from a slice of f32.
     /// The slice is already known to be of the right length.
-    fn from_slice(slice: &[f32]) -> Cow<UnalignedVector<Self>> {
+    fn from_slice(slice: &[f32]) -> Cow<'_, UnalignedVector<Self>> {
         Self::from_bytes(cast_slice(slice)).unwrap()
     }
 
@@ -51,6 +53,11 @@ impl UnalignedVectorCodec for f32 {
         vec.vector.len() / size_of::<f32>()
     }
 
+    fn prefix(vec: &UnalignedVector<Self>, len: usize) -> &UnalignedVector<Self> {
+        let end = vec.vector.len().min(len * size_of::<f32>());
+        UnalignedVector::from_bytes_unchecked(&vec.vector[..end])
+    }
+
     fn is_zero(vec: &UnalignedVector<Self>) -> bool {
         vec.iter().all(|v| v == 0.0)
     }
@@ -17,13 +17,16 @@ mod binary_quantized_test;
 
 /// Determine the way the vectors should be read and written from the database
 pub trait UnalignedVectorCodec: std::borrow::ToOwned + Sized {
+    /// The number of bytes of the words the vector is stored in, in the native byte order.
+    const WORD_SIZE: usize;
+
     /// Creates an unaligned vector from a slice of bytes.
     /// Don't allocate.
-    fn from_bytes(bytes: &[u8]) -> Result<Cow<UnalignedVector<Self>>, SizeMismatch>;
+    fn from_bytes(bytes: &[u8]) -> Result<Cow<'_, UnalignedVector<Self>>, SizeMismatch>;
 
     /// Creates an unaligned vector from a slice of f32.
     /// May allocate depending on the codec.
-    fn from_slice(slice: &[f32]) -> Cow<UnalignedVector<Self>>;
+    fn from_slice(slice: &[f32]) -> Cow<'_, UnalignedVector<Self>>;
 
     /// Creates an unaligned slice of f32 wrapper from a slice of f32.
     /// The slice is already known to be of the right length.
@@ -41,6 +44,11 @@ pub trait UnalignedVectorCodec: std::borrow::ToOwned + Sized {
     /// Returns the len of the vector in terms of elements.
     fn len(vec: &UnalignedVector<Self>) -> usize;
 
+    /// Returns the vector restricted to its first `len` elements, or the whole
+    /// vector if it is shorter. Some codecs may keep a few more elements to not
+    /// split their words. Don't allocate.
+    fn prefix(vec: &UnalignedVector<Self>, len: usize) -> &UnalignedVector<Self>;
+
     /// Returns true if all the elements in the vector are equal to 0.
     fn is_zero(vec: &UnalignedVector<Self>) -> bool;
 }
@@ -64,13 +72,13 @@ impl<Codec: UnalignedVectorCodec> UnalignedVector<Codec> {
 
     /// Creates an unaligned vector from a slice of bytes.
     /// Don't allocate.
-    pub fn from_bytes(bytes: &[u8]) -> Result<Cow<UnalignedVector<Codec>>, SizeMismatch> {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Cow<'_, UnalignedVector<Codec>>, SizeMismatch> {
         Codec::from_bytes(bytes)
     }
 
     /// Creates an unaligned vector from a slice of f32.
     /// May allocate depending on the codec.
-    pub fn from_slice(slice: &[f32]) -> Cow<UnalignedVector<Codec>> {
+    pub fn from_slice(slice: &[f32]) -> Cow<'_, UnalignedVector<Codec>> {
         Codec::from_slice(slice)
     }
 
@@ -101,6 +109,11 @@ impl<Codec: UnalignedVectorCodec> UnalignedVector<Codec> {
         Codec::len(self)
     }
 
+    /// Returns the vector restricted to its first `len` elements.
+    pub fn prefix(&self, len: usize) -> &Self {
+        Codec::prefix(self, len)
+    }
+
     /// Creates an unaligned slice of something. It's up to the caller to ensure
     /// it will be used with the same type it was created initially.
     pub(crate) fn from_bytes_unchecked(bytes: &[u8]) -> &Self {
@@ -0,0 +1,226 @@
+use heed::types::{Bytes, DecodeIgnore};
+use heed::{BytesDecode, RoTxn};
+use roaring::RoaringBitmap;
+
+use crate::distance::Distance;
+use crate::error::KeyContext;
+use crate::internals::KeyCodec;
+use crate::node::{Descendants, SplitPlaneNormal};
+use crate::unaligned_vector::UnalignedVector;
+use crate::{
+    Database, ItemId, Key, Node, NodeCodec, NodeId, NodeMode, PendingBuildCodec, Prefix,
+    PrefixCodec, Result,
+};
+
+/// The problems found in an index by [`Reader::verify`](crate::Reader::verify).
+///
+/// The item IDs are the ones given by the user and the tree node IDs are the
+/// internal IDs of the nodes. An index without any problem returns an empty report.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct VerifyReport {
+    /// The roots of the trees that are not made of valid tree nodes
+    /// and must be built again.
+    pub broken_trees: Vec<ItemId>,
+    /// The tree nodes that are not part of any tree.
+    pub orphan_tree_nodes: RoaringBitmap,
+    /// The tree nodes that are linked more than once, in the same tree or in different trees.
+    pub shared_tree_nodes: RoaringBitmap,
+    /// The tree nodes that are linked by a tree but don't exist.
+    pub dangling_tree_nodes: RoaringBitmap,
+    /// The tree nodes that cannot be decoded.
+    pub corrupted_tree_nodes: RoaringBitmap,
+    /// The items of the index that are not reachable from every tree.
+    pub missing_items: RoaringBitmap,
+    /// The items reachable from the trees that are not part of the items of the index.
+    pub unknown_items: RoaringBitmap,
+    /// The items linked by the trees or the metadata that don't exist.
+    pub dangling_items: RoaringBitmap,
+    /// The items that cannot be decoded or whose vector doesn't have the
+    /// dimensions of the index.
+    pub invalid_items: RoaringBitmap,
+}
+
+impl VerifyReport {
+    /// Returns `true` if no problem was found in the index.
+    pub fn is_valid(&self) -> bool {
+        *self == VerifyReport::default()
+    }
+}
+
+/// What was deleted by [`Writer::collect_garbage`](crate::Writer::collect_garbage).
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct GarbageReport {
+    /// The IDs of the tree nodes that were not reachable from any tree.
+    pub deleted_tree_nodes: RoaringBitmap,
+    /// The size of the keys and values that were deleted, in bytes.
+    pub reclaimed_bytes: u64,
+}
+
+/// The result of walking every tree of an index.
+pub(crate) struct Verification {
+    pub report: VerifyReport,
+    /// The roots of the trees without any problem.
+    pub valid_roots: Vec<ItemId>,
+    /// The tree nodes of the trees without any problem.
+    pub valid_tree_nodes: RoaringBitmap,
+}
+
+/// Walks the trees with the given `roots` and the trees of the pending build, if any,
+/// and checks them against the `items` of the index and the nodes of the database.
+pub(crate) fn verify<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+    dimensions: usize,
+    roots: &[ItemId],
+    items: &RoaringBitmap,
+) -> Result<Verification> {
+    let mut report = VerifyReport::default();
+
+    let expected_len = UnalignedVector::<D::VectorCodec>::from_slice(&vec![0.0; dimensions]).len();
+    let mut stored_items = RoaringBitmap::new();
+    for result in database
+        .remap_types::<PrefixCodec, Bytes>()
+        .prefix_iter(rtxn, &Prefix::item(index))?
+        .remap_key_type::<KeyCodec>()
+    {
+        let (key, bytes) = result?;
+        let item = key.node.unwrap_item();
+        stored_items.push(item);
+        match NodeCodec::<D>::bytes_decode(bytes) {
+            Ok(Node::Leaf(leaf)) if leaf.vector.len() == expected_len => (),
+            _ => {
+                report.invalid_items.insert(item);
+            }
+        }
+    }
+
+    let mut stored_tree_nodes = RoaringBitmap::new();
+    for result in database
+        .remap_types::<PrefixCodec, DecodeIgnore>()
+        .prefix_iter(rtxn, &Prefix::tree(index))?
+        .remap_key_type::<KeyCodec>()
+    {
+        let (key, _) = result?;
+        stored_tree_nodes.push(key.node.unwrap_tree());
+    }
+
+    let pending_key = Key::pending_build(index);
+    let pending_roots: Vec<_> = database
+        .remap_data_type::<PendingBuildCodec>()
+        .get(rtxn, &pending_key)
+        .with_key(pending_key)?
+        .map_or_else(Vec::new, |pending| pending.roots.iter().collect());
+
+    let mut linked_tree_nodes = RoaringBitmap::new();
+    let mut valid_roots = Vec::new();
+    let mut valid_tree_nodes = RoaringBitmap::new();
+    let visible_roots = roots.iter().map(|root| (*root, true));
+    let pending_roots = pending_roots.iter().map(|root| (*root, false));
+    for (root, visible) in visible_roots.chain(pending_roots) {
+        let mut tree_nodes = RoaringBitmap::new();
+        let mut tree_items = RoaringBitmap::new();
+        let mut broken = false;
+
+        let mut explore = vec![NodeId::tree(root)];
+        while let Some(node_id) = explore.pop() {
+            match node_id.mode {
+                NodeMode::Item => {
+                    // an item cannot be linked twice in a single tree
+                    broken |= !tree_items.insert(node_id.item);
+                    continue;
+                }
+                NodeMode::Tree => (),
+                NodeMode::Metadata | NodeMode::Updated => {
+                    broken = true;
+                    continue;
+                }
+            }
+
+            let tree_node = node_id.item;
+            if linked_tree_nodes.contains(tree_node) || !tree_nodes.insert(tree_node) {
+                report.shared_tree_nodes.insert(tree_node);
+                broken = true;
+                continue;
+            }
+
+            match database.get(rtxn, &Key::tree(index, tree_node)) {
+                Ok(Some(Node::Descendants(Descendants { descendants }))) => {
+                    broken |= !tree_items.is_disjoint(&descendants);
+                    tree_items |= &*descendants;
+                }
+                Ok(Some(Node::SplitPlaneNormal(SplitPlaneNormal { left, right, .. }))) => {
+                    explore.push(left);
+                    explore.push(right);
+                }
+                Ok(Some(Node::Leaf(_))) | Err(heed::Error::Decoding(_)) => {
+                    report.corrupted_tree_nodes.insert(tree_node);
+                    broken = true;
+                }
+                Ok(None) => {
+                    report.dangling_tree_nodes.insert(tree_node);
+                    broken = true;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        linked_tree_nodes |= &tree_nodes;
+        report.dangling_items |= &tree_items - &stored_items;
+        if visible {
+            report.missing_items |= items - &tree_items;
+            report.unknown_items |= tree_items - items;
+            if broken {
+                report.broken_trees.push(root);
+            } else {
+                valid_roots.push(root);
+                valid_tree_nodes |= tree_nodes;
+            }
+        }
+    }
+
+    report.orphan_tree_nodes = stored_tree_nodes - linked_tree_nodes;
+    report.dangling_items |= items - &stored_items;
+    // The items that don't exist are not reported twice
+    report.missing_items -= &report.dangling_items;
+    report.unknown_items -= &report.dangling_items;
+
+    Ok(Verification { report, valid_roots, valid_tree_nodes })
+}
+
+/// Returns the tree nodes reachable from the given `roots` and from the roots of the pending
+/// build, if any. The nodes that are missing or cannot be decoded are skipped.
+pub(crate) fn reachable_tree_nodes<D: Distance>(
+    rtxn: &RoTxn,
+    database: Database<D>,
+    index: u16,
+    roots: &[ItemId],
+) -> Result<RoaringBitmap> {
+    let pending_key = Key::pending_build(index);
+    let pending_roots: Vec<_> = database
+        .remap_data_type::<PendingBuildCodec>()
+        .get(rtxn, &pending_key)
+        .with_key(pending_key)?
+        .map_or_else(Vec::new, |pending| pending.roots.iter().collect());
+
+    let mut reachable = RoaringBitmap::new();
+    let mut explore: Vec<_> =
+        roots.iter().chain(&pending_roots).map(|&r| NodeId::tree(r)).collect();
+    while let Some(node_id) = explore.pop() {
+        // the shared nodes are only explored once to avoid looping on cycles
+        if node_id.mode != NodeMode::Tree || !reachable.insert(node_id.item) {
+            continue;
+        }
+
+        match database.get(rtxn, &Key::tree(index, node_id.item)) {
+            Ok(Some(Node::SplitPlaneNormal(SplitPlaneNormal { left, right, .. }))) => {
+                explore.push(left);
+                explore.push(right);
+            }
+            Ok(_) | Err(heed::Error::Decoding(_)) => (),
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Ok(reachable)
+}
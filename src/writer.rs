@@ -1,16 +1,25 @@
 use std::any::TypeId;
 use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, Read};
 use std::mem;
-use std::path::PathBuf;
+use std::num::NonZeroUsize;
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
 
 use heed::types::{Bytes, DecodeIgnore, Unit};
-use heed::{MdbError, PutFlags, RoTxn, RwTxn};
+use heed::{BytesDecode, BytesEncode, MdbError, PutFlags, RoTxn, RwTxn};
+use memmap2::Mmap;
 use rand::{Rng, SeedableRng};
-use rayon::iter::repeatn;
+use rayon::iter::repeat_n;
 use rayon::prelude::*;
+use rayon::ThreadPool;
 use roaring::RoaringBitmap;
 
+use crate::annoy::{self, AnnoyDistance};
 use crate::distance::Distance;
+use crate::dump;
+use crate::error::KeyContext;
 use crate::internals::{KeyCodec, Side};
 use crate::item_iter::ItemIter;
 use crate::node::{Descendants, ItemIds, Leaf, SplitPlaneNormal};
@@ -20,12 +29,19 @@ use crate::parallel::{
     TmpNodesReader,
 };
 use crate::reader::item_leaf;
+use crate::roaring::RoaringBitmapCodec;
 use crate::unaligned_vector::UnalignedVector;
+use crate::verify::{reachable_tree_nodes, verify, Verification};
 use crate::{
-    Database, Error, ItemId, Key, Metadata, MetadataCodec, Node, NodeCodec, NodeId, Prefix,
-    PrefixCodec, Result,
+    BuildConfig, BuildConfigCodec, Database, Error, GarbageReport, ItemId, Key, Metadata,
+    MetadataCodec, Node, NodeCodec, NodeId, NormalPrecision, PendingBuild, PendingBuildCodec,
+    Prefix, PrefixCodec, Projection, ProjectionCodec, Result, SearchTuning, SearchTuningCodec,
+    VectorReader, VerifyReport, VersionCodec, FORMAT_VERSION,
 };
 
+/// The number of items projected at once, in parallel, before being written in a temporary file.
+const PROJECTION_BATCH_SIZE: usize = 4096;
+
 /// The options available when building the arroy database.
 pub struct ArroyBuilder<'a, D: Distance, R: Rng + SeedableRng> {
     writer: &'a Writer<D>,
@@ -37,12 +53,69 @@ pub struct ArroyBuilder<'a, D: Distance, R: Rng + SeedableRng> {
 struct BuildOption<'a> {
     n_trees: Option<usize>,
     split_after: Option<usize>,
+    split_dimensions: Option<usize>,
+    reduced_dimensions: Option<usize>,
+    normal_precision: Option<NormalPrecision>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+    thread_pool: Option<&'a ThreadPool>,
     cancel: Box<dyn Fn() -> bool + 'a + Sync + Send>,
 }
 
 impl Default for BuildOption<'_> {
     fn default() -> Self {
-        Self { n_trees: None, split_after: None, cancel: Box::new(|| false) }
+        Self {
+            n_trees: None,
+            split_after: None,
+            split_dimensions: None,
+            reduced_dimensions: None,
+            normal_precision: None,
+            seed: None,
+            deterministic: None,
+            thread_pool: None,
+            cancel: Box::new(|| false),
+        }
+    }
+}
+
+impl BuildOption<'_> {
+    /// Uses the options stored by the previous build for the ones that are not set.
+    fn or_stored(&mut self, stored: BuildConfig) {
+        self.n_trees = self.n_trees.or(stored.n_trees);
+        self.split_after = self.split_after.or(stored.split_after);
+        self.split_dimensions = self.split_dimensions.or(stored.split_dimensions);
+        self.reduced_dimensions = self.reduced_dimensions.or(stored.reduced_dimensions);
+        self.normal_precision = self.normal_precision.or(Some(stored.normal_precision));
+        self.seed = self.seed.or(stored.seed);
+        self.deterministic = self.deterministic.or(Some(stored.deterministic));
+    }
+
+    fn config(&self) -> BuildConfig {
+        BuildConfig {
+            n_trees: self.n_trees,
+            split_after: self.split_after,
+            split_dimensions: self.split_dimensions,
+            reduced_dimensions: self.reduced_dimensions,
+            normal_precision: self.normal_precision.unwrap_or_default(),
+            seed: self.seed,
+            deterministic: self.is_deterministic(),
+        }
+    }
+
+    fn is_deterministic(&self) -> bool {
+        self.deterministic.unwrap_or(false)
+    }
+
+    /// Runs the operation in the thread pool of the options, or in the current one if none was set.
+    fn install<OP, T>(&self, op: OP) -> T
+    where
+        OP: FnOnce() -> T + Send,
+        T: Send,
+    {
+        match self.thread_pool {
+            Some(thread_pool) => thread_pool.install(op),
+            None => op(),
+        }
     }
 }
 
@@ -85,6 +158,149 @@ impl<'a, D: Distance, R: Rng + SeedableRng> ArroyBuilder<'a, D, R> {
         self
     }
 
+    /// Computes the split planes on the first `dimensions` dimensions of the vectors only.
+    ///
+    /// It's useful with Matryoshka embeddings, where the first dimensions already carry
+    /// most of the meaning: the split planes are smaller and faster to traverse while
+    /// the full vectors are still stored and used to rank the candidates.
+    ///
+    /// The trees that already exist keep their split planes, the search works on any
+    /// mix of them. Binary quantized vectors are restricted by words of 64 dimensions.
+    ///
+    /// The build returns an [`Error::InvalidBuildOption`] if the number of dimensions
+    /// is zero or greater than the dimensions of the index.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use arroy::{Writer, distances::Euclidean};
+    /// # let (writer, wtxn): (Writer<Euclidean>, heed::RwTxn) = todo!();
+    /// use rand::rngs::StdRng;
+    /// use rand::SeedableRng;
+    /// let mut rng = StdRng::seed_from_u64(92);
+    /// writer.builder(&mut rng).split_dimensions(256).build(&mut wtxn);
+    /// ```
+    pub fn split_dimensions(&mut self, dimensions: usize) -> &mut Self {
+        self.inner.split_dimensions = Some(dimensions);
+        self
+    }
+
+    /// Computes the split planes on the vectors projected to `dimensions` dimensions
+    /// by a random projection that is generated once and stored in the index.
+    ///
+    /// The split planes are smaller and faster to traverse, while the original vectors
+    /// are still stored and used to rank the candidates. Projecting the items costs
+    /// some time at every build, and changing the number of projected dimensions, or the
+    /// ones of the index, generates a new projection and rebuilds all the trees.
+    ///
+    /// The build returns an [`Error::InvalidBuildOption`] if the number of dimensions
+    /// is zero or not smaller than the dimensions of the index.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use arroy::{Writer, distances::Euclidean};
+    /// # let (writer, wtxn): (Writer<Euclidean>, heed::RwTxn) = todo!();
+    /// use rand::rngs::StdRng;
+    /// use rand::SeedableRng;
+    /// let mut rng = StdRng::seed_from_u64(92);
+    /// writer.builder(&mut rng).reduce_dimensions(128).build(&mut wtxn);
+    /// ```
+    pub fn reduce_dimensions(&mut self, dimensions: usize) -> &mut Self {
+        self.inner.reduced_dimensions = Some(dimensions);
+        self
+    }
+
+    /// Stores the normals of the split planes with a lower precision than the items,
+    /// which makes the trees a lot smaller at the cost of slightly less precise splits.
+    ///
+    /// Only the new split planes use the precision, the existing ones are kept as is.
+    /// It has no effect on the binary quantized distances as their normals are already
+    /// smaller than the compact ones.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use arroy::{NormalPrecision, Writer, distances::Euclidean};
+    /// # let (writer, wtxn): (Writer<Euclidean>, heed::RwTxn) = todo!();
+    /// use rand::rngs::StdRng;
+    /// use rand::SeedableRng;
+    /// let mut rng = StdRng::seed_from_u64(92);
+    /// writer.builder(&mut rng).normal_precision(NormalPrecision::F16).build(&mut wtxn);
+    /// ```
+    pub fn normal_precision(&mut self, precision: NormalPrecision) -> &mut Self {
+        self.inner.normal_precision = Some(precision);
+        self
+    }
+
+    /// Build the trees with a random number generator initialized from this seed
+    /// instead of the one given to [`Writer::builder`].
+    ///
+    /// The seed, like the other options, is stored in the database and is used
+    /// by the following builds of the index unless they specify another one.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use arroy::{Writer, distances::Euclidean};
+    /// # let (writer, wtxn): (Writer<Euclidean>, heed::RwTxn) = todo!();
+    /// use rand::rngs::StdRng;
+    /// use rand::SeedableRng;
+    /// let mut rng = StdRng::seed_from_u64(92);
+    /// writer.builder(&mut rng).seed(42).build(&mut wtxn);
+    /// ```
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.inner.seed = Some(seed);
+        self
+    }
+
+    /// Generates the exact same database for the same items and random number generator,
+    /// whatever the number of threads used to build it.
+    ///
+    /// By default, the threads share the tree node IDs and, when the number of trees is
+    /// not specified, stop generating trees as soon as enough tree nodes are generated.
+    /// Both depend on the scheduling of the threads. In deterministic mode, every tree
+    /// is generated with its own IDs that are remapped in the order of the trees once
+    /// they are all generated. It's slightly slower and uses more temporary disk space.
+    ///
+    /// It's best used with a [`Self::seed`] which is stored in the database along with this option.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use arroy::{Writer, distances::Euclidean};
+    /// # let (writer, wtxn): (Writer<Euclidean>, heed::RwTxn) = todo!();
+    /// use rand::rngs::StdRng;
+    /// use rand::SeedableRng;
+    /// let mut rng = StdRng::seed_from_u64(92);
+    /// writer.builder(&mut rng).seed(42).deterministic(true).build(&mut wtxn);
+    /// ```
+    pub fn deterministic(&mut self, deterministic: bool) -> &mut Self {
+        self.inner.deterministic = Some(deterministic);
+        self
+    }
+
+    /// Generates the trees in the given thread pool instead of the global or current one.
+    ///
+    /// It makes it possible to isolate the threads used to build the trees from
+    /// the ones used by the rest of the application, for instance to serve queries.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use arroy::{Writer, distances::Euclidean};
+    /// # let (writer, wtxn): (Writer<Euclidean>, heed::RwTxn) = todo!();
+    /// use rand::rngs::StdRng;
+    /// use rand::SeedableRng;
+    /// let thread_pool = rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+    /// let mut rng = StdRng::seed_from_u64(92);
+    /// writer.builder(&mut rng).thread_pool(&thread_pool).build(&mut wtxn);
+    /// ```
+    pub fn thread_pool(&mut self, thread_pool: &'a ThreadPool) -> &mut Self {
+        self.inner.thread_pool = Some(thread_pool);
+        self
+    }
+
     /// Provide a closure that can cancel the indexing process early if needed.
     /// There is no guarantee on when the process is going to cancel itself, but
     /// arroy will try to stop as soon as possible once the closure returns `true`.
@@ -120,13 +336,42 @@ impl<'a, D: Distance, R: Rng + SeedableRng> ArroyBuilder<'a, D, R> {
         self
     }
 
+    /// Fills the options that are not set with the ones stored by the previous build
+    /// and returns the random number generator to use if a seed is set.
+    fn resolve_options(&mut self, rtxn: &RoTxn) -> Result<Option<R>> {
+        self.inner.or_stored(self.writer.build_config(rtxn)?);
+
+        let dimensions = self.writer.dimensions;
+        let invalid = |option, value, range| Error::InvalidBuildOption {
+            reason: format!(
+                "{option} must be in {range} for an index of {dimensions} dimensions, got {value}"
+            ),
+        };
+        match self.inner.split_dimensions {
+            Some(split) if !(1..=dimensions).contains(&split) => {
+                return Err(invalid("split_dimensions", split, format!("1..={dimensions}")))
+            }
+            _ => (),
+        }
+        match self.inner.reduced_dimensions {
+            Some(reduced) if !(1..dimensions).contains(&reduced) => {
+                return Err(invalid("reduce_dimensions", reduced, format!("1..{dimensions}")))
+            }
+            _ => (),
+        }
+
+        match self.inner.seed {
+            Some(seed) => self.writer.seeded_rng(rtxn, seed).map(Some),
+            None => Ok(None),
+        }
+    }
+
     /// Generates a forest of `n_trees` trees.
     ///
     /// More trees give higher precision when querying at the cost of more disk usage.
     ///
-    /// This function is using rayon to spawn threads. It can be configured
-    /// by using the [`rayon::ThreadPoolBuilder`] and the
-    /// [`rayon::ThreadPool::install`].
+    /// This function is using rayon to spawn threads. The thread pool
+    /// to use can be configured with [`Self::thread_pool`].
     ///
     /// # Example
     ///
@@ -139,8 +384,99 @@ impl<'a, D: Distance, R: Rng + SeedableRng> ArroyBuilder<'a, D, R> {
     /// writer.builder(&mut rng).build(&mut wtxn);
     /// ```
     pub fn build(&mut self, wtxn: &mut RwTxn) -> Result<()> {
-        self.writer.build(wtxn, self.rng, &self.inner)
+        self.writer.upgrade(wtxn)?;
+        let mut seeded = self.resolve_options(wtxn)?;
+        let rng = seeded.as_mut().unwrap_or(&mut *self.rng);
+        self.writer.build(wtxn, rng, &self.inner)
+    }
+
+    /// Generates at most `max_trees` new trees and checkpoints them into the database.
+    /// Returns `true` once the build is finished, `false` if it must be called again.
+    ///
+    /// The trees are stored in a pending state that is not visible to the [`Reader`](crate::Reader)s,
+    /// which continue to use the previous trees. Every call resumes the build from the trees
+    /// committed by the previous calls, making it possible to commit between steps and to not
+    /// lose the work done when the build is cancelled or the process crashes. Once the last
+    /// trees are generated, the updated items are inserted into the existing trees and all the
+    /// trees are made visible at once.
+    ///
+    /// The pending trees are discarded if the items are modified between two steps
+    /// or if the index is built with [`Self::build`] in the meantime.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use arroy::{Writer, distances::Euclidean};
+    /// # let (writer, env): (Writer<Euclidean>, heed::Env) = todo!();
+    /// use std::num::NonZeroUsize;
+    /// use rand::rngs::StdRng;
+    /// use rand::SeedableRng;
+    /// let mut rng = StdRng::seed_from_u64(92);
+    /// let trees_per_step = NonZeroUsize::new(10).unwrap();
+    /// loop {
+    ///     let mut wtxn = env.write_txn()?;
+    ///     let finished = writer.builder(&mut rng).n_trees(100).build_step(&mut wtxn, trees_per_step)?;
+    ///     wtxn.commit()?;
+    ///     if finished {
+    ///         break;
+    ///     }
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn build_step(&mut self, wtxn: &mut RwTxn, max_trees: NonZeroUsize) -> Result<bool> {
+        self.writer.upgrade(wtxn)?;
+        let mut seeded = self.resolve_options(wtxn)?;
+        let rng = seeded.as_mut().unwrap_or(&mut *self.rng);
+        self.writer.build_step(wtxn, rng, &self.inner, max_trees.get())
     }
+
+    /// Builds the trees by only reading the database, without blocking the other writers.
+    ///
+    /// The generated tree nodes are stored in temporary files and must be written into the
+    /// database in a short write transaction with [`PreparedBuild::apply`]. The read transaction
+    /// can be dropped as soon as this function returns.
+    ///
+    /// Contrary to [`Self::build`], the items are not preprocessed before building the trees.
+    /// When using a distance that needs it, like the [`DotProduct`](crate::distances::DotProduct),
+    /// [`Writer::preprocess`] must be called and committed before preparing the build,
+    /// an [`Error::NeedPreprocess`] is returned otherwise.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use arroy::{Writer, distances::Euclidean};
+    /// # let (writer, rtxn): (Writer<Euclidean>, heed::RoTxn) = todo!();
+    /// use rand::rngs::StdRng;
+    /// use rand::SeedableRng;
+    /// let mut rng = StdRng::seed_from_u64(92);
+    /// let mut builder = writer.builder(&mut rng);
+    /// let prepared = builder.n_trees(10).prepare(&rtxn)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn prepare(&mut self, rtxn: &RoTxn) -> Result<PreparedBuild<'_, D>> {
+        self.writer.check_version(rtxn)?;
+        if !self.writer.is_preprocessed(rtxn)? {
+            return Err(Error::NeedPreprocess(self.writer.index));
+        }
+        let mut seeded = self.resolve_options(rtxn)?;
+        let rng = seeded.as_mut().unwrap_or(&mut *self.rng);
+        let snapshot = self.writer.snapshot(rtxn)?;
+        let built = self.writer.build_from_snapshot(rtxn, rng, &self.inner)?;
+        Ok(PreparedBuild { writer: self.writer, options: &self.inner, snapshot, built })
+    }
+}
+
+/// The way the vectors are converted by [`Writer::prepare_changing_dimensions`].
+#[derive(Debug, Clone, Copy)]
+pub enum DimensionChange<'a> {
+    /// Keeps the first dimensions of the vectors and drops the others.
+    /// It's the way to reduce the size of Matryoshka embeddings.
+    Truncate,
+    /// Appends zeros to the vectors up to the new number of dimensions.
+    ZeroPad,
+    /// Multiplies the vectors by a linear projection matrix stored in row-major
+    /// order, with one row of the old number of dimensions per new dimension.
+    Project(&'a [f32]),
 }
 
 /// A writer to store new items, remove existing ones,
@@ -148,8 +484,8 @@ impl<'a, D: Distance, R: Rng + SeedableRng> ArroyBuilder<'a, D, R> {
 /// neighbors to items or vectors.
 #[derive(Debug)]
 pub struct Writer<D: Distance> {
-    database: Database<D>,
-    index: u16,
+    pub(crate) database: Database<D>,
+    pub(crate) index: u16,
     dimensions: usize,
     /// The folder in which tempfile will write its temporary files.
     tmpdir: Option<PathBuf>,
@@ -197,6 +533,112 @@ impl<D: Distance> Writer<D> {
         Ok(Writer { database: database.remap_data_type(), index, dimensions, tmpdir })
     }
 
+    /// Returns a writer after having deleted the tree nodes and rewrote all the items
+    /// to the new number of dimensions. All the items are marked as updated and the
+    /// index must be built again before being read.
+    ///
+    /// The [`split_dimensions`](ArroyBuilder::split_dimensions) and
+    /// [`reduce_dimensions`](ArroyBuilder::reduce_dimensions) options stored by the
+    /// previous builds are forgotten as they depend on the number of dimensions.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use arroy::{DimensionChange, Writer, distances::Euclidean};
+    /// # let (writer, mut wtxn): (Writer<Euclidean>, heed::RwTxn) = todo!();
+    /// // Only keep the first 256 dimensions of Matryoshka embeddings
+    /// let writer = writer.prepare_changing_dimensions(&mut wtxn, 256, DimensionChange::Truncate)?;
+    /// # Ok::<(), arroy::Error>(())
+    /// ```
+    pub fn prepare_changing_dimensions(
+        self,
+        wtxn: &mut RwTxn,
+        dimensions: usize,
+        change: DimensionChange,
+    ) -> Result<Writer<D>> {
+        let invalid = |reason| Error::InvalidDimensionChange {
+            from: self.dimensions,
+            to: dimensions,
+            reason,
+        };
+        match change {
+            _ if dimensions == 0 => {
+                return Err(invalid("the vectors must have at least one dimension"))
+            }
+            DimensionChange::Truncate if dimensions > self.dimensions => {
+                return Err(invalid("vectors cannot be truncated to more dimensions"))
+            }
+            DimensionChange::ZeroPad if dimensions < self.dimensions => {
+                return Err(invalid("vectors cannot be padded to fewer dimensions"))
+            }
+            DimensionChange::Truncate | DimensionChange::ZeroPad
+                if dimensions == self.dimensions =>
+            {
+                return Ok(self)
+            }
+            DimensionChange::Project(matrix) if matrix.len() != dimensions * self.dimensions => {
+                return Err(invalid("the projection matrix must be of size new x old dimensions"))
+            }
+            _ => (),
+        }
+
+        clear_tree_nodes(wtxn, self.database, self.index)?;
+        let config = BuildConfig {
+            split_dimensions: None,
+            reduced_dimensions: None,
+            ..self.build_config(wtxn)?
+        };
+        self.write_build_config(wtxn, &config)?;
+
+        let mut cursor = self
+            .database
+            .remap_key_type::<PrefixCodec>()
+            .prefix_iter_mut(wtxn, &Prefix::item(self.index))?
+            .remap_key_type::<KeyCodec>();
+        while let Some((item_id, node)) = cursor.next().transpose()? {
+            match node {
+                Node::Leaf(Leaf { header: _, vector }) => {
+                    let mut vector = vector.to_vec();
+                    vector.truncate(self.dimensions);
+                    let vector = match change {
+                        DimensionChange::Truncate | DimensionChange::ZeroPad => {
+                            vector.resize(dimensions, 0.0);
+                            vector
+                        }
+                        DimensionChange::Project(matrix) => matrix
+                            .chunks_exact(self.dimensions)
+                            .map(|row| row.iter().zip(&vector).map(|(m, v)| m * v).sum())
+                            .collect(),
+                    };
+                    let vector = UnalignedVector::from_vec(vector);
+                    let new_leaf = Node::Leaf(Leaf { header: D::new_header(&vector), vector });
+                    unsafe {
+                        // safety: We do not keep a reference to the current value, we own it.
+                        cursor.put_current_with_options::<NodeCodec<D>>(
+                            PutFlags::empty(),
+                            &item_id,
+                            &new_leaf,
+                        )?
+                    };
+                }
+                Node::Descendants(_) | Node::SplitPlaneNormal(_) => {
+                    return Err(Error::corrupted_database(item_id, "an item is not a leaf"))
+                }
+            }
+        }
+        drop(cursor);
+
+        for item in self.item_indices(wtxn)? {
+            self.database.remap_data_type::<Unit>().put(
+                wtxn,
+                &Key::updated(self.index, item),
+                &(),
+            )?;
+        }
+
+        Ok(Writer { dimensions, ..self })
+    }
+
     /// Specifies the folder in which arroy will write temporary files when building the tree.
     ///
     /// If specified it uses the [`tempfile::tempfile_in`] function, otherwise it will
@@ -235,6 +677,126 @@ impl<D: Distance> Writer<D> {
                 .is_none())
     }
 
+    /// Stores the `search_k` tuned with [`Reader::tune_search_k`](crate::Reader::tune_search_k) in the index, the queries
+    /// that don't set a `search_k` use it from now on. `None` removes it.
+    pub fn set_search_tuning(&self, wtxn: &mut RwTxn, tuning: Option<SearchTuning>) -> Result<()> {
+        let key = Key::search_tuning(self.index);
+        match tuning {
+            Some(tuning) => {
+                self.database.remap_data_type::<SearchTuningCodec>().put(wtxn, &key, &tuning)?
+            }
+            None => {
+                self.database.remap_data_type::<DecodeIgnore>().delete(wtxn, &key)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the version of the format the index is written in.
+    ///
+    /// The indexes that were never built and the ones written
+    /// before the format was versioned are in the version `0`.
+    pub fn format_version(&self, rtxn: &RoTxn) -> Result<u32> {
+        let key = Key::version(self.index);
+        let version =
+            self.database.remap_data_type::<VersionCodec>().get(rtxn, &key).with_key(key)?;
+        Ok(version.unwrap_or(0))
+    }
+
+    /// Returns `true` if the index was written by an older version of arroy
+    /// and must be migrated with [`Self::upgrade`] before being built again.
+    pub fn need_upgrade(&self, rtxn: &RoTxn) -> Result<bool> {
+        if self.format_version(rtxn)? >= FORMAT_VERSION {
+            return Ok(false);
+        }
+
+        let database = self.database.remap_data_type::<DecodeIgnore>();
+        Ok(database.get(rtxn, &Key::metadata(self.index))?.is_some()
+            || database.get(rtxn, &Key::legacy_updated_items(self.index))?.is_some())
+    }
+
+    /// Migrates an index written by an older version of arroy to the current format, in place.
+    ///
+    /// It's a no-op for the indexes already in the current format and it's done automatically
+    /// by [`ArroyBuilder::build`] and [`ArroyBuilder::build_step`]. The indexes written by a
+    /// newer version of arroy cannot be downgraded.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use arroy::{Writer, distances::Euclidean};
+    /// # let (writer, env): (Writer<Euclidean>, heed::Env) = todo!();
+    /// let mut wtxn = env.write_txn()?;
+    /// if writer.need_upgrade(&wtxn)? {
+    ///     writer.upgrade(&mut wtxn)?;
+    /// }
+    /// wtxn.commit()?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn upgrade(&self, wtxn: &mut RwTxn) -> Result<()> {
+        let version = self.format_version(wtxn)?;
+        if version > FORMAT_VERSION {
+            return Err(Error::UnsupportedVersion {
+                index: self.index,
+                version,
+                supported: FORMAT_VERSION,
+            });
+        }
+
+        if version < 1 {
+            log::debug!("upgrading the index {} from the unversioned format...", self.index);
+            self.upgrade_legacy_updated_items(wtxn)?;
+        }
+
+        // Like the metadata, the version is only written by the builds
+        if self
+            .database
+            .remap_data_type::<DecodeIgnore>()
+            .get(wtxn, &Key::metadata(self.index))?
+            .is_some()
+        {
+            self.database.remap_data_type::<VersionCodec>().put(
+                wtxn,
+                &Key::version(self.index),
+                &FORMAT_VERSION,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// The versions of arroy preceding the format version stored the updated items
+    /// in a single bitmap, we move them to their own keys like the builds expect.
+    fn upgrade_legacy_updated_items(&self, wtxn: &mut RwTxn) -> Result<()> {
+        let key = Key::legacy_updated_items(self.index);
+        let updated =
+            self.database.remap_data_type::<RoaringBitmapCodec>().get(wtxn, &key).with_key(key)?;
+        if let Some(updated) = updated {
+            for item in &updated {
+                self.database.remap_data_type::<Unit>().put(
+                    wtxn,
+                    &Key::updated(self.index, item),
+                    &(),
+                )?;
+            }
+            self.database.delete(wtxn, &key)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns an error if the index must be upgraded, or cannot be, before being built.
+    fn check_version(&self, rtxn: &RoTxn) -> Result<()> {
+        let version = self.format_version(rtxn)?;
+        if version > FORMAT_VERSION {
+            Err(Error::UnsupportedVersion { index: self.index, version, supported: FORMAT_VERSION })
+        } else if self.need_upgrade(rtxn)? {
+            Err(Error::NeedUpgrade(self.index))
+        } else {
+            Ok(())
+        }
+    }
+
     /// Returns `true` if the database contains the given item.
     pub fn contains_item(&self, rtxn: &RoTxn, item: ItemId) -> Result<bool> {
         self.database
@@ -299,6 +861,49 @@ impl<D: Distance> Writer<D> {
         Ok(())
     }
 
+    /// Adds the vectors of a dataset file with the item IDs `first_id`, `first_id + 1`, etc.
+    /// and returns the number of vectors added. The vectors are read and written one at a
+    /// time and appended to the index when their IDs are higher than the existing ones.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use arroy::{Writer, VectorReader, distances::Euclidean};
+    /// # let (writer, mut wtxn): (Writer<Euclidean>, heed::RwTxn) = todo!();
+    /// let vectors = VectorReader::open("sift_base.fvecs")?;
+    /// let count = writer.import_vectors(&mut wtxn, vectors, 0)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn import_vectors<R: Read>(
+        &self,
+        wtxn: &mut RwTxn,
+        mut vectors: VectorReader<R>,
+        first_id: ItemId,
+    ) -> Result<u32> {
+        let mut vector = Vec::new();
+        let mut count = 0;
+        let mut append = true;
+        while vectors.read_vector(&mut vector)? {
+            let item = first_id.checked_add(count).ok_or_else(|| Error::InvalidVectorFile {
+                reason: format!("the item IDs of the vectors must be lower than {}", ItemId::MAX),
+            })?;
+            if append {
+                match self.append_item(wtxn, item, &vector) {
+                    Ok(()) => (),
+                    Err(Error::InvalidItemAppend) => append = false,
+                    Err(e) => return Err(e),
+                }
+            }
+            if !append {
+                self.add_item(wtxn, item, &vector)?;
+            }
+            count += 1;
+        }
+
+        log::debug!("imported {count} vectors of the {:?} file", vectors.format());
+        Ok(count)
+    }
+
     /// Deletes an item stored in this database and returns `true` if it existed.
     pub fn del_item(&self, wtxn: &mut RwTxn, item: ItemId) -> Result<bool> {
         if self.database.delete(wtxn, &Key::item(self.index, item))? {
@@ -314,6 +919,34 @@ impl<D: Distance> Writer<D> {
         }
     }
 
+    /// Deletes all the items stored in this database that are part of the given bitmap
+    /// and returns the number of items that really existed.
+    ///
+    /// Consecutive item ids are deleted with a single range deletion which makes it
+    /// much faster than calling [`Self::del_item`] for every one of them.
+    pub fn del_items(&self, wtxn: &mut RwTxn, items: &RoaringBitmap) -> Result<u64> {
+        let mut deleted = RoaringBitmap::new();
+
+        for (start, end) in consecutive_ranges(items) {
+            let range = Key::item(self.index, start)..=Key::item(self.index, end);
+            for result in self.database.remap_data_type::<DecodeIgnore>().range(wtxn, &range)? {
+                let (key, _) = result?;
+                deleted.push(key.node.unwrap_item());
+            }
+            self.database.remap_data_type::<DecodeIgnore>().delete_range(wtxn, &range)?;
+        }
+
+        for item in &deleted {
+            self.database.remap_data_type::<Unit>().put(
+                wtxn,
+                &Key::updated(self.index, item),
+                &(),
+            )?;
+        }
+
+        Ok(deleted.len())
+    }
+
     /// Removes everything in the database, user items and internal tree nodes.
     pub fn clear(&self, wtxn: &mut RwTxn) -> Result<()> {
         let mut cursor = self
@@ -330,6 +963,436 @@ impl<D: Distance> Writer<D> {
         Ok(())
     }
 
+    /// Replaces the content of the index `to` by a copy of the items, the trees and the
+    /// metadata of this index. Nothing is done if `to` is the index of this writer.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use arroy::{Writer, distances::Euclidean};
+    /// # let (writer, mut wtxn): (Writer<Euclidean>, heed::RwTxn) = todo!();
+    /// // Keep a backup of the index before updating it
+    /// writer.copy_to(&mut wtxn, 42)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn copy_to(&self, wtxn: &mut RwTxn, to: u16) -> Result<()> {
+        if to == self.index {
+            return Ok(());
+        }
+        Writer::<D>::new(self.database, to, self.dimensions).clear(wtxn)?;
+        let count = self.copy_values(wtxn, self.index, to)?;
+        log::debug!("copied {count} values from the index {} to the index {to}", self.index);
+        Ok(())
+    }
+
+    /// Moves the index under the index `to`, replacing its content. This index is empty afterward.
+    /// Nothing is done if `to` is the index of this writer.
+    pub fn move_to(&self, wtxn: &mut RwTxn, to: u16) -> Result<()> {
+        if to == self.index {
+            return Ok(());
+        }
+        self.copy_to(wtxn, to)?;
+        self.clear(wtxn)
+    }
+
+    /// Swaps the content of this index and of the index `other`.
+    ///
+    /// The readers opened after the transaction is committed see the other index, making it
+    /// possible to build a new version of an index under a spare index and to make it live at once.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use arroy::{Writer, distances::Euclidean};
+    /// # let (env, database): (heed::Env, arroy::Database<Euclidean>) = todo!();
+    /// # let mut rng: rand::rngs::StdRng = todo!();
+    /// let live = Writer::new(database, 0, 768);
+    /// let mut wtxn = env.write_txn()?;
+    /// // The new version of the index is built under the index 1...
+    /// let spare = Writer::new(database, 1, 768);
+    /// spare.builder(&mut rng).build(&mut wtxn)?;
+    /// // ...and replaces the live index 0, which is kept under the index 1
+    /// live.swap_with(&mut wtxn, 1)?;
+    /// wtxn.commit()?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn swap_with(&self, wtxn: &mut RwTxn, other: u16) -> Result<()> {
+        if other == self.index {
+            return Ok(());
+        }
+
+        let count = self.swap_values(wtxn, self.index, other)?;
+        log::debug!("swapped the {count} values of the indexes {} and {other}", self.index);
+        Ok(())
+    }
+
+    /// Swaps the values of the indexes `a` and `b` and returns their number. Both indexes
+    /// are read in batches covering the same range of nodes that are swapped at once.
+    fn swap_values(&self, wtxn: &mut RwTxn, a: u16, b: u16) -> Result<u64> {
+        const BATCH_SIZE: usize = 1024;
+
+        // The nodes after `start` and up to `end`, or to the last item, of an index
+        let range = |index: u16, start: Option<NodeId>, end: Option<NodeId>| {
+            let start = match start {
+                Some(node) => Bound::Excluded(Key::new(index, node)),
+                None => Bound::Included(Key::metadata(index)),
+            };
+            let end = match end {
+                Some(node) => Bound::Included(Key::new(index, node)),
+                None => Bound::Included(Key::item(index, ItemId::MAX)),
+            };
+            (start, end)
+        };
+
+        let database = self.database.remap_data_type::<Bytes>();
+        let mut count = 0;
+        let mut batches: [Vec<(NodeId, Vec<u8>)>; 2] =
+            [Vec::with_capacity(BATCH_SIZE), Vec::with_capacity(BATCH_SIZE)];
+        let mut start = None;
+        loop {
+            // The nodes following a full batch were not read yet, we stop at the smallest last one
+            let mut end: Option<NodeId> = None;
+            for (index, batch) in [a, b].into_iter().zip(&mut batches) {
+                for result in database.range(wtxn, &range(index, start, None))?.take(BATCH_SIZE) {
+                    let (key, value) = result?;
+                    batch.push((key.node, value.to_vec()));
+                }
+                if let Some(&(last, _)) = batch.last().filter(|_| batch.len() == BATCH_SIZE) {
+                    end = Some(end.map_or(last, |end| end.min(last)));
+                }
+            }
+            if batches.iter().all(Vec::is_empty) {
+                break;
+            }
+            if let Some(end) = end {
+                batches.iter_mut().for_each(|batch| batch.retain(|&(node, _)| node <= end));
+            }
+
+            database.delete_range(wtxn, &range(a, start, end))?;
+            database.delete_range(wtxn, &range(b, start, end))?;
+            let [batch_a, batch_b] = &mut batches;
+            for (to, batch) in [(b, batch_a), (a, batch_b)] {
+                for (node, value) in batch.drain(..) {
+                    database.put(wtxn, &Key::new(to, node), &value)?;
+                    count += 1;
+                }
+            }
+
+            match end {
+                Some(end) => start = Some(end),
+                None => break,
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Copies the values of the index `from` in the index `to` and returns their number.
+    /// They are read in batches, the database cannot be modified while it is being iterated.
+    fn copy_values(&self, wtxn: &mut RwTxn, from: u16, to: u16) -> Result<u64> {
+        const BATCH_SIZE: usize = 1024;
+
+        let database = self.database.remap_data_type::<Bytes>();
+        let mut count = 0;
+        let mut batch: Vec<(NodeId, Vec<u8>)> = Vec::with_capacity(BATCH_SIZE);
+        let mut start = Bound::Included(Key::metadata(from));
+        loop {
+            let end = Bound::Included(Key::item(from, ItemId::MAX));
+            for result in database.range(wtxn, &(start, end))?.take(BATCH_SIZE) {
+                let (key, value) = result?;
+                batch.push((key.node, value.to_vec()));
+            }
+            let Some(&(last, _)) = batch.last() else { break };
+            start = Bound::Excluded(Key::new(from, last));
+
+            for (node, value) in batch.drain(..) {
+                database.put(wtxn, &Key::new(to, node), &value)?;
+                count += 1;
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Verifies the trees of the index like [`Reader::verify`](crate::Reader::verify) and
+    /// rebuilds the broken ones. Returns the report of the problems found before repairing.
+    ///
+    /// The tree nodes that are not part of a valid tree are deleted, the build that is not
+    /// finished yet is discarded, and the items that some trees can't reach are inserted
+    /// again. The options of the last build are used to generate the missing trees.
+    ///
+    /// The items that cannot be decoded or that don't have the dimensions of the index cannot be
+    /// repaired, they must be inserted again or deleted first. Nothing is modified when there are some.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use arroy::{Writer, distances::Euclidean};
+    /// # let (writer, mut wtxn): (Writer<Euclidean>, heed::RwTxn) = todo!();
+    /// use rand::rngs::StdRng;
+    /// use rand::SeedableRng;
+    /// let mut rng = StdRng::seed_from_u64(92);
+    /// let report = writer.repair(&mut wtxn, &mut rng)?;
+    /// println!("{} trees were built again", report.broken_trees.len());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn repair<R: Rng + SeedableRng>(
+        &self,
+        wtxn: &mut RwTxn,
+        rng: &mut R,
+    ) -> Result<VerifyReport> {
+        let (verification, items) = {
+            let metadata = self.metadata(wtxn)?.ok_or(Error::MissingMetadata(self.index))?;
+            let roots: Vec<_> = metadata.roots.iter().collect();
+            let verification =
+                verify(wtxn, self.database, self.index, self.dimensions, &roots, &metadata.items)?;
+            (verification, metadata.items)
+        };
+        let Verification { report, valid_roots, valid_tree_nodes } = verification;
+        if report.is_valid() || !report.invalid_items.is_empty() {
+            return Ok(report);
+        }
+
+        log::debug!("deleting the tree nodes that are not part of a valid tree...");
+        self.database.delete(wtxn, &Key::pending_build(self.index))?;
+        let mut cursor = self
+            .database
+            .remap_types::<PrefixCodec, DecodeIgnore>()
+            .prefix_iter_mut(wtxn, &Prefix::tree(self.index))?
+            .remap_key_type::<KeyCodec>();
+        while let Some((key, _)) = cursor.next().transpose()? {
+            if !valid_tree_nodes.contains(key.node.unwrap_tree()) {
+                // safety: we keep no reference into the database between operations
+                unsafe { cursor.del_current()? };
+            }
+        }
+        drop(cursor);
+
+        log::debug!("keeping the {} valid trees...", valid_roots.len());
+        let metadata = Metadata {
+            dimensions: self.dimensions.try_into().unwrap(),
+            items,
+            roots: ItemIds::from_slice(&valid_roots),
+            distance: D::name(),
+        };
+        let metadata_key = Key::metadata(self.index);
+        self.database.remap_data_type::<MetadataCodec>().put(wtxn, &metadata_key, &metadata)?;
+
+        // The build removes the items from the trees before inserting them again
+        let to_update = &report.missing_items | &report.unknown_items | &report.dangling_items;
+        for item in &to_update {
+            self.database.remap_data_type::<Unit>().put(
+                wtxn,
+                &Key::updated(self.index, item),
+                &(),
+            )?;
+        }
+
+        self.builder(rng).build(wtxn)?;
+
+        Ok(report)
+    }
+
+    /// Deletes the tree nodes that are not reachable from the trees of the index
+    /// and returns what was reclaimed.
+    ///
+    /// The nodes of the build that is not finished yet are kept. The IDs of the deleted
+    /// nodes are reused by the next builds.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use arroy::{Writer, distances::Euclidean};
+    /// # let (writer, mut wtxn): (Writer<Euclidean>, heed::RwTxn) = todo!();
+    /// let report = writer.collect_garbage(&mut wtxn)?;
+    /// println!("{} bytes were reclaimed", report.reclaimed_bytes);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn collect_garbage(&self, wtxn: &mut RwTxn) -> Result<GarbageReport> {
+        let version = self.format_version(wtxn)?;
+        if version > FORMAT_VERSION {
+            return Err(Error::UnsupportedVersion {
+                index: self.index,
+                version,
+                supported: FORMAT_VERSION,
+            });
+        }
+
+        let roots: Vec<_> =
+            self.metadata(wtxn)?.map_or_else(Vec::new, |metadata| metadata.roots.iter().collect());
+        let reachable = reachable_tree_nodes(wtxn, self.database, self.index, &roots)?;
+
+        let mut report = GarbageReport::default();
+        let mut cursor = self
+            .database
+            .remap_types::<PrefixCodec, Bytes>()
+            .prefix_iter_mut(wtxn, &Prefix::tree(self.index))?
+            .remap_key_type::<Bytes>();
+        while let Some((key_bytes, bytes)) = cursor.next().transpose()? {
+            let key = KeyCodec::bytes_decode(key_bytes).map_err(heed::Error::Decoding)?;
+            let tree_node = key.node.unwrap_tree();
+            if !reachable.contains(tree_node) {
+                report.deleted_tree_nodes.insert(tree_node);
+                report.reclaimed_bytes += (key_bytes.len() + bytes.len()) as u64;
+                // safety: we keep no reference into the database between operations
+                unsafe { cursor.del_current()? };
+            }
+        }
+
+        log::debug!("deleted {} orphan tree nodes", report.deleted_tree_nodes.len());
+        Ok(report)
+    }
+
+    /// Returns the options used by the last build of the index.
+    fn build_config(&self, rtxn: &RoTxn) -> Result<BuildConfig> {
+        let key = Key::build_config(self.index);
+        let config =
+            self.database.remap_data_type::<BuildConfigCodec>().get(rtxn, &key).with_key(key)?;
+        Ok(config.unwrap_or_default())
+    }
+
+    /// Returns the projection the trees of the index are built on.
+    fn projection<'t>(&self, rtxn: &'t RoTxn) -> Result<Option<Projection<'t>>> {
+        let key = Key::projection(self.index);
+        self.database.remap_data_type::<ProjectionCodec>().get(rtxn, &key).with_key(key)
+    }
+
+    /// Returns the metadata written by the last build of the index.
+    fn metadata<'t>(&self, rtxn: &'t RoTxn) -> Result<Option<Metadata<'t>>> {
+        let key = Key::metadata(self.index);
+        self.database.remap_data_type::<MetadataCodec>().get(rtxn, &key).with_key(key)
+    }
+
+    /// Returns the trees of the build that is not finished yet.
+    fn pending_build<'t>(&self, rtxn: &'t RoTxn) -> Result<Option<PendingBuild<'t>>> {
+        let key = Key::pending_build(self.index);
+        self.database.remap_data_type::<PendingBuildCodec>().get(rtxn, &key).with_key(key)
+    }
+
+    /// Returns the projection to build the trees on and `true` if it isn't the one
+    /// stored in the index, in which case all the trees must be built again.
+    fn tree_projection<'t, R: Rng>(
+        &self,
+        rtxn: &'t RoTxn,
+        rng: &mut R,
+        options: &BuildOption,
+    ) -> Result<(Option<Projection<'t>>, bool)> {
+        let stored = self.projection(rtxn)?;
+        if self.projection_is_up_to_date(stored.as_ref(), options) {
+            Ok((stored, false))
+        } else {
+            let projection = options
+                .reduced_dimensions
+                .map(|dimensions| Projection::random(rng, self.dimensions, dimensions));
+            Ok((projection, true))
+        }
+    }
+
+    /// Returns `true` if the trees built on this projection match the requested options.
+    fn projection_is_up_to_date(
+        &self,
+        projection: Option<&Projection>,
+        options: &BuildOption,
+    ) -> bool {
+        match (projection, options.reduced_dimensions) {
+            (None, None) => true,
+            (Some(projection), Some(dimensions)) => {
+                projection.dimensions() == dimensions
+                    && projection.source_dimensions() == self.dimensions
+            }
+            (Some(_), None) | (None, Some(_)) => false,
+        }
+    }
+
+    fn write_projection(&self, wtxn: &mut RwTxn, projection: Option<&Projection>) -> Result<()> {
+        let key = Key::projection(self.index);
+        match projection {
+            Some(projection) => {
+                self.database.remap_data_type::<ProjectionCodec>().put(wtxn, &key, projection)?
+            }
+            None => {
+                self.database.delete(wtxn, &key)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the generator of the ids of the new tree nodes and the number of tree nodes.
+    /// When all the trees are rebuilt, only the nodes of the `new_roots` are counted as used
+    /// as the other ones will be deleted, but their ids are still avoided.
+    fn tree_node_ids(
+        &self,
+        rtxn: &RoTxn,
+        rebuild: bool,
+        new_roots: &[ItemId],
+    ) -> Result<(ConcurrentNodeIds, u64)> {
+        let used_node_ids = self.used_tree_node(rtxn)?;
+        let nb_tree_nodes = used_node_ids.len();
+        let concurrent_node_ids = if rebuild {
+            let new_tree_nodes = reachable_tree_nodes(rtxn, self.database, self.index, new_roots)?;
+            let replaced = &used_node_ids - new_tree_nodes;
+            ConcurrentNodeIds::replacing(used_node_ids, &replaced)
+        } else {
+            ConcurrentNodeIds::new(used_node_ids)
+        };
+        Ok((concurrent_node_ids, nb_tree_nodes))
+    }
+
+    /// Returns the leafs the trees are built on. When the index reduces the dimensions
+    /// of the items, they are projected once and stored in a temporary file.
+    fn tree_leafs<'t>(
+        &self,
+        rtxn: &'t RoTxn,
+        options: &BuildOption,
+        item_indices: &RoaringBitmap,
+        projection: Option<&Projection>,
+    ) -> Result<ImmutableLeafs<'t, D>> {
+        let leafs = ImmutableLeafs::new(rtxn, self.database, self.index, item_indices.len())?;
+        let Some(projection) = projection else { return Ok(leafs) };
+
+        log::debug!(
+            "projecting {} items to {} dimensions...",
+            item_indices.len(),
+            projection.dimensions()
+        );
+        let mut tmp_leafs: TmpNodes<Bytes> = match self.tmpdir.as_ref() {
+            Some(path) => TmpNodes::new_in(path)?,
+            None => TmpNodes::new()?,
+        };
+        let item_ids: Vec<_> = item_indices.iter().collect();
+        for chunk in item_ids.chunks(PROJECTION_BATCH_SIZE) {
+            if (options.cancel)() {
+                return Err(Error::BuildCancelled);
+            }
+
+            let projected = options.install(|| {
+                chunk
+                    .par_iter()
+                    .map(|&item| {
+                        let leaf = leafs.get(item)?.unwrap();
+                        let node = Node::Leaf(projection.project_leaf(&leaf));
+                        let bytes =
+                            NodeCodec::bytes_encode(&node).map_err(heed::Error::Encoding)?;
+                        Ok((item, bytes.into_owned()))
+                    })
+                    .collect::<heed::Result<Vec<_>>>()
+            })?;
+            for (item, bytes) in &projected {
+                tmp_leafs.put(*item, bytes)?;
+            }
+        }
+
+        Ok(ImmutableLeafs::from_tmp_nodes(tmp_leafs.into_bytes_reader()?)?)
+    }
+
+    /// Returns a random number generator initialized from the seed and the number of trees
+    /// already built, so that the trees added by a later build are not copies of the first ones.
+    fn seeded_rng<R: SeedableRng>(&self, rtxn: &RoTxn, seed: u64) -> Result<R> {
+        let n_roots = self.metadata(rtxn)?.map_or(0, |metadata| metadata.roots.len());
+        let n_pending_roots = self.pending_build(rtxn)?.map_or(0, |pending| pending.roots.len());
+        Ok(R::seed_from_u64(seed.wrapping_add((n_roots + n_pending_roots) as u64)))
+    }
+
     fn used_tree_node(&self, rtxn: &RoTxn) -> Result<RoaringBitmap> {
         Ok(self
             .database
@@ -361,6 +1424,189 @@ impl<D: Distance> Writer<D> {
         rng: &mut R,
         options: &BuildOption,
     ) -> Result<()> {
+        self.preprocess_items(wtxn, options)?;
+        let built = self.build_from_snapshot(wtxn, rng, options)?;
+        self.write_built_trees(wtxn, options, built)
+    }
+
+    /// Generates at most `max_trees` new trees and stores them in the database, without
+    /// making them visible to the readers. Returns `true` once all the trees have been
+    /// generated and the metadata of the index updated.
+    fn build_step<R: Rng + SeedableRng>(
+        &self,
+        wtxn: &mut RwTxn,
+        rng: &mut R,
+        options: &BuildOption,
+        max_trees: usize,
+    ) -> Result<bool> {
+        self.preprocess_items(wtxn, options)?;
+
+        let item_indices = self.item_indices(wtxn)?;
+        let n_items = item_indices.len();
+        if self.fit_in_descendant(options, n_items) {
+            let built = self.build_from_snapshot(wtxn, rng, options)?;
+            self.write_built_trees(wtxn, options, built)?;
+            return Ok(true);
+        }
+
+        let fingerprint = self.snapshot(wtxn)?.fingerprint();
+        let pending_key = Key::pending_build(self.index);
+        let stored_projection = self.projection(wtxn)?.map(Projection::into_owned);
+        let pending = self.pending_build(wtxn)?;
+        let pending = match pending {
+            Some(pending) if pending.fingerprint != fingerprint => {
+                log::debug!("The index was modified since the last step, restarting the build...");
+                self.discard_pending_build(wtxn)?;
+                None
+            }
+            Some(pending)
+                if !self.projection_is_up_to_date(
+                    pending
+                        .new_projection
+                        .as_ref()
+                        .map_or(stored_projection.as_ref(), Option::as_ref),
+                    options,
+                ) =>
+            {
+                log::debug!("The projection changed since the last step, restarting the build...");
+                self.discard_pending_build(wtxn)?;
+                None
+            }
+            Some(pending) => Some((
+                pending.new_projection.map(|projection| projection.map(Projection::into_owned)),
+                pending.roots.iter().collect(),
+            )),
+            None => None,
+        };
+
+        // The new projection is only written with the metadata once all the trees built on it
+        // are finished, until then the readers keep using the previous trees.
+        let (new_projection, mut pending_roots): (_, Vec<_>) = match pending {
+            Some(pending) => pending,
+            None => {
+                let (projection, projection_changed) = self.tree_projection(wtxn, rng, options)?;
+                let new_projection =
+                    projection_changed.then(|| projection.map(Projection::into_owned));
+                (new_projection, Vec::new())
+            }
+        };
+
+        // The trees built on another projection are replaced, we build all of them again
+        let n_roots = match new_projection {
+            Some(_) => {
+                log::debug!("The projection changed, rebuilding all the trees...");
+                0
+            }
+            None => self.metadata(wtxn)?.map_or(0, |metadata| metadata.roots.len()),
+        };
+
+        if (options.cancel)() {
+            return Err(Error::BuildCancelled);
+        }
+
+        let projection = new_projection.as_ref().map_or(stored_projection.as_ref(), Option::as_ref);
+        let (concurrent_node_ids, nb_tree_nodes) =
+            self.tree_node_ids(wtxn, new_projection.is_some(), &pending_roots)?;
+        let frozzen_reader = FrozzenReader {
+            leafs: &self.tree_leafs(wtxn, options, &item_indices, projection)?,
+            trees: &ImmutableTrees::new(wtxn, self.database, self.index, nb_tree_nodes)?,
+            concurrent_node_ids: &concurrent_node_ids,
+        };
+
+        let n_trees_to_build =
+            options.n_trees.map(|n_trees| n_trees.saturating_sub(n_roots + pending_roots.len()));
+        log::debug!("started building at most {max_trees} trees for {n_items} items...");
+        let (new_roots, tmp_nodes) = self.build_trees(
+            options,
+            rng,
+            n_trees_to_build,
+            max_trees,
+            &item_indices,
+            &frozzen_reader,
+        )?;
+
+        let finished = match n_trees_to_build {
+            Some(n_trees) => new_roots.len() >= n_trees,
+            None => new_roots.len() < max_trees || concurrent_node_ids.used() >= n_items,
+        };
+
+        log::debug!("started inserting the tree nodes of {} trees...", tmp_nodes.len());
+        for tmp_node in &tmp_nodes {
+            for (item_id, item_bytes) in tmp_node.to_insert() {
+                let key = Key::tree(self.index, item_id);
+                self.database.remap_data_type::<Bytes>().put(wtxn, &key, item_bytes)?;
+            }
+        }
+        pending_roots.extend(new_roots);
+
+        if !finished {
+            log::debug!("checkpointing the {} unfinished trees...", pending_roots.len());
+            let pending = PendingBuild {
+                fingerprint,
+                new_projection,
+                roots: ItemIds::from_slice(&pending_roots),
+            };
+            self.database.remap_data_type::<PendingBuildCodec>().put(
+                wtxn,
+                &pending_key,
+                &pending,
+            )?;
+            return Ok(false);
+        }
+
+        // The pending trees are finished, we must now insert the updated
+        // items in the existing trees and make all of them visible at once.
+        self.database.delete(wtxn, &pending_key)?;
+
+        let updated_items = self.updated_items(wtxn)?;
+        let to_insert = &item_indices & &updated_items;
+        let metadata = self.metadata(wtxn)?.filter(|_| new_projection.is_none());
+        let projection = new_projection.as_ref().map_or(stored_projection.as_ref(), Option::as_ref);
+        let (concurrent_node_ids, nb_tree_nodes) =
+            self.tree_node_ids(wtxn, new_projection.is_some(), &pending_roots)?;
+        let frozzen_reader = FrozzenReader {
+            leafs: &self.tree_leafs(wtxn, options, &item_indices, projection)?,
+            trees: &ImmutableTrees::new(wtxn, self.database, self.index, nb_tree_nodes)?,
+            concurrent_node_ids: &concurrent_node_ids,
+        };
+
+        let (roots, nodes_to_write) = match metadata {
+            Some(ref metadata) => self.update_trees(
+                options,
+                rng,
+                metadata,
+                &to_insert,
+                &updated_items,
+                &frozzen_reader,
+            )?,
+            None => (Vec::new(), Vec::new()),
+        };
+
+        let built = BuiltTrees::Forest {
+            items: item_indices,
+            roots,
+            new_roots: pending_roots,
+            nodes_to_write,
+            nb_tree_nodes: concurrent_node_ids.used(),
+            new_projection,
+        };
+        self.write_built_trees(wtxn, options, built)?;
+
+        Ok(true)
+    }
+
+    /// Prepares the items to be inserted in the trees. It's a no-op for
+    /// most of the distances but the [`DotProduct`](crate::distances::DotProduct)
+    /// one must rewrite every item before building.
+    ///
+    /// This is done automatically by [`ArroyBuilder::build`] but it must be called and
+    /// committed before calling [`ArroyBuilder::prepare`] for the trees to be built on
+    /// the preprocessed items.
+    pub fn preprocess(&self, wtxn: &mut RwTxn) -> Result<()> {
+        self.preprocess_items(wtxn, &BuildOption::default())
+    }
+
+    fn preprocess_items(&self, wtxn: &mut RwTxn, options: &BuildOption) -> Result<()> {
         log::debug!("started preprocessing the items...");
 
         if (options.cancel)() {
@@ -379,102 +1625,63 @@ impl<D: Distance> Writer<D> {
             return Err(Error::BuildCancelled);
         }
 
-        let item_indices = self.item_indices(wtxn)?;
-        let n_items = item_indices.len();
-
-        if self.fit_in_descendant(options, item_indices.len()) {
-            log::debug!("We can fit every elements in a single descendant node, we can skip all the build process");
-            // No item left in the index, we can clear every tree
-
-            self.database.remap_data_type::<Bytes>().delete_range(
-                wtxn,
-                &(Key::tree(self.index, 0)..=Key::tree(self.index, ItemId::MAX)),
-            )?;
+        Ok(())
+    }
 
-            let mut roots = Vec::new();
+    fn is_preprocessed(&self, rtxn: &RoTxn) -> Result<bool> {
+        let is_preprocessed = D::is_preprocessed(rtxn, |rtxn| {
+            Ok(self
+                .database
+                .remap_key_type::<PrefixCodec>()
+                .prefix_iter(rtxn, &Prefix::item(self.index))?
+                .remap_key_type::<KeyCodec>())
+        })?;
+        Ok(is_preprocessed)
+    }
 
-            if !item_indices.is_empty() {
-                // if we have more than 0 elements we need to create a descendant node
+    /// Builds the trees by only reading the database and stores the generated
+    /// tree nodes in temporary files. Nothing is written in the database.
+    fn build_from_snapshot<R: Rng + SeedableRng>(
+        &self,
+        rtxn: &RoTxn,
+        rng: &mut R,
+        options: &BuildOption,
+    ) -> Result<BuiltTrees> {
+        let item_indices = self.item_indices(rtxn)?;
+        let n_items = item_indices.len();
 
-                self.database.put(
-                    wtxn,
-                    &Key::tree(self.index, 0),
-                    &Node::Descendants(Descendants { descendants: Cow::Borrowed(&item_indices) }),
-                )?;
-                roots.push(0);
-            }
+        log::debug!("retrieve the updated items...");
+        let updated_items = self.updated_items(rtxn)?;
 
-            log::debug!("reset the updated items...");
-            let mut updated_iter = self
-                .database
-                .remap_types::<PrefixCodec, DecodeIgnore>()
-                .prefix_iter_mut(wtxn, &Prefix::updated(self.index))?
-                .remap_key_type::<KeyCodec>();
-            while updated_iter.next().transpose()?.is_some() {
-                // Safe because we don't hold any reference to the database currently
-                unsafe {
-                    updated_iter.del_current()?;
-                }
-            }
-            drop(updated_iter);
-
-            log::debug!("write the metadata...");
-            let metadata = Metadata {
-                dimensions: self.dimensions.try_into().unwrap(),
-                items: item_indices,
-                roots: ItemIds::from_slice(&roots),
-                distance: D::name(),
-            };
-            self.database.remap_data_type::<MetadataCodec>().put(
-                wtxn,
-                &Key::metadata(self.index),
-                &metadata,
-            )?;
+        let (projection, projection_changed) = self.tree_projection(rtxn, rng, options)?;
+        let new_projection =
+            projection_changed.then(|| projection.clone().map(Projection::into_owned));
 
-            return Ok(());
-        }
+        // The trees built on another projection cannot be updated, we build all of them again
+        let metadata = self.metadata(rtxn)?.filter(|_| !projection_changed);
+        let mut roots =
+            metadata.as_ref().map_or_else(Vec::new, |metadata| metadata.roots.iter().collect());
 
-        log::debug!("reset and retrieve the updated items...");
-        let mut updated_items = RoaringBitmap::new();
-        let mut updated_iter = self
-            .database
-            .remap_types::<PrefixCodec, DecodeIgnore>()
-            .prefix_iter_mut(wtxn, &Prefix::updated(self.index))?
-            .remap_key_type::<KeyCodec>();
-        while let Some((key, _)) = updated_iter.next().transpose()? {
-            let inserted = updated_items.push(key.node.item);
-            debug_assert!(inserted, "The keys should be sorted by LMDB");
-            // Safe because we don't hold any reference to the database currently
-            unsafe {
-                updated_iter.del_current()?;
-            }
+        if self.fit_in_descendant(options, n_items) {
+            log::debug!("We can fit every elements in a single descendant node, we can skip all the build process");
+            return Ok(BuiltTrees::SingleDescendants { items: item_indices, new_projection });
         }
-        drop(updated_iter);
 
         // while iterating on the nodes we want to delete all the modified element even if they are being inserted right after.
         let to_delete = &updated_items;
         let to_insert = &item_indices & &updated_items;
 
-        let metadata = self
-            .database
-            .remap_data_type::<MetadataCodec>()
-            .get(wtxn, &Key::metadata(self.index))?;
-        let mut roots =
-            metadata.as_ref().map_or_else(Vec::new, |metadata| metadata.roots.iter().collect());
-
         log::debug!("Getting a reference to your {} items...", n_items);
 
         if (options.cancel)() {
             return Err(Error::BuildCancelled);
         }
 
-        let used_node_ids = self.used_tree_node(wtxn)?;
-        let nb_tree_nodes = used_node_ids.len();
-
-        let concurrent_node_ids = ConcurrentNodeIds::new(used_node_ids);
+        let (concurrent_node_ids, nb_tree_nodes) =
+            self.tree_node_ids(rtxn, new_projection.is_some(), &[])?;
         let frozzen_reader = FrozzenReader {
-            leafs: &ImmutableLeafs::new(wtxn, self.database, self.index, item_indices.len())?,
-            trees: &ImmutableTrees::new(wtxn, self.database, self.index, nb_tree_nodes)?,
+            leafs: &self.tree_leafs(rtxn, options, &item_indices, projection.as_ref())?,
+            trees: &ImmutableTrees::new(rtxn, self.database, self.index, nb_tree_nodes)?,
             // The globally incrementing node ids that are shared between threads.
             concurrent_node_ids: &concurrent_node_ids,
         };
@@ -507,50 +1714,159 @@ impl<D: Distance> Writer<D> {
             .zip(metadata)
             .map(|(n_trees, metadata)| n_trees.saturating_sub(metadata.roots.len()))
             .or(options.n_trees);
-        let (mut thread_roots, mut tmp_nodes) =
-            self.build_trees(options, rng, n_trees_to_build, &item_indices, &frozzen_reader)?;
+        let (new_roots, mut tmp_nodes) = self.build_trees(
+            options,
+            rng,
+            n_trees_to_build,
+            usize::MAX,
+            &item_indices,
+            &frozzen_reader,
+        )?;
         nodes_to_write.append(&mut tmp_nodes);
 
-        log::debug!("started updating the tree nodes of {} trees...", tmp_nodes.len());
-        for (i, tmp_node) in nodes_to_write.iter().enumerate() {
-            log::debug!(
-                "started deleting the {} tree nodes of the {i}nth trees...",
-                tmp_node.len()
-            );
-            for item_id in tmp_node.to_delete() {
-                let key = Key::tree(self.index, item_id);
-                self.database.remap_data_type::<Bytes>().delete(wtxn, &key)?;
+        Ok(BuiltTrees::Forest {
+            items: item_indices,
+            roots,
+            new_roots,
+            nodes_to_write,
+            nb_tree_nodes: concurrent_node_ids.used(),
+            new_projection,
+        })
+    }
+
+    /// Writes the tree nodes generated by [`Self::build_from_snapshot`] in the database,
+    /// resets the updated items and writes the new metadata.
+    fn write_built_trees(
+        &self,
+        wtxn: &mut RwTxn,
+        options: &BuildOption,
+        built: BuiltTrees,
+    ) -> Result<()> {
+        // The trees of an unfinished build are based on outdated items or node ids
+        self.discard_pending_build(wtxn)?;
+
+        log::debug!("reset the updated items...");
+        self.database.remap_data_type::<DecodeIgnore>().delete_range(
+            wtxn,
+            &(Key::updated(self.index, 0)..=Key::updated(self.index, ItemId::MAX)),
+        )?;
+
+        let (items, roots) = match built {
+            BuiltTrees::SingleDescendants { items, new_projection } => {
+                // No item left in the index, we can clear every tree
+                self.database.remap_data_type::<Bytes>().delete_range(
+                    wtxn,
+                    &(Key::tree(self.index, 0)..=Key::tree(self.index, ItemId::MAX)),
+                )?;
+                if let Some(projection) = new_projection {
+                    self.write_projection(wtxn, projection.as_ref())?;
+                }
+
+                let mut roots = Vec::new();
+
+                if !items.is_empty() {
+                    // if we have more than 0 elements we need to create a descendant node
+
+                    self.database.put(
+                        wtxn,
+                        &Key::tree(self.index, 0),
+                        &Node::Descendants(Descendants { descendants: Cow::Borrowed(&items) }),
+                    )?;
+                    roots.push(0);
+                }
+
+                (items, roots)
             }
-            log::debug!(
-                "started inserting the {} tree nodes of the {i}nth trees...",
-                tmp_node.len()
-            );
-            for (item_id, item_bytes) in tmp_node.to_insert() {
-                let key = Key::tree(self.index, item_id);
-                self.database.remap_data_type::<Bytes>().put(wtxn, &key, item_bytes)?;
+            BuiltTrees::Forest {
+                items,
+                mut roots,
+                mut new_roots,
+                nodes_to_write,
+                nb_tree_nodes,
+                new_projection,
+            } => {
+                if let Some(projection) = new_projection {
+                    log::debug!("deleting the trees built on the previous projection...");
+                    // The new trees may already have been written by the previous build steps
+                    let new_tree_nodes =
+                        reachable_tree_nodes(wtxn, self.database, self.index, &new_roots)?;
+                    for tree_node in self.used_tree_node(wtxn)? - new_tree_nodes {
+                        self.database.delete(wtxn, &Key::tree(self.index, tree_node))?;
+                    }
+                    self.database.delete(wtxn, &Key::search_tuning(self.index))?;
+                    self.write_projection(wtxn, projection.as_ref())?;
+                }
+
+                log::debug!("started updating the tree nodes of {} trees...", nodes_to_write.len());
+                for (i, tmp_node) in nodes_to_write.iter().enumerate() {
+                    log::debug!(
+                        "started deleting the {} tree nodes of the {i}nth trees...",
+                        tmp_node.len()
+                    );
+                    for item_id in tmp_node.to_delete() {
+                        let key = Key::tree(self.index, item_id);
+                        self.database.remap_data_type::<Bytes>().delete(wtxn, &key)?;
+                    }
+                    log::debug!(
+                        "started inserting the {} tree nodes of the {i}nth trees...",
+                        tmp_node.len()
+                    );
+                    for (item_id, item_bytes) in tmp_node.to_insert() {
+                        let key = Key::tree(self.index, item_id);
+                        self.database.remap_data_type::<Bytes>().put(wtxn, &key, item_bytes)?;
+                    }
+                }
+
+                if new_roots.is_empty() {
+                    // we may have too many nodes
+                    log::debug!("Deleting the extraneous trees if there is some...");
+                    self.delete_extra_trees(
+                        wtxn,
+                        options,
+                        &mut roots,
+                        options.n_trees,
+                        nb_tree_nodes,
+                        items.len(),
+                    )?;
+                } else {
+                    roots.append(&mut new_roots);
+                }
+
+                (items, roots)
             }
-        }
+        };
 
-        if thread_roots.is_empty() {
-            // we may have too many nodes
-            log::debug!("Deleting the extraneous trees if there is some...");
-            self.delete_extra_trees(
-                wtxn,
-                options,
-                &mut roots,
-                options.n_trees,
-                concurrent_node_ids.used(),
-                n_items,
-            )?;
+        log::debug!("write the build options...");
+        self.write_build_config(wtxn, &options.config())?;
+
+        self.write_metadata(wtxn, items, &roots)
+    }
+
+    /// Stores the options used to build the index, they are the defaults of the next builds.
+    fn write_build_config(&self, wtxn: &mut RwTxn, config: &BuildConfig) -> Result<()> {
+        let key = Key::build_config(self.index);
+        if config.is_empty() {
+            self.database.delete(wtxn, &key)?;
         } else {
-            roots.append(&mut thread_roots);
+            self.database.remap_data_type::<BuildConfigCodec>().put(wtxn, &key, config)?;
         }
+        Ok(())
+    }
 
-        log::debug!("write the metadata...");
+    /// Writes the metadata of the trees with the version of the format they are written in.
+    fn write_metadata(
+        &self,
+        wtxn: &mut RwTxn,
+        items: RoaringBitmap,
+        roots: &[ItemId],
+    ) -> Result<()> {
+        log::debug!("write the metadata and the format version...");
+        let version_key = Key::version(self.index);
+        self.database.remap_data_type::<VersionCodec>().put(wtxn, &version_key, &FORMAT_VERSION)?;
         let metadata = Metadata {
             dimensions: self.dimensions.try_into().unwrap(),
-            items: item_indices,
-            roots: ItemIds::from_slice(&roots),
+            items,
+            roots: ItemIds::from_slice(roots),
             distance: D::name(),
         };
         match self.database.remap_data_type::<MetadataCodec>().put(
@@ -573,32 +1889,75 @@ impl<D: Distance> Writer<D> {
         frozen_reader: &FrozzenReader<D>,
     ) -> Result<(Vec<ItemId>, Vec<TmpNodesReader>)> {
         let roots: Vec<_> = metadata.roots.iter().collect();
+        let seed = rng.next_u64();
+
+        if !opt.is_deterministic() {
+            return opt.install(|| {
+                roots
+                    .into_par_iter()
+                    .map(|root| {
+                        self.update_tree::<R>(opt, seed, root, to_insert, to_delete, frozen_reader)
+                    })
+                    .collect()
+            });
+        }
 
-        repeatn(rng.next_u64(), metadata.roots.len())
-            .zip(roots)
-            .map(|(seed, root)| {
-                log::debug!("started updating tree {root:X}...");
-                let mut rng = R::seed_from_u64(seed.wrapping_add(root as u64));
-                let mut tmp_nodes: TmpNodes<NodeCodec<D>> = match self.tmpdir.as_ref() {
-                    Some(path) => TmpNodes::new_in(path)?,
-                    None => TmpNodes::new()?,
-                };
-                let root_node = NodeId::tree(root);
-                let (node_id, _items) = self.update_nodes_in_file(
-                    opt,
-                    frozen_reader,
-                    &mut rng,
-                    root_node,
-                    to_insert,
-                    to_delete,
-                    &mut tmp_nodes,
-                )?;
-                assert!(node_id.mode != NodeMode::Item, "update_nodes_in_file returned an item even though there was more than a single element");
+        let concurrent_node_ids = frozen_reader.concurrent_node_ids;
+        let trees: Vec<_> = opt.install(|| {
+            roots
+                .into_par_iter()
+                .map(|root| {
+                    let local_node_ids = concurrent_node_ids.local();
+                    let frozen_reader = frozen_reader.with_node_ids(&local_node_ids);
+                    let (root, nodes) = self.update_tree::<R>(
+                        opt,
+                        seed,
+                        root,
+                        to_insert,
+                        to_delete,
+                        &frozen_reader,
+                    )?;
+                    Ok((root, nodes, local_node_ids.used()))
+                })
+                .collect::<Result<_>>()
+        })?;
 
-                log::debug!("finished updating tree {root:X}");
-                Ok((node_id.unwrap_tree(), tmp_nodes.into_bytes_reader()?))
-            })
-            .collect()
+        let mut allocated = Vec::with_capacity(trees.len());
+        for (root, nodes, n_local_ids) in trees {
+            allocated.push((root, nodes, allocate_node_ids(concurrent_node_ids, n_local_ids)?));
+        }
+        self.remap_node_ids(opt, concurrent_node_ids.first_local(), allocated)
+    }
+
+    fn update_tree<R: Rng + SeedableRng>(
+        &self,
+        opt: &BuildOption,
+        seed: u64,
+        root: ItemId,
+        to_insert: &RoaringBitmap,
+        to_delete: &RoaringBitmap,
+        frozen_reader: &FrozzenReader<D>,
+    ) -> Result<(ItemId, TmpNodesReader)> {
+        log::debug!("started updating tree {root:X}...");
+        let mut rng = R::seed_from_u64(seed.wrapping_add(root as u64));
+        let mut tmp_nodes: TmpNodes<NodeCodec<D>> = match self.tmpdir.as_ref() {
+            Some(path) => TmpNodes::new_in(path)?,
+            None => TmpNodes::new()?,
+        };
+        let root_node = NodeId::tree(root);
+        let (node_id, _items) = self.update_nodes_in_file(
+            opt,
+            frozen_reader,
+            &mut rng,
+            root_node,
+            to_insert,
+            to_delete,
+            &mut tmp_nodes,
+        )?;
+        assert!(node_id.mode != NodeMode::Item, "update_nodes_in_file returned an item even though there was more than a single element");
+
+        log::debug!("finished updating tree {root:X}");
+        Ok((node_id.unwrap_tree(), tmp_nodes.into_bytes_reader()?))
     }
 
     /// Update the nodes that changed and delete the deleted nodes all at once.
@@ -647,7 +2006,7 @@ impl<D: Distance> Writer<D> {
                     let new_id =
                         self.make_tree_in_file(opt, frozen_reader, rng, &new_items, tmp_nodes)?;
 
-                    return Ok((new_id, new_items));
+                    Ok((new_id, new_items))
                 }
             }
             NodeMode::Tree => {
@@ -691,7 +2050,7 @@ impl<D: Distance> Writer<D> {
                             Ok((current_node, new_descendants))
                         }
                     }
-                    Node::SplitPlaneNormal(SplitPlaneNormal { normal, left, right }) => {
+                    Node::SplitPlaneNormal(SplitPlaneNormal { normal, left, right, precision }) => {
                         // Split the to_insert into two bitmaps on the left and right of this normal
                         let mut left_ids = RoaringBitmap::new();
                         let mut right_ids = RoaringBitmap::new();
@@ -701,6 +2060,8 @@ impl<D: Distance> Writer<D> {
                         } else {
                             for leaf in to_insert {
                                 let node = frozen_reader.leafs.get(leaf)?.unwrap();
+                                // The split plane may have been computed on fewer dimensions
+                                let node = D::prefix_leaf(node, normal.len());
                                 match D::side(&normal, &node, rng) {
                                     Side::Left => left_ids.insert(leaf),
                                     Side::Right => right_ids.insert(leaf),
@@ -757,6 +2118,7 @@ impl<D: Distance> Writer<D> {
                                         normal,
                                         left: new_left,
                                         right: new_right,
+                                        precision,
                                     }),
                                 )?;
                             }
@@ -778,36 +2140,150 @@ impl<D: Distance> Writer<D> {
         opt: &BuildOption,
         rng: &mut R,
         n_trees: Option<usize>,
+        max_trees: usize,
         item_indices: &RoaringBitmap,
         frozen_reader: &FrozzenReader<D>,
     ) -> Result<(Vec<ItemId>, Vec<TmpNodesReader>)> {
         let n_items = item_indices.len();
         let concurrent_node_ids = frozen_reader.concurrent_node_ids;
+        let seed = rng.next_u64();
+        let n_trees_to_build = n_trees.unwrap_or(usize::MAX).min(max_trees);
+
+        if !opt.is_deterministic() {
+            return opt.install(|| {
+                repeat_n(seed, n_trees_to_build)
+                    .enumerate()
+                    // Stop generating trees once the specified number of tree nodes are generated
+                    // but continue to generate trees if the number of trees is unspecified
+                    .take_any_while(|_| match n_trees {
+                        Some(_) => true,
+                        None => concurrent_node_ids.used() < n_items,
+                    })
+                    .map(|(i, seed)| {
+                        self.build_tree::<R>(opt, seed, i, item_indices, frozen_reader)
+                    })
+                    .collect()
+            });
+        }
 
-        repeatn(rng.next_u64(), n_trees.unwrap_or(usize::MAX))
-            .enumerate()
-            // Stop generating trees once the specified number of tree nodes are generated
-            // but continue to generate trees if the number of trees is unspecified
-            .take_any_while(|_| match n_trees {
-                Some(_) => true,
-                None => concurrent_node_ids.used() < n_items,
-            })
-            .map(|(i, seed)| {
-                log::debug!("started generating tree {i:X}...");
-                let mut rng = R::seed_from_u64(seed.wrapping_add(i as u64));
-                let mut tmp_nodes = match self.tmpdir.as_ref() {
+        // When the number of trees is unspecified, we generate them by batches and only keep
+        // the ones that would have been generated one after the other before reaching the
+        // number of tree nodes. Discarding the extra trees makes it independent of the threads.
+        let batch_size = match n_trees {
+            Some(_) => n_trees_to_build,
+            None => opt.install(rayon::current_num_threads),
+        };
+        let enough_nodes = || n_trees.is_none() && concurrent_node_ids.used() >= n_items;
+
+        let mut trees = Vec::new();
+        let mut start = 0;
+        while start < n_trees_to_build && !enough_nodes() {
+            let end = start.saturating_add(batch_size).min(n_trees_to_build);
+            let batch: Vec<_> = opt.install(|| {
+                (start..end)
+                    .into_par_iter()
+                    .map(|i| {
+                        let local_node_ids = concurrent_node_ids.local();
+                        let frozen_reader = frozen_reader.with_node_ids(&local_node_ids);
+                        let (root, nodes) =
+                            self.build_tree::<R>(opt, seed, i, item_indices, &frozen_reader)?;
+                        Ok((root, nodes, local_node_ids.used()))
+                    })
+                    .collect::<Result<_>>()
+            })?;
+
+            for (root, nodes, n_local_ids) in batch {
+                if enough_nodes() {
+                    break;
+                }
+                trees.push((root, nodes, allocate_node_ids(concurrent_node_ids, n_local_ids)?));
+            }
+            start = end;
+        }
+
+        self.remap_node_ids(opt, concurrent_node_ids.first_local(), trees)
+    }
+
+    fn build_tree<R: Rng + SeedableRng>(
+        &self,
+        opt: &BuildOption,
+        seed: u64,
+        i: usize,
+        item_indices: &RoaringBitmap,
+        frozen_reader: &FrozzenReader<D>,
+    ) -> Result<(ItemId, TmpNodesReader)> {
+        log::debug!("started generating tree {i:X}...");
+        let mut rng = R::seed_from_u64(seed.wrapping_add(i as u64));
+        let mut tmp_nodes = match self.tmpdir.as_ref() {
+            Some(path) => TmpNodes::new_in(path)?,
+            None => TmpNodes::new()?,
+        };
+        let root_id =
+            self.make_tree_in_file(opt, frozen_reader, &mut rng, item_indices, &mut tmp_nodes)?;
+        assert!(
+            root_id.mode != NodeMode::Item,
+            "make_tree_in_file returned an item even though there was more than a single element"
+        );
+        log::debug!("finished generating tree {i:X}");
+        // make_tree will NEVER return a leaf when called as root
+        Ok((root_id.unwrap_tree(), tmp_nodes.into_bytes_reader()?))
+    }
+
+    /// Rewrites the trees generated with local node IDs, starting at `first_local`,
+    /// to use the IDs allocated for them instead.
+    fn remap_node_ids(
+        &self,
+        opt: &BuildOption,
+        first_local: ItemId,
+        trees: Vec<(ItemId, TmpNodesReader, Vec<ItemId>)>,
+    ) -> Result<(Vec<ItemId>, Vec<TmpNodesReader>)> {
+        opt.install(|| self.remap_trees_node_ids(first_local, trees))
+    }
+
+    fn remap_trees_node_ids(
+        &self,
+        first_local: ItemId,
+        trees: Vec<(ItemId, TmpNodesReader, Vec<ItemId>)>,
+    ) -> Result<(Vec<ItemId>, Vec<TmpNodesReader>)> {
+        trees
+            .into_par_iter()
+            .map(|(root, nodes, node_ids)| {
+                let remap = |id: ItemId| match id.checked_sub(first_local) {
+                    Some(local) => node_ids[local as usize],
+                    None => id,
+                };
+                let remap_node = |node: NodeId| match node.mode {
+                    NodeMode::Tree => NodeId::tree(remap(node.item)),
+                    _ => node,
+                };
+
+                let mut tmp_nodes: TmpNodes<NodeCodec<D>> = match self.tmpdir.as_ref() {
                     Some(path) => TmpNodes::new_in(path)?,
                     None => TmpNodes::new()?,
                 };
-                let root_id =
-                    self.make_tree_in_file(opt, frozen_reader, &mut rng, item_indices, &mut tmp_nodes)?;
-                assert!(
-                    root_id.mode != NodeMode::Item,
-                    "make_tree_in_file returned an item even though there was more than a single element"
-                );
-                log::debug!("finished generating tree {i:X}");
-                // make_tree will NEVER return a leaf when called as root
-                Ok((root_id.unwrap_tree(), tmp_nodes.into_bytes_reader()?))
+                for (item_id, bytes) in nodes.to_insert() {
+                    let node =
+                        match NodeCodec::<D>::bytes_decode(bytes).map_err(heed::Error::Decoding)? {
+                            Node::SplitPlaneNormal(SplitPlaneNormal {
+                                left,
+                                right,
+                                normal,
+                                precision,
+                            }) => Node::SplitPlaneNormal(SplitPlaneNormal {
+                                left: remap_node(left),
+                                right: remap_node(right),
+                                normal,
+                                precision,
+                            }),
+                            node => node,
+                        };
+                    tmp_nodes.put(remap(item_id), &node)?;
+                }
+                for item_id in nodes.to_delete() {
+                    tmp_nodes.remove(remap(item_id));
+                }
+
+                Ok((remap(root), tmp_nodes.into_bytes_reader()?))
             })
             .collect()
     }
@@ -837,7 +2313,8 @@ impl<D: Distance> Writer<D> {
             return Ok(NodeId::tree(item_id));
         }
 
-        let children = ImmutableSubsetLeafs::from_item_ids(reader.leafs, item_indices);
+        let children = ImmutableSubsetLeafs::from_item_ids(reader.leafs, item_indices)
+            .with_prefix(opt.split_dimensions);
         let mut children_left = Vec::with_capacity(children.len() as usize);
         let mut children_right = Vec::with_capacity(children.len() as usize);
         let mut remaining_attempts = 3;
@@ -885,6 +2362,7 @@ impl<D: Distance> Writer<D> {
             normal,
             left: self.make_tree_in_file(opt, reader, rng, &children_left, tmp_nodes)?,
             right: self.make_tree_in_file(opt, reader, rng, &children_right, tmp_nodes)?,
+            precision: opt.normal_precision.unwrap_or_default(),
         };
 
         let new_node_id = reader.concurrent_node_ids.next()?;
@@ -939,13 +2417,13 @@ impl<D: Distance> Writer<D> {
 
     fn delete_tree(&self, wtxn: &mut RwTxn, node: NodeId) -> Result<()> {
         let key = Key::new(self.index, node);
-        match self.database.get(wtxn, &key)?.ok_or(Error::missing_key(key))? {
+        match self.database.get(wtxn, &key).with_key(key)?.ok_or(Error::missing_key(key))? {
             // the leafs are shared between the trees, we MUST NOT delete them.
             Node::Leaf(_) => Ok(()),
             Node::Descendants(_) => {
                 self.database.delete(wtxn, &key).map(|_| ()).map_err(Error::from)
             }
-            Node::SplitPlaneNormal(SplitPlaneNormal { normal: _, left, right }) => {
+            Node::SplitPlaneNormal(SplitPlaneNormal { normal: _, left, right, .. }) => {
                 self.delete_tree(wtxn, left)?;
                 self.delete_tree(wtxn, right)?;
                 self.database.delete(wtxn, &key).map(|_| ()).map_err(Error::from)
@@ -954,12 +2432,12 @@ impl<D: Distance> Writer<D> {
     }
 
     // Fetches the item's ids, not the tree nodes ones.
-    fn item_indices(&self, wtxn: &mut RwTxn<'_>) -> heed::Result<RoaringBitmap> {
+    fn item_indices(&self, rtxn: &RoTxn) -> heed::Result<RoaringBitmap> {
         let mut indices = RoaringBitmap::new();
         for result in self
             .database
             .remap_types::<PrefixCodec, DecodeIgnore>()
-            .prefix_iter(wtxn, &Prefix::item(self.index))?
+            .prefix_iter(rtxn, &Prefix::item(self.index))?
             .remap_key_type::<KeyCodec>()
         {
             let (i, _) = result?;
@@ -968,6 +2446,266 @@ impl<D: Distance> Writer<D> {
 
         Ok(indices)
     }
+
+    // Fetches the ids of the items that were updated since the last build.
+    fn updated_items(&self, rtxn: &RoTxn) -> heed::Result<RoaringBitmap> {
+        let mut updated = RoaringBitmap::new();
+        for result in self
+            .database
+            .remap_types::<PrefixCodec, DecodeIgnore>()
+            .prefix_iter(rtxn, &Prefix::updated(self.index))?
+            .remap_key_type::<KeyCodec>()
+        {
+            let (key, _) = result?;
+            let inserted = updated.push(key.node.item);
+            debug_assert!(inserted, "The keys should be sorted by LMDB");
+        }
+
+        Ok(updated)
+    }
+
+    /// Summarizes the current state of the items and trees of the index.
+    fn snapshot(&self, rtxn: &RoTxn) -> Result<Snapshot> {
+        let items = self.item_indices(rtxn)?;
+        let updated = self.updated_items(rtxn)?;
+
+        let mut hasher = Fnv64::new();
+        for item in &updated {
+            let key = Key::item(self.index, item);
+            if let Some(bytes) = self.database.remap_data_type::<Bytes>().get(rtxn, &key)? {
+                hasher.write(&item.to_be_bytes());
+                hasher.write(&(bytes.len() as u64).to_be_bytes());
+                hasher.write(bytes);
+            }
+        }
+
+        let roots = match self.metadata(rtxn)? {
+            Some(metadata) => Some(metadata.roots.iter().collect()),
+            None => None,
+        };
+        let tree_nodes = self.used_tree_node(rtxn)?;
+
+        Ok(Snapshot { items, updated, updated_vectors: hasher.finish(), roots, tree_nodes })
+    }
+
+    /// Deletes the trees of the build that is not finished yet, if any.
+    fn discard_pending_build(&self, wtxn: &mut RwTxn) -> Result<()> {
+        let key = Key::pending_build(self.index);
+        if let Some(pending) = self.pending_build(wtxn)? {
+            let roots: Vec<_> = pending.roots.iter().collect();
+            log::debug!("Deleting the {} trees of the unfinished build", roots.len());
+            for root in roots {
+                self.delete_tree(wtxn, NodeId::tree(root))?;
+            }
+            self.database.delete(wtxn, &key)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes all the items, the trees and the metadata of the index in a dump that can be
+    /// restored with [`Self::restore`], in another environment or under another index.
+    ///
+    /// The dump is versioned, ends with a checksum, and doesn't depend on the byte order of
+    /// the machine. Its values are in the format of the index, see [`FORMAT_VERSION`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use arroy::{Writer, distances::Euclidean};
+    /// # let (writer, rtxn): (Writer<Euclidean>, heed::RoTxn) = todo!();
+    /// let file = std::fs::File::create("index.dump")?;
+    /// writer.dump(&rtxn, file)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn dump(&self, rtxn: &RoTxn, writer: impl io::Write) -> Result<()> {
+        dump::dump(rtxn, self.database, self.index, self.dimensions, writer)
+    }
+
+    /// Replaces the content of the index by a dump written by [`Self::dump`], possibly
+    /// of another index. The dump must have the distance and the dimensions of this writer.
+    ///
+    /// The values are written as they are read, the transaction must be aborted
+    /// if an error is returned, the index may only contain a part of the dump.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use arroy::{Writer, distances::Euclidean};
+    /// # let (writer, mut wtxn): (Writer<Euclidean>, heed::RwTxn) = todo!();
+    /// let file = std::fs::File::open("index.dump")?;
+    /// writer.restore(&mut wtxn, file)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn restore(&self, wtxn: &mut RwTxn, reader: impl io::Read) -> Result<()> {
+        self.clear(wtxn)?;
+        dump::restore(wtxn, self.database, self.index, self.dimensions, reader)
+    }
+}
+
+impl<D: AnnoyDistance> Writer<D> {
+    /// Replaces the content of the index by the items and the trees of a file written by
+    /// [Annoy](https://github.com/spotify/annoy) with `AnnoyIndex.save`. The trees are
+    /// converted node by node instead of being built again.
+    ///
+    /// The Annoy index must have the number of dimensions of this writer and the metric
+    /// given by [`AnnoyDistance::METRIC`]. The item `i` of Annoy becomes the item `i`.
+    ///
+    /// The split planes of the euclidean and manhattan metrics of Annoy have an offset
+    /// that arroy ignores when searching, the items are found but with a lower recall
+    /// than in Annoy. The trees can be built again with [`Self::builder`] after the import.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use arroy::{Writer, distances::Cosine};
+    /// # let (writer, mut wtxn): (Writer<Cosine>, heed::RwTxn) = todo!();
+    /// writer.import_annoy(&mut wtxn, "index.ann")?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn import_annoy(&self, wtxn: &mut RwTxn, path: impl AsRef<Path>) -> Result<()> {
+        let file = File::open(path)?;
+        // safety: the file must not be modified while it's imported, like when Annoy loads it
+        let mmap = unsafe { Mmap::map(&file)? };
+
+        self.clear(wtxn)?;
+        let (items, roots) =
+            annoy::import(wtxn, self.database, self.index, self.dimensions, &mmap)?;
+        self.write_metadata(wtxn, items, &roots)
+    }
+}
+
+/// The trees generated from a snapshot of the database and
+/// that are ready to be written into it.
+enum BuiltTrees {
+    /// Every item fits in a single descendants node that replaces all the trees.
+    SingleDescendants {
+        items: RoaringBitmap,
+        /// The projection replacing the one of the index, `Some(None)` removes it.
+        new_projection: Option<Option<Projection<'static>>>,
+    },
+    /// The existing trees have been updated and new ones may have been generated.
+    Forest {
+        items: RoaringBitmap,
+        /// The roots of the updated trees.
+        roots: Vec<ItemId>,
+        /// The roots of the newly generated trees.
+        new_roots: Vec<ItemId>,
+        nodes_to_write: Vec<TmpNodesReader>,
+        /// The total number of tree nodes once the nodes are written.
+        nb_tree_nodes: u64,
+        /// The projection replacing the one of the index and all the trees
+        /// that were built on it, `Some(None)` removes it.
+        new_projection: Option<Option<Projection<'static>>>,
+    },
+}
+
+/// A summary of the state of an index used to make sure it wasn't
+/// modified between the moment the trees are built and written.
+#[derive(Debug, PartialEq)]
+struct Snapshot {
+    items: RoaringBitmap,
+    updated: RoaringBitmap,
+    /// A hash of the vectors of the updated items.
+    updated_vectors: u64,
+    roots: Option<Vec<ItemId>>,
+    tree_nodes: RoaringBitmap,
+}
+
+impl Snapshot {
+    /// Returns a hash of the items of the index and of the roots of its trees.
+    ///
+    /// The tree nodes are not part of it as they are modified when checkpointing a build.
+    /// The hash is persisted in the database and must not change between versions.
+    fn fingerprint(&self) -> u64 {
+        let mut hasher = Fnv64::new();
+        for bitmap in [&self.items, &self.updated] {
+            hasher.write(&bitmap.len().to_be_bytes());
+            bitmap.iter().for_each(|item| hasher.write(&item.to_be_bytes()));
+        }
+        hasher.write(&self.updated_vectors.to_be_bytes());
+        match &self.roots {
+            Some(roots) => {
+                hasher.write(&[1]);
+                hasher.write(&(roots.len() as u64).to_be_bytes());
+                roots.iter().for_each(|root| hasher.write(&root.to_be_bytes()));
+            }
+            None => hasher.write(&[0]),
+        }
+        hasher.finish()
+    }
+}
+
+/// The 64-bit FNV-1a hash, unlike the hasher of the standard library
+/// its algorithm is fixed and it can be persisted.
+struct Fnv64 {
+    state: u64,
+}
+
+impl Fnv64 {
+    fn new() -> Fnv64 {
+        Fnv64 { state: 0xCBF2_9CE4_8422_2325 }
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.state = (self.state ^ byte as u64).wrapping_mul(0x0100_0000_01B3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.state
+    }
+}
+
+/// The trees of an index built from a read transaction, see [`ArroyBuilder::prepare`].
+///
+/// The built tree nodes live in temporary files until they are written
+/// into the database by calling [`PreparedBuild::apply`].
+pub struct PreparedBuild<'a, D: Distance> {
+    writer: &'a Writer<D>,
+    options: &'a BuildOption<'a>,
+    snapshot: Snapshot,
+    built: BuiltTrees,
+}
+
+impl<D: Distance> PreparedBuild<'_, D> {
+    /// Writes the prepared trees into the database.
+    ///
+    /// The write transaction only needs to last the time it takes to write the tree nodes.
+    /// Returns an [`Error::OutdatedBuild`] if the items or the trees of the index were
+    /// modified since the build was prepared, in which case it must be prepared again.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use arroy::{Writer, distances::Euclidean};
+    /// # let (writer, env): (Writer<Euclidean>, heed::Env) = todo!();
+    /// use rand::rngs::StdRng;
+    /// use rand::SeedableRng;
+    /// let mut rng = StdRng::seed_from_u64(92);
+    /// let rtxn = env.read_txn()?;
+    /// let mut builder = writer.builder(&mut rng);
+    /// let prepared = builder.prepare(&rtxn)?;
+    /// drop(rtxn);
+    ///
+    /// let mut wtxn = env.write_txn()?;
+    /// prepared.apply(&mut wtxn)?;
+    /// wtxn.commit()?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn apply(self, wtxn: &mut RwTxn) -> Result<()> {
+        let PreparedBuild { writer, options, snapshot, built } = self;
+
+        if writer.snapshot(wtxn)? != snapshot {
+            return Err(Error::OutdatedBuild(writer.index));
+        }
+        if (options.cancel)() {
+            return Err(Error::BuildCancelled);
+        }
+
+        writer.write_built_trees(wtxn, options, built)
+    }
 }
 
 /// Represents the final version of the leafs and contains
@@ -979,6 +2717,18 @@ struct FrozzenReader<'a, D: Distance> {
     concurrent_node_ids: &'a ConcurrentNodeIds,
 }
 
+impl<D: Distance> FrozzenReader<'_, D> {
+    /// Returns the same reader generating the tree node IDs with `node_ids`.
+    fn with_node_ids<'b>(&'b self, node_ids: &'b ConcurrentNodeIds) -> FrozzenReader<'b, D> {
+        FrozzenReader { leafs: self.leafs, trees: self.trees, concurrent_node_ids: node_ids }
+    }
+}
+
+/// Allocates the IDs replacing the `n` local IDs of a tree, in order.
+fn allocate_node_ids(concurrent_node_ids: &ConcurrentNodeIds, n: u64) -> Result<Vec<ItemId>> {
+    (0..n).map(|_| concurrent_node_ids.next()).collect()
+}
+
 /// Randomly and efficiently splits the items into the left and right children vectors.
 fn randomly_split_children<R: Rng>(
     rng: &mut R,
@@ -998,6 +2748,19 @@ fn randomly_split_children<R: Rng>(
     }
 }
 
+/// Returns the inclusive ranges of consecutive ids contained in the bitmap.
+fn consecutive_ranges(bitmap: &RoaringBitmap) -> impl Iterator<Item = (ItemId, ItemId)> + '_ {
+    let mut iter = bitmap.iter().peekable();
+    std::iter::from_fn(move || {
+        let start = iter.next()?;
+        let mut end = start;
+        while let Some(next) = iter.next_if(|&next| Some(next) == end.checked_add(1)) {
+            end = next;
+        }
+        Some((start, end))
+    })
+}
+
 /// Clears everything but the leafs nodes (items).
 /// Starts from the last node and stops at the first leaf.
 fn clear_tree_nodes<D: Distance>(
@@ -1006,6 +2769,10 @@ fn clear_tree_nodes<D: Distance>(
     index: u16,
 ) -> Result<()> {
     database.delete(wtxn, &Key::metadata(index))?;
+    database.delete(wtxn, &Key::pending_build(index))?;
+    database.delete(wtxn, &Key::projection(index))?;
+    database.delete(wtxn, &Key::version(index))?;
+    database.delete(wtxn, &Key::search_tuning(index))?;
     let mut cursor = database
         .remap_types::<PrefixCodec, DecodeIgnore>()
         .prefix_iter_mut(wtxn, &Prefix::tree(index))?
@@ -1024,3 +2791,32 @@ fn split_imbalance(left_indices_len: u64, right_indices_len: u64) -> f64 {
     let f = ls / (ls + rs + f64::EPSILON); // Avoid 0/0
     f.max(1.0 - f)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fnv64() {
+        let mut hasher = Fnv64::new();
+        hasher.write(b"a");
+        assert_eq!(hasher.finish(), 0xAF63_DC4C_8601_EC8C);
+    }
+
+    #[test]
+    fn snapshot_fingerprint_is_stable() {
+        // The fingerprint is persisted with the unfinished builds,
+        // changing it would throw away the checkpoints of every database.
+        let snapshot = Snapshot {
+            items: RoaringBitmap::from_iter([0, 1, 2, 5, 8]),
+            updated: RoaringBitmap::from_iter([5, 8]),
+            updated_vectors: 0x0123_4567_89AB_CDEF,
+            roots: Some(vec![9, 12]),
+            tree_nodes: RoaringBitmap::from_iter([9, 10, 11, 12]),
+        };
+        assert_eq!(snapshot.fingerprint(), 0xF53F_D5C8_6232_4B2D);
+
+        let snapshot = Snapshot { roots: None, ..snapshot };
+        assert_eq!(snapshot.fingerprint(), 0x9E37_0C12_2508_9F0B);
+    }
+}